@@ -1,4 +1,4 @@
-use asterisk::{shell, AsteriskArgs, CliResult, Subcommand};
+use asterisk::{models, shell, threads, AsteriskArgs, CliResult, Subcommand};
 use clap::{CommandFactory, Parser};
 
 //--------------------------------------------------------------------------------------------------
@@ -14,7 +14,9 @@ async fn main() -> CliResult<()> {
         Some(Subcommand::Serve {}) => {
             println!("Coming soon...");
         }
-        Some(Subcommand::Shell {}) => shell::run().await?,
+        Some(Subcommand::Shell { resume }) => shell::run(resume).await?,
+        Some(Subcommand::Models { pull }) => models::run(pull).await?,
+        Some(Subcommand::Threads { export }) => threads::run(export)?,
         None => AsteriskArgs::command().print_help()?,
     }
 
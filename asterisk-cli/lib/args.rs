@@ -16,5 +16,19 @@ pub struct AsteriskArgs {
 #[derive(Debug, Parser)]
 pub enum Subcommand {
     Serve {},
-    Shell {},
+    Shell {
+        /// The ID of a saved thread to resume.
+        #[arg(long)]
+        resume: Option<String>,
+    },
+    Models {
+        /// The name of a model to pull from the Ollama library instead of listing the local models.
+        #[arg(long)]
+        pull: Option<String>,
+    },
+    Threads {
+        /// The ID of a saved thread to print as JSON instead of listing all threads.
+        #[arg(long)]
+        export: Option<String>,
+    },
 }
@@ -32,6 +32,10 @@ pub enum CliError {
     #[error("model error: {0}")]
     ModelError(#[from] ModelError),
 
+    /// JSON error.
+    #[error("json error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
     /// Invalid model error.
     #[error("invalid model: {0}")]
     InvalidModel(String),
@@ -8,7 +8,9 @@ mod styles;
 // Exports
 //--------------------------------------------------------------------------------------------------
 
+pub mod models;
 pub mod shell;
+pub mod threads;
 
 pub use args::*;
 pub use error::*;
@@ -0,0 +1,100 @@
+use std::io::Write;
+
+use asterisk_core::{
+    models::{
+        ollama::{with_default_tag, OllamaAdminClient},
+        registry::ModelRegistry,
+    },
+    utils::{self, Env},
+};
+use colored::Colorize;
+use futures_util::StreamExt;
+
+use crate::CliResult;
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Lists the models of the local Ollama server, or pulls the given one.
+pub async fn run(pull: Option<String>) -> CliResult<()> {
+    utils::load_env(Env::Dev);
+    let admin = open_admin()?;
+
+    if let Some(model) = pull {
+        return self::pull(&admin, &model).await;
+    }
+
+    let models = admin.list_models().await?;
+    if models.is_empty() {
+        println!("{}", "no local models".italic().dimmed());
+        return Ok(());
+    }
+
+    let running = admin.running_models().await?;
+    for model in models {
+        let loaded = running.iter().any(|running| running.name == model.name);
+        println!(
+            "{} {}",
+            model.name.bold(),
+            format!(
+                "{}, {}, {:.1} GB{}",
+                model.details.parameter_size,
+                model.details.quantization_level,
+                model.size as f64 / 1e9,
+                if loaded { ", loaded" } else { "" }
+            )
+            .italic()
+            .dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+/// Pulls the model unless the Ollama server already has it.
+pub async fn pull_if_missing(admin: &OllamaAdminClient, model: &str) -> CliResult<()> {
+    if admin.has_model(model).await? {
+        return Ok(());
+    }
+
+    pull(admin, model).await
+}
+
+/// Pulls the model, printing the progress on a single line.
+pub async fn pull(admin: &OllamaAdminClient, model: &str) -> CliResult<()> {
+    println!(
+        "{}",
+        format!("pulling {}", with_default_tag(model))
+            .italic()
+            .dimmed()
+    );
+
+    let mut progress = admin.pull(model).await?;
+    while let Some(update) = progress.next().await {
+        let update = update?;
+        let line = match update.fraction() {
+            Some(fraction) => format!("{} {:>3.0}%", update.status, fraction * 100.0),
+            None => update.status,
+        };
+
+        print!("\r\x1b[2K{}", line.italic().dimmed());
+        std::io::stdout().flush()?;
+    }
+
+    println!();
+    Ok(())
+}
+
+/// Opens a client for the Ollama server of the first `ollama` profile in the registry, or the
+/// local server if there is none.
+fn open_admin() -> CliResult<OllamaAdminClient> {
+    let registry = ModelRegistry::from_env()?;
+    let admin = registry
+        .profiles
+        .iter()
+        .find_map(|profile| profile.ollama_admin())
+        .unwrap_or_default();
+
+    Ok(admin)
+}
@@ -1,10 +1,14 @@
-use std::{env, io::Write, process};
+use std::{io::Write, process};
 
 use asterisk_core::{
-    agents::dreamer::{channels, ActionMessage, Dreamer, Metrics, ThreadMessage},
+    agents::dreamer::{
+        channels, AgentAction, CompactionStrategy, ContextWindow, Dreamer, DreamerBudget,
+        HistoryContext, Metrics, ThreadMessage, ThreadStore,
+    },
     models::{
-        openai::{ModelType, OpenAILikeModel, OpenAIModel},
-        ModelResult, Prompt, TextModel,
+        self,
+        registry::{ModelRegistry, SamplingParams},
+        ChatModel, ModelFamily,
     },
     utils::{self, Env},
 };
@@ -20,30 +24,29 @@ use tokio::{
     sync::mpsc,
 };
 
-use crate::{CliError, CliResult};
-
-//--------------------------------------------------------------------------------------------------
-// Types
-//--------------------------------------------------------------------------------------------------
-
-#[derive(Clone, Debug)]
-pub enum Model {
-    OpenAIModel(OpenAIModel),
-    OpenAILikeModel(OpenAILikeModel),
-}
+use crate::{threads, CliError, CliResult};
 
 //--------------------------------------------------------------------------------------------------
 // Functions
 //--------------------------------------------------------------------------------------------------
 
-/// Runs the shell.
-pub async fn run() -> CliResult<()> {
+/// Runs the shell, resuming the saved thread with the given ID if any.
+pub async fn run(resume: Option<String>) -> CliResult<()> {
     utils::load_env(Env::Dev);
 
     // Create the model behind the agent.
-    let agent = select_agent()?;
+    let agent = select_agent(resume).await?;
 
-    println!("\n{}", "dreamer agent initialized ".italic().dimmed());
+    println!(
+        "\n{}",
+        format!(
+            "dreamer agent initialized on thread {} ({} prompt tokens)",
+            agent.thread().id(),
+            agent.prompt_tokens()
+        )
+        .italic()
+        .dimmed()
+    );
 
     // Create channels for the agent and external communication
     let (agent_channels, mut external_channels) = channels::create();
@@ -162,14 +165,67 @@ fn handle_metric_message(metrics: Metrics) -> CliResult<()> {
                         .color(*OBSERVATION_TAG_COLOR)
                 );
             }
+            ThreadMessage::Summary(message) => {
+                println!(
+                    "\n{}\n{}",
+                    " thread summary "
+                        .italic()
+                        .color(*SYSTEM_MESSAGE_HEADER_FG_COLOR)
+                        .on_color(*SUMMARY_TAG_COLOR),
+                    message
+                        .get_main_content()
+                        .italic()
+                        .color(*SUMMARY_TAG_COLOR)
+                );
+            }
         },
+        Metrics::Usage(metrics) => {
+            let cost = match metrics.run.cost {
+                Some(cost) => format!(", ~${cost:.4} total"),
+                None => String::new(),
+            };
+            println!(
+                "\n{}",
+                format!(
+                    "{} tokens ({} prompt, {} completion), {} tokens this run{cost}",
+                    metrics.usage.total_tokens,
+                    metrics.usage.prompt_tokens,
+                    metrics.usage.completion_tokens,
+                    metrics.run.total_tokens,
+                )
+                .italic()
+                .dimmed()
+            );
+        }
+        Metrics::BudgetExceeded(limit) => {
+            println!(
+                "\n{}\n{}",
+                " agent stopped "
+                    .italic()
+                    .color(*SYSTEM_MESSAGE_HEADER_FG_COLOR)
+                    .on_color(*NOTIFICATION_TAG_COLOR),
+                limit.to_string().italic().color(*NOTIFICATION_TAG_COLOR)
+            );
+        }
     }
 
     Ok(())
 }
 
-fn handle_action_message(_action: ActionMessage) -> CliResult<()> {
+fn handle_action_message(action: AgentAction) -> CliResult<()> {
     terminal::disable_raw_mode()?;
+    match action {
+        AgentAction::Reply(reply) => {
+            println!(
+                "\n{}\n{}",
+                " agent reply "
+                    .color(*SYSTEM_MESSAGE_HEADER_FG_COLOR)
+                    .on_color(*REPLY_MESSAGE_HEADER_BG_COLOR),
+                reply.message
+            );
+        }
+    }
+
     Ok(())
 }
 
@@ -215,7 +271,9 @@ async fn prompt_and_send(message_tx: &mpsc::UnboundedSender<String>) -> CliResul
     Ok(())
 }
 
-fn select_agent() -> CliResult<Dreamer<Model>> {
+async fn select_agent(resume: Option<String>) -> CliResult<Dreamer<Box<dyn ChatModel>>> {
+    let registry = ModelRegistry::from_env()?;
+
     println!(
         "{}\n",
         " choose a model: "
@@ -223,93 +281,81 @@ fn select_agent() -> CliResult<Dreamer<Model>> {
             .color(*SYSTEM_MESSAGE_HEADER_FG_COLOR)
             .on_color(*SYSTEM_MESSAGE_HEADER_BG_COLOR)
     );
-    println!("{} gpt-4o", " 1.".bold().black().on_white());
-    println!("{} gpt-4o-mini", " 2.".bold().black().on_white());
-    println!(
-        "{} llama-3-1-8b (fireworks)",
-        " 3.".bold().black().on_white()
-    );
-    println!(
-        "{} llama-3-1-70b (fireworks)",
-        " 4.".bold().black().on_white()
-    );
-    println!(
-        "{} llama-3-1-8b (sambanova)",
-        " 5.".bold().black().on_white()
-    );
-    println!(
-        "{} llama-3-1-70b (sambanova)",
-        " 6.".bold().black().on_white()
-    );
+    for (index, profile) in registry.profiles.iter().enumerate() {
+        println!(
+            "{} {}",
+            format!(" {}.", index + 1).bold().black().on_white(),
+            profile.description.as_ref().unwrap_or(&profile.name)
+        );
+    }
     print!(">>> ");
     std::io::stdout().flush().unwrap();
 
     let mut input = String::new();
     std::io::stdin().read_line(&mut input).unwrap();
 
-    let model: Model = match input.trim() {
-        "1" => Model::OpenAIModel(
-            OpenAIModel::builder()
-                .model(ModelType::Gpt4o_2024_08_06)
-                .stop(["<contd>"])
-                .temperature(0.)
-                .seed(0)
-                .build(),
-        ),
-        "2" => Model::OpenAIModel(
-            OpenAIModel::builder()
-                .model(ModelType::Gpt4oMini_2024_07_18)
-                .stop(["<contd>"])
-                .temperature(0.)
-                .seed(0)
-                .build(),
-        ),
-        "" | "3" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("FIREWORKS_API_KEY").unwrap())
-                .base_url(FIREWORKS_URL)
-                .model(FIREWORKS_LLAMA_3_1_8B_MODEL)
-                .stop(["<contd>"])
-                .temperature(0.)
-                .build(),
-        ),
-        "4" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("FIREWORKS_API_KEY").unwrap())
-                .base_url(FIREWORKS_URL)
-                .model(FIREWORKS_LLAMA_3_1_70B_MODEL)
-                .stop(["<contd>"])
-                .temperature(0.)
-                .build(),
-        ),
-        "5" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("SAMBA_NOVA_API_KEY").unwrap())
-                .base_url(SAMBA_NOVA_URL)
-                .model(SAMBA_NOVA_LLAMA_3_1_8B_MODEL)
-                .stop(["<contd>"])
-                .temperature(0.)
-                .build(),
-        ),
-        "6" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("SAMBA_NOVA_API_KEY").unwrap())
-                .base_url(SAMBA_NOVA_URL)
-                .model(SAMBA_NOVA_LLAMA_3_1_70B_MODEL)
-                .stop(["<contd>"])
-                .temperature(0.)
-                .build(),
-        ),
-        _ => return Err(CliError::InvalidModel(input.trim().to_string())),
+    let profile = match input.trim() {
+        "" => registry.default_profile()?,
+        input => input
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| registry.profiles.get(index.checked_sub(1)?))
+            .ok_or_else(|| CliError::InvalidModel(input.to_string()))?,
     };
 
+    // Local models are pulled before the agent starts rather than failing on the first message.
+    if let Some(admin) = profile.ollama_admin() {
+        crate::models::pull_if_missing(&admin, &profile.model).await?;
+    }
+
+    // The agent marks unfinished thoughts with `...<contd>`, so generation stops right there.
+    let model = profile
+        .clone()
+        .with_params(SamplingParams {
+            stop: Some(vec![CONTINUATION_STOP_SEQUENCE.to_string()]),
+            ..Default::default()
+        })
+        .build()?;
+
     println!(
         "\n{} {}",
-        model.get_model().italic().dimmed(),
+        model.model_id().italic().dimmed(),
         "selected ".italic().dimmed()
     );
 
-    let agent = Dreamer::builder().model(model).build();
+    let store = threads::open_store()?;
+    let mut builder = Dreamer::builder();
+    if let Some(id) = resume {
+        builder = builder.thread(store.load(&id)?);
+    }
+
+    // Older messages are summarized once the thread fills most of the model's context, or sooner
+    // if its tokens are only estimated.
+    let family = ModelFamily::of(model.model_id());
+    let counter = models::token_counter(model.model_id());
+    let mut max_tokens = family.context_window() / 4 * 3;
+    if !counter.is_exact() {
+        max_tokens -= max_tokens * ESTIMATE_MARGIN_PERCENT / 100;
+    }
+
+    let window = ContextWindow::default()
+        .max_tokens(max_tokens)
+        .max_observation_tokens(MAX_OBSERVATION_TOKENS)
+        .strategy(CompactionStrategy::Summarize);
+
+    let agent = builder
+        .token_counter(counter)
+        .context_window(window)
+        .model(model)
+        .thread_store(store)
+        .prices(registry.price_table())
+        .context_provider(HistoryContext::default())
+        .budget(
+            DreamerBudget::default()
+                .max_steps(MAX_STEPS)
+                .max_consecutive_thoughts(MAX_CONSECUTIVE_THOUGHTS),
+        )
+        .build();
 
     Ok(agent)
 }
@@ -323,42 +369,25 @@ lazy_static! {
     static ref SYSTEM_MESSAGE_HEADER_FG_COLOR: Color = Color::Black;
     static ref USER_MESSAGE_HEADER_BG_COLOR: Color = Color::Green;
     static ref USER_MESSAGE_HEADER_FG_COLOR: Color = Color::Black;
+    static ref REPLY_MESSAGE_HEADER_BG_COLOR: Color = Color::BrightMagenta;
     static ref THOUGHT_TAG_COLOR: Color = Color::BrightBlack;
     static ref ACTION_TAG_COLOR: Color = Color::BrightCyan;
     static ref NOTIFICATION_TAG_COLOR: Color = Color::BrightYellow;
     static ref OBSERVATION_TAG_COLOR: Color = Color::BrightGreen;
+    static ref SUMMARY_TAG_COLOR: Color = Color::BrightBlue;
 }
 
-const FIREWORKS_URL: &str = "https://api.fireworks.ai/inference/v1/chat/completions";
-const FIREWORKS_LLAMA_3_1_8B_MODEL: &str = "accounts/fireworks/models/llama-v3p1-8b-instruct";
-const FIREWORKS_LLAMA_3_1_70B_MODEL: &str = "accounts/fireworks/models/llama-v3p1-70b-instruct";
-
-const SAMBA_NOVA_URL: &str = "https://api.sambanova.ai/v1/chat/completions";
-const SAMBA_NOVA_LLAMA_3_1_8B_MODEL: &str = "Meta-Llama-3.1-8B-Instruct";
-const SAMBA_NOVA_LLAMA_3_1_70B_MODEL: &str = "Meta-Llama-3.1-70B-Instruct";
+const CONTINUATION_STOP_SEQUENCE: &str = "<contd>";
 
-//--------------------------------------------------------------------------------------------------
-// Methods
-//--------------------------------------------------------------------------------------------------
+/// The most model calls the agent may make per user message.
+const MAX_STEPS: u32 = 50;
 
-impl Model {
-    pub fn get_model(&self) -> String {
-        match self {
-            Model::OpenAIModel(model) => model.get_config().model.clone(),
-            Model::OpenAILikeModel(model) => model.get_config().model.clone(),
-        }
-    }
-}
+/// The most thoughts the agent may have in a row without taking an action.
+const MAX_CONSECUTIVE_THOUGHTS: u32 = 15;
 
-//--------------------------------------------------------------------------------------------------
-// Trait Implementations
-//--------------------------------------------------------------------------------------------------
+/// The most tokens of a tool result the agent sees, so one large result can't fill its context.
+const MAX_OBSERVATION_TOKENS: usize = 4_000;
 
-impl TextModel for Model {
-    async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
-        match self {
-            Model::OpenAIModel(model) => model.prompt(prompt).await,
-            Model::OpenAILikeModel(model) => model.prompt(prompt).await,
-        }
-    }
-}
+/// The share of the context window, in percent, left free when tokens are only estimated, in case
+/// they are undercounted.
+const ESTIMATE_MARGIN_PERCENT: usize = 15;
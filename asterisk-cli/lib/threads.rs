@@ -0,0 +1,71 @@
+use std::{env, path::PathBuf, time::SystemTime};
+
+use asterisk_core::agents::dreamer::{recency_label, FileThreadStore, ThreadStore};
+use colored::Colorize;
+
+use crate::CliResult;
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Lists the saved threads, or prints the one with the given ID as JSON.
+pub fn run(export: Option<String>) -> CliResult<()> {
+    let store = open_store()?;
+
+    if let Some(id) = export {
+        let thread = store.load(&id)?;
+        println!("{}", serde_json::to_string_pretty(&thread)?);
+        return Ok(());
+    }
+
+    let summaries = store.list()?;
+    if summaries.is_empty() {
+        println!("{}", "no saved threads".italic().dimmed());
+        return Ok(());
+    }
+
+    let now = SystemTime::now();
+    for summary in summaries {
+        println!(
+            "{} {}",
+            summary.id.bold(),
+            format!(
+                "{} messages, {}, updated {}",
+                summary.messages,
+                summary.model.as_deref().unwrap_or("no model yet"),
+                recency_label(summary.updated_at, now)
+            )
+            .italic()
+            .dimmed()
+        );
+        if let Some(preview) = summary.preview {
+            println!("  {preview}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens the store the shell saves its threads in.
+///
+/// The threads live in `$ASTERISK_THREADS_DIR`, or `~/.asterisk/threads` when it is not set.
+pub fn open_store() -> CliResult<FileThreadStore> {
+    let dir = match env::var_os(THREADS_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".asterisk")
+            .join("threads"),
+    };
+
+    Ok(FileThreadStore::new(dir)?)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The environment variable that overrides where threads are saved.
+const THREADS_DIR_ENV: &str = "ASTERISK_THREADS_DIR";
@@ -2,44 +2,13 @@ use std::{env, io::Write};
 
 use asterisk_core::{
     models::{
-        ollama::{self, OllamaModel},
-        openai::{self, OpenAILikeModel, OpenAIModel},
-        ModelError, ModelResult, Prompt, PromptMessage, TextStreamModel,
+        registry::ModelRegistry, ModelError, ModelResult, Prompt, PromptMessage, StreamEvent,
+        TextStreamModel,
     },
     utils::{self, Env},
 };
 use colored::Colorize;
-use futures::{stream::BoxStream, StreamExt};
-
-//--------------------------------------------------------------------------------------------------
-// Constants
-//--------------------------------------------------------------------------------------------------
-
-const TOGETHER_URL: &str = "https://api.together.xyz/v1/chat/completions";
-const TOGETHER_LLAMA_3_1_8B_MODEL: &str = "meta-llama/Meta-Llama-3.1-8B-Instruct-Turbo";
-
-const FIREWORKS_URL: &str = "https://api.fireworks.ai/inference/v1/chat/completions";
-const FIREWORKS_LLAMA_3_1_8B_MODEL: &str = "accounts/fireworks/models/llama-v3p1-8b-instruct";
-const FIREWORKS_LLAMA_3_1_70B_MODEL: &str = "accounts/fireworks/models/llama-v3p1-70b-instruct";
-
-const GROQ_URL: &str = "https://api.groq.com/openai/v1/chat/completions";
-const GROQ_LLAMA_3_8B_MODEL: &str = "llama3-8b-8192";
-
-const SAMBA_NOVA_URL: &str = "https://api.sambanova.ai/v1/chat/completions";
-const SAMBA_NOVA_LLAMA_3_1_8B_MODEL: &str = "Meta-Llama-3.1-8B-Instruct";
-
-const CEREBRAS_URL: &str = "https://api.cerebras.ai/v1/chat/completions";
-const CEREBRAS_LLAMA_3_1_8B_MODEL: &str = "llama3.1-8b";
-
-//-------------------------------------------------------------------------------------------------
-// Types
-//-------------------------------------------------------------------------------------------------
-
-enum Model {
-    OpenAIModel(OpenAIModel),
-    OpenAILikeModel(OpenAILikeModel),
-    OllamaModel(OllamaModel),
-}
+use futures::StreamExt;
 
 //-------------------------------------------------------------------------------------------------
 // Main
@@ -50,31 +19,16 @@ async fn main() -> ModelResult<()> {
     utils::load_env(Env::Dev);
     tracing_subscriber::fmt::init();
 
+    let registry = ModelRegistry::from_env()?;
+
     println!("{}\n", " choose a model: ".bold().black().on_bright_cyan());
-    println!("{} gpt-4o", " 1.".bold().black().on_white());
-    println!("{} gpt-4o-mini", " 2.".bold().black().on_white());
-    println!(
-        "{} llama-3-1-8b (fireworks)",
-        " 3.".bold().black().on_white()
-    );
-    println!(
-        "{} llama-3-1-8b (sambanova)",
-        " 4.".bold().black().on_white()
-    );
-    println!(
-        "{} llama-3-1-8b (cerebras)",
-        " 5.".bold().black().on_white()
-    );
-    println!(
-        "{} llama-3-1-8b (together)",
-        " 6.".bold().black().on_white()
-    );
-    println!("{} llama-3-8b (groq)", " 7.".bold().black().on_white());
-    println!("{} llama-3-1-8b (ollama)", " 8.".bold().black().on_white());
-    println!(
-        "{} llama-3-1-70b (fireworks)",
-        " 9.".bold().black().on_white()
-    );
+    for (index, profile) in registry.profiles.iter().enumerate() {
+        println!(
+            "{} {}",
+            format!(" {}.", index + 1).bold().black().on_white(),
+            profile.description.as_ref().unwrap_or(&profile.name)
+        );
+    }
     print!(">>> ");
     std::io::stdout().flush().unwrap();
 
@@ -85,79 +39,21 @@ async fn main() -> ModelResult<()> {
     let mut input = String::new();
     std::io::stdin().read_line(&mut input).unwrap();
 
-    let model: Model = match input.trim() {
-        "1" => Model::OpenAIModel(
-            OpenAIModel::builder()
-                .model(openai::ModelType::Gpt4o_2024_08_06)
-                .temperature(0.)
-                .build(),
-        ),
-        "" | "2" => Model::OpenAIModel(
-            OpenAIModel::builder()
-                .model(openai::ModelType::Gpt4oMini_2024_07_18)
-                .temperature(0.)
-                .build(),
-        ),
-        "3" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("FIREWORKS_API_KEY").unwrap())
-                .base_url(FIREWORKS_URL)
-                .model(FIREWORKS_LLAMA_3_1_8B_MODEL)
-                .temperature(0.)
-                .build(),
-        ),
-        "4" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("SAMBA_NOVA_API_KEY").unwrap())
-                .base_url(SAMBA_NOVA_URL)
-                .model(SAMBA_NOVA_LLAMA_3_1_8B_MODEL)
-                .temperature(0.)
-                .build(),
-        ),
-        "5" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("CEREBRAS_API_KEY").unwrap())
-                .base_url(CEREBRAS_URL)
-                .model(CEREBRAS_LLAMA_3_1_8B_MODEL)
-                .temperature(0.)
-                .build(),
-        ),
-        "6" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("TOGETHER_API_KEY").unwrap())
-                .base_url(TOGETHER_URL)
-                .model(TOGETHER_LLAMA_3_1_8B_MODEL)
-                .temperature(0.)
-                .build(),
-        ),
-        "7" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("GROQ_API_KEY").unwrap())
-                .base_url(GROQ_URL)
-                .model(GROQ_LLAMA_3_8B_MODEL)
-                .temperature(0.)
-                .build(),
-        ),
-        "8" => Model::OllamaModel(
-            OllamaModel::builder()
-                .model(ollama::ModelType::Llama3_1_8B)
-                .temperature(0.)
-                .build(),
-        ),
-        "9" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("FIREWORKS_API_KEY").unwrap())
-                .base_url(FIREWORKS_URL)
-                .model(FIREWORKS_LLAMA_3_1_70B_MODEL)
-                .temperature(0.)
-                .build(),
-        ),
-        _ => return Err(ModelError::custom(anyhow::anyhow!("invalid model"))),
+    let model = match input.trim() {
+        "" => registry.build("gpt-4o-mini")?,
+        input => match input
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| index.checked_sub(1))
+        {
+            Some(index) if index < registry.profiles.len() => registry.profiles[index].build()?,
+            _ => return Err(ModelError::custom(anyhow::anyhow!("invalid model"))),
+        },
     };
 
     println!(
         "\n{}{}",
-        model.get_name().italic().dimmed(),
+        model.model_id().italic().dimmed(),
         " selected".italic().dimmed()
     );
 
@@ -176,10 +72,13 @@ async fn main() -> ModelResult<()> {
         println!("\n{}", " assistant: ".bold().black().on_bright_cyan());
 
         let mut response = String::new();
-        while let Some(chunk) = output.next().await {
-            let chunk = chunk?;
+        while let Some(event) = output.next().await {
+            let StreamEvent::ContentDelta(chunk) = event? else {
+                continue;
+            };
+
             response.push_str(&chunk);
-            std::io::stdout().write_all(&chunk.as_bytes()).unwrap();
+            std::io::stdout().write_all(chunk.as_bytes()).unwrap();
         }
         println!();
 
@@ -190,34 +89,3 @@ async fn main() -> ModelResult<()> {
         }
     }
 }
-
-//-------------------------------------------------------------------------------------------------
-// Methods
-//-------------------------------------------------------------------------------------------------
-
-impl Model {
-    fn get_name(&self) -> String {
-        match self {
-            Model::OpenAIModel(model) => model.get_config().model.to_string(),
-            Model::OpenAILikeModel(model) => model.get_config().model.to_string(),
-            Model::OllamaModel(model) => model.get_config().model.to_string(),
-        }
-    }
-}
-
-//-------------------------------------------------------------------------------------------------
-// Trait Implementations
-//-------------------------------------------------------------------------------------------------
-
-impl TextStreamModel for Model {
-    async fn prompt_stream(
-        &self,
-        prompt: impl Into<Prompt> + Send,
-    ) -> ModelResult<BoxStream<'static, ModelResult<String>>> {
-        match self {
-            Model::OpenAIModel(model) => model.prompt_stream(prompt).await,
-            Model::OpenAILikeModel(model) => model.prompt_stream(prompt).await,
-            Model::OllamaModel(model) => model.prompt_stream(prompt).await,
-        }
-    }
-}
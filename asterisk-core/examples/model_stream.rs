@@ -1,5 +1,5 @@
 use asterisk_core::{
-    models::{openai::OpenAIModel, ModelResult, TextStreamModel},
+    models::{openai::OpenAIModel, ModelResult, StreamEvent, TextStreamModel},
     prompt,
     utils::{self, Env},
 };
@@ -28,8 +28,11 @@ async fn main() -> ModelResult<()> {
     let mut output = model.prompt_stream(prompt).await?;
 
     let mut response = String::new();
-    while let Some(chunk) = output.next().await {
-        let chunk = chunk?;
+    while let Some(event) = output.next().await {
+        let StreamEvent::ContentDelta(chunk) = event? else {
+            continue;
+        };
+
         println!("{}", chunk);
         response.push_str(&chunk);
     }
@@ -0,0 +1,82 @@
+use asterisk_core::{
+    models::{
+        openai::{self, OpenAIModel, RequestMessages},
+        ModelError, ModelResult,
+    },
+    prompt,
+    tools::{registry::ToolRegistry, ToolResult, TypedTool},
+    utils::{self, Env},
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio_util::sync::CancellationToken;
+
+//-------------------------------------------------------------------------------------------------
+// Types
+//-------------------------------------------------------------------------------------------------
+
+struct Weather;
+
+#[derive(Deserialize, JsonSchema)]
+struct WeatherArgs {
+    /// The city to get the weather for.
+    city: String,
+}
+
+//-------------------------------------------------------------------------------------------------
+// Trait Implementations
+//-------------------------------------------------------------------------------------------------
+
+impl TypedTool for Weather {
+    type Args = WeatherArgs;
+
+    fn name(&self) -> String {
+        "get_weather".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Gets the current weather in a city.".to_string()
+    }
+
+    async fn call(&self, args: WeatherArgs, _: CancellationToken) -> ToolResult<String> {
+        Ok(format!("It is sunny in {}.", args.city))
+    }
+}
+
+//-------------------------------------------------------------------------------------------------
+// Main
+//-------------------------------------------------------------------------------------------------
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ModelResult<()> {
+    utils::load_env(Env::Dev);
+    tracing_subscriber::fmt::init();
+
+    let mut tools = ToolRegistry::new();
+    tools.register(Weather);
+
+    let model = OpenAIModel::builder().tools(tools.openai_tools()).build();
+
+    let mut messages = RequestMessages::from(prompt! {
+        system: "You are a helpful assistant.",
+        user: "What is the weather in Tokyo?",
+    });
+
+    let response = model
+        .call_with_tools(&mut messages, |tool_call: openai::ToolCall| {
+            let tools = &tools;
+            async move {
+                let args: Map<String, Value> = tool_call.function.parse_arguments()?;
+                tools
+                    .execute(&tool_call.function.name, args, CancellationToken::new())
+                    .await
+                    .map_err(ModelError::custom)
+            }
+        })
+        .await?;
+
+    println!("chat model output = {:#?}", response.content());
+
+    Ok(())
+}
@@ -1,36 +1,16 @@
-use std::{
-    env,
-    io::{BufRead, BufReader, Write},
-};
+use std::io::{BufRead, BufReader, Write};
 
 use asterisk_core::{
     models::{
-        openai::{self, OpenAILikeModel, OpenAIModel},
-        ModelError, ModelResult, Prompt, PromptMessage, TextStreamModel,
+        registry::ModelRegistry, ModelError, ModelResult, Prompt, PromptMessage, StreamEvent,
+        TextStreamModel,
     },
     utils::{self, Env},
 };
 use colored::Colorize;
-use futures::{stream::BoxStream, StreamExt};
+use futures::StreamExt;
 use regex::RegexBuilder;
 
-//--------------------------------------------------------------------------------------------------
-// Constants
-//--------------------------------------------------------------------------------------------------
-
-const FIREWORKS_URL: &str = "https://api.fireworks.ai/inference/v1/chat/completions";
-const FIREWORKS_LLAMA_3_1_8B_MODEL: &str = "accounts/fireworks/models/llama-v3p1-8b-instruct";
-const FIREWORKS_LLAMA_3_1_70B_MODEL: &str = "accounts/fireworks/models/llama-v3p1-70b-instruct";
-
-//-------------------------------------------------------------------------------------------------
-// Types
-//-------------------------------------------------------------------------------------------------
-
-enum Model {
-    OpenAIModel(OpenAIModel),
-    OpenAILikeModel(OpenAILikeModel),
-}
-
 //-------------------------------------------------------------------------------------------------
 // Main
 //-------------------------------------------------------------------------------------------------
@@ -40,58 +20,37 @@ async fn main() -> ModelResult<()> {
     utils::load_env(Env::Dev);
     tracing_subscriber::fmt::init();
 
+    let registry = ModelRegistry::from_env()?;
+
     println!("{}\n", " choose a model: ".bold().black().on_bright_cyan());
-    println!("{} gpt-4o", " 1.".bold().black().on_white());
-    println!("{} gpt-4o-mini", " 2.".bold().black().on_white());
-    println!(
-        "{} llama-3-1-8b (fireworks)",
-        " 3.".bold().black().on_white()
-    );
-    println!(
-        "{} llama-3-1-70b (fireworks)",
-        " 4.".bold().black().on_white()
-    );
+    for (index, profile) in registry.profiles.iter().enumerate() {
+        println!(
+            "{} {}",
+            format!(" {}.", index + 1).bold().black().on_white(),
+            profile.description.as_ref().unwrap_or(&profile.name)
+        );
+    }
     print!(">>> ");
     std::io::stdout().flush().unwrap();
 
     let mut input = String::new();
     std::io::stdin().read_line(&mut input).unwrap();
 
-    let model: Model = match input.trim() {
-        "1" => Model::OpenAIModel(
-            OpenAIModel::builder()
-                .model(openai::ModelType::Gpt4o_2024_08_06)
-                .temperature(0.)
-                .build(),
-        ),
-        "2" => Model::OpenAIModel(
-            OpenAIModel::builder()
-                .model(openai::ModelType::Gpt4oMini_2024_07_18)
-                .temperature(0.)
-                .build(),
-        ),
-        "" | "3" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("FIREWORKS_API_KEY").unwrap())
-                .base_url(FIREWORKS_URL)
-                .model(FIREWORKS_LLAMA_3_1_8B_MODEL)
-                .temperature(0.)
-                .build(),
-        ),
-        "4" => Model::OpenAILikeModel(
-            OpenAILikeModel::builder()
-                .api_key(env::var("FIREWORKS_API_KEY").unwrap())
-                .base_url(FIREWORKS_URL)
-                .model(FIREWORKS_LLAMA_3_1_70B_MODEL)
-                .temperature(0.)
-                .build(),
-        ),
-        _ => return Err(ModelError::custom(anyhow::anyhow!("invalid model"))),
+    let model = match input.trim() {
+        "" => registry.build_default()?,
+        input => match input
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| index.checked_sub(1))
+        {
+            Some(index) if index < registry.profiles.len() => registry.profiles[index].build()?,
+            _ => return Err(ModelError::custom(anyhow::anyhow!("invalid model"))),
+        },
     };
 
     println!(
         "\n{}{}",
-        model.get_name().italic().dimmed(),
+        model.model_id().italic().dimmed(),
         " selected".italic().dimmed()
     );
 
@@ -101,10 +60,10 @@ async fn main() -> ModelResult<()> {
         let mut input = String::new();
 
         let stdin = std::io::stdin();
-        let mut reader = BufReader::new(stdin).lines();
+        let reader = BufReader::new(stdin).lines();
 
         let mut count = 0;
-        while let Some(line) = reader.next() {
+        for line in reader {
             let line = line.unwrap();
             if line.is_empty() {
                 if count < 1 {
@@ -123,10 +82,13 @@ async fn main() -> ModelResult<()> {
         println!("\n{}", " assistant: ".bold().black().on_bright_cyan());
 
         let mut response = String::new();
-        while let Some(chunk) = output.next().await {
-            let chunk = chunk?;
+        while let Some(event) = output.next().await {
+            let StreamEvent::ContentDelta(chunk) = event? else {
+                continue;
+            };
+
             response.push_str(&chunk);
-            std::io::stdout().write_all(&chunk.as_bytes()).unwrap();
+            std::io::stdout().write_all(chunk.as_bytes()).unwrap();
         }
         println!();
     }
@@ -179,35 +141,6 @@ fn parse_prompt(mut prompt: &str) -> Prompt {
     prompt_builder
 }
 
-//-------------------------------------------------------------------------------------------------
-// Methods
-//-------------------------------------------------------------------------------------------------
-
-impl Model {
-    fn get_name(&self) -> String {
-        match self {
-            Model::OpenAIModel(model) => model.get_config().model.to_string(),
-            Model::OpenAILikeModel(model) => model.get_config().model.to_string(),
-        }
-    }
-}
-
-//-------------------------------------------------------------------------------------------------
-// Trait Implementations
-//-------------------------------------------------------------------------------------------------
-
-impl TextStreamModel for Model {
-    async fn prompt_stream(
-        &self,
-        prompt: impl Into<Prompt> + Send,
-    ) -> ModelResult<BoxStream<'static, ModelResult<String>>> {
-        match self {
-            Model::OpenAIModel(model) => model.prompt_stream(prompt).await,
-            Model::OpenAILikeModel(model) => model.prompt_stream(prompt).await,
-        }
-    }
-}
-
 //-------------------------------------------------------------------------------------------------
 // Tests
 //-------------------------------------------------------------------------------------------------
@@ -1,18 +1,34 @@
 //! First attempt at creating a reliable agent.
 
-use std::collections::HashMap;
+use std::{collections::HashSet, sync::Arc, time::SystemTime};
 
-use serde_json::Map;
-use tokio::{sync::mpsc, task::JoinHandle};
+use serde_json::{Map, Value};
+use tokio::{
+    sync::mpsc,
+    task::JoinHandle,
+    time::{self, Instant},
+};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    models::{openai::OpenAIModel, TextModel},
-    tools::{self, inbox::Inbox, Tool},
+    models::{
+        openai::OpenAIModel, EstimatingCounter, ModelResponse, Prompt, PromptMessage, TextModel,
+        TokenCounter, UsageLedger,
+    },
+    tools::{
+        self,
+        inbox::Inbox,
+        outbox::{Outbox, OUTBOX_TOOL_NAME},
+        registry::ToolRegistry,
+        ToolError, ToolResult,
+    },
 };
 
 use super::{
-    ActionMessage, AgentSideChannels, DreamerBuilder, DreamerError, DreamerResult, Metrics,
-    ThoughtMessage, Thread, ThreadMessage,
+    ActionMessage, AgentAction, AgentSideChannels, BudgetLimit, BudgetTracker, CompactionStrategy,
+    ContextMessage, ContextProvider, ContextWindow, DreamerBudget, DreamerBuilder, DreamerResult,
+    Metrics, ThoughtMessage, Thread, ThreadMessage, ThreadStore, UsageMetrics, ACTION_TAG,
+    DEFAULT_CONTEXT_LIMIT, SUMMARY_INSTRUCTION, THOUGHT_TAG,
 };
 
 //-------------------------------------------------------------------------------------------------
@@ -20,7 +36,7 @@ use super::{
 //--------------------------------------------------------------------------------------------------
 
 /// The system instruction for the dreamer agent.
-pub const DREAMER_SYSTEM_INSTRUCTION: &str = include_str!("instructions/dreamer-0.1.1.md");
+pub const DREAMER_SYSTEM_INSTRUCTION: &str = include_str!("instructions/dreamer-0.1.4.md");
 
 /// The notification message from the user.
 pub const NOTIFICATION_USER_MESSAGE: &str = "Message from the user!";
@@ -68,16 +84,55 @@ pub struct Dreamer<M = OpenAIModel> {
     /// The thread of conversation.
     pub(crate) thread: Thread,
 
-    /// The tool for reading the user message.
+    /// A handle to the tool for reading the user message.
     pub(crate) inbox: Inbox,
 
-    /// The provided tools the assistant has access to.
-    pub(crate) provided_tools: HashMap<String, Box<dyn Tool + Send + Sync>>,
+    /// The tools the assistant has access to, including the inbox and outbox.
+    pub(crate) tools: ToolRegistry,
+
+    /// The sources of the context shown to the agent after each observation.
+    pub(crate) context_providers: Vec<Box<dyn ContextProvider>>,
+
+    /// The most context items shown to the agent at once.
+    pub(crate) context_limit: usize,
+
+    /// Where the thread is saved as it changes, if anywhere.
+    pub(crate) store: Option<Arc<dyn ThreadStore>>,
+
+    /// When the thread had last changed as of the last time it was saved.
+    pub(crate) saved_at: Option<SystemTime>,
+
+    /// The limits on the size of the thread sent to the model.
+    pub(crate) window: ContextWindow,
+
+    /// Counts the tokens of the thread sent to the model.
+    pub(crate) counter: Arc<dyn TokenCounter>,
+
+    /// The token usage and estimated spend of the agent.
+    pub(crate) usage: UsageLedger,
+
+    /// The limits on the work the agent does.
+    pub(crate) budget: DreamerBudget,
+
+    /// The work done since the agent last woke up.
+    pub(crate) tracker: BudgetTracker,
+
+    /// The tool call the agent asked for that has yet to run.
+    pub(crate) pending_call: Option<PendingToolCall>,
 
     /// Whether the agent is idle.
     pub(crate) idle: bool,
 }
 
+/// A tool call parsed from an action message.
+pub(crate) struct PendingToolCall {
+    /// The name of the tool.
+    name: String,
+
+    /// The arguments to call the tool with.
+    args: Map<String, Value>,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Methods
 //--------------------------------------------------------------------------------------------------
@@ -92,11 +147,25 @@ impl Dreamer {
 impl<M> Dreamer<M> {
     /// Creates a new `Dreamer` with specified model.
     pub fn new(model: M, system_instruction: String) -> Self {
+        let inbox = Inbox::default();
+        let mut tools = ToolRegistry::new();
+        register_builtin_tools(&mut tools, &inbox);
+
         Self {
             model,
-            thread: Thread::new(system_instruction),
-            inbox: Inbox::default(),
-            provided_tools: HashMap::new(),
+            thread: Thread::new(tools.render_into(&system_instruction)),
+            inbox,
+            tools,
+            context_providers: Vec::new(),
+            context_limit: DEFAULT_CONTEXT_LIMIT,
+            store: None,
+            saved_at: None,
+            window: ContextWindow::default(),
+            counter: Arc::new(EstimatingCounter::default()),
+            usage: UsageLedger::default(),
+            budget: DreamerBudget::default(),
+            tracker: BudgetTracker::default(),
+            pending_call: None,
             idle: true,
         }
     }
@@ -107,25 +176,77 @@ impl<M> Dreamer<M> {
         M: TextModel + Send + Sync + 'static,
     {
         tokio::spawn(async move {
+            // Replies go out over the action channel.
+            self.tools
+                .register(Outbox::<AgentAction>::new(channels.action_tx.clone()));
+
             loop {
+                // Save whatever changed since the last time round.
+                self.save_thread().await;
+
                 if self.idle {
-                    // Check if there is an incoming message from the outside world
-                    if let Some(message) = channels.message_rx.recv().await {
+                    // Wait for an incoming message from the outside world, stopping once no more
+                    // can arrive.
+                    let Some(message) = channels.message_rx.recv().await else {
+                        return Ok(());
+                    };
+
+                    self.handle_incoming_message(message, &channels.metrics_tx)?;
+                    continue;
+                }
+
+                let deadline = self.budget.deadline_at(&self.tracker);
+
+                // Run the tool the agent asked for.
+                if let Some(call) = self.pending_call.take() {
+                    let cancel = CancellationToken::new();
+                    let mut interrupted_by = None;
+                    let result = tokio::select! {
+                        result = self.tools.execute(&call.name, call.args, cancel.clone()) => result,
+                        // A message from the outside world interrupts the tool.
+                        Some(message) = channels.message_rx.recv() => {
+                            interrupted_by = Some(message);
+                            Err(ToolError::Cancelled)
+                        }
+                        // So does the deadline passing.
+                        _ = sleep_until(deadline) => Err(ToolError::Cancelled),
+                    };
+
+                    // Let any work the tool left behind know it is no longer wanted.
+                    cancel.cancel();
+
+                    self.handle_tool_result(&call.name, result, &channels.metrics_tx)?;
+                    self.update_context().await;
+                    if let Some(message) = interrupted_by {
                         self.handle_incoming_message(message, &channels.metrics_tx)?;
                     }
 
                     continue;
                 }
 
+                // Stop if the agent has run out of budget.
+                if let Some(limit) = self.budget.check(&self.tracker, &self.usage.total()) {
+                    self.handle_budget_exceeded(limit, &channels.metrics_tx)?;
+                    continue;
+                }
+
+                // Make room in the thread if it no longer fits the window.
+                self.compact_thread(&channels.metrics_tx).await?;
+
                 tokio::select! {
                     // API call to the LLM
                     response = self.call() => {
-                        self.handle_model_response(response?, &channels.metrics_tx)?;
+                        let response = response?;
+                        self.tracker.step();
+                        self.record_usage(&response, &channels.metrics_tx)?;
+                        self.handle_model_response(response.content, &channels.metrics_tx)?;
                     }
                     // Incoming message from the outside world
-                    message = channels.message_rx.recv() => if let Some(message) = message {
+                    Some(message) = channels.message_rx.recv() => {
                         self.handle_incoming_message(message, &channels.metrics_tx)?;
                     }
+                    // The deadline passed mid-call, which the budget check picks up next.
+                    _ = sleep_until(deadline) => {}
                 }
             }
         })
@@ -133,6 +254,79 @@ impl<M> Dreamer<M> {
 }
 
 impl<M> Dreamer<M> {
+    /// Returns the token usage and estimated spend of the agent so far.
+    pub fn usage(&self) -> &UsageLedger {
+        &self.usage
+    }
+
+    /// Returns the thread of conversation.
+    pub fn thread(&self) -> &Thread {
+        &self.thread
+    }
+
+    /// Returns the number of tokens in the prompt the thread makes, as counted by the token
+    /// counter of the agent.
+    pub fn prompt_tokens(&self) -> usize {
+        self.counter
+            .count_prompt(&Prompt::from(self.thread.clone()))
+    }
+
+    /// Saves the thread to the store if it changed since it was last saved.
+    ///
+    /// Stores write to disk, so the save runs on the blocking thread pool with a copy of the
+    /// thread. A failed save is logged rather than stopping the agent, and is retried on the next
+    /// change.
+    async fn save_thread(&mut self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+
+        let updated_at = self.thread.updated_at();
+        if self.saved_at == Some(updated_at) {
+            return;
+        }
+
+        let (store, thread) = (Arc::clone(store), self.thread.clone());
+        match tokio::task::spawn_blocking(move || store.save(&thread)).await {
+            Ok(Ok(())) => self.saved_at = Some(updated_at),
+            Ok(Err(error)) => {
+                tracing::warn!("failed to save thread {}: {error}", self.thread.id())
+            }
+            Err(error) => tracing::warn!("failed to save thread {}: {error}", self.thread.id()),
+        }
+    }
+
+    /// Records the token usage of a model response and reports it to the metrics channel.
+    fn record_usage(
+        &mut self,
+        response: &ModelResponse,
+        metrics_tx: &mpsc::UnboundedSender<Metrics>,
+    ) -> DreamerResult<()> {
+        if self.thread.model() != Some(response.model.as_str()) {
+            self.thread.set_model(response.model.as_str());
+        }
+
+        let Some(usage) = response.usage else {
+            return Ok(());
+        };
+
+        let record = self
+            .usage
+            .record(self.thread.id(), response.model.as_str(), usage);
+
+        // Send metrics to the metrics channel.
+        metrics_tx.send(Metrics::Usage(Box::new(UsageMetrics {
+            run: self.usage.total(),
+            thread: self.usage.thread(&record.thread_id),
+            thread_id: record.thread_id,
+            model: record.model,
+            usage: record.usage,
+            cost: record.cost,
+        })))?;
+
+        Ok(())
+    }
+
     /// Handles the model response.
     fn handle_model_response(
         &mut self,
@@ -144,18 +338,36 @@ impl<M> Dreamer<M> {
         }
 
         // Parse the response into a ThreadMessage.
-        let message: ThreadMessage = response.parse()?;
+        let message = match response.parse::<ThreadMessage>() {
+            Ok(message) => message,
+            // A reply without a tag is reported back so the agent can correct it.
+            Err(_) => {
+                let error = ToolError::InvalidAction(format!(
+                    "the reply must start with `{THOUGHT_TAG}` or `{ACTION_TAG}`"
+                ));
+                return self.handle_invalid_reply(response, error, metrics_tx);
+            }
+        };
 
-        // Handle the message based on its type.
+        // Handle the message based on its type. Actions are added to the thread by
+        // `handle_action` so that they come before their observations.
         match message.clone() {
-            ThreadMessage::Thought(thought) => self.handle_thought(thought, metrics_tx)?,
+            ThreadMessage::Thought(thought) => {
+                self.handle_thought(thought, metrics_tx)?;
+
+                // Add message to the thread.
+                self.thread.push_message(message);
+            }
             ThreadMessage::Action(action) => self.handle_action(action, metrics_tx)?,
-            _ => return Err(DreamerError::InvalidResponseMessage(message)),
+            // Only the runtime writes the other kinds of messages, so the agent is told so.
+            _ => {
+                let error = ToolError::InvalidAction(format!(
+                    "only `{THOUGHT_TAG}` and `{ACTION_TAG}` messages can be sent"
+                ));
+                self.handle_invalid_reply(response, error, metrics_tx)?;
+            }
         }
 
-        // Add message to the thread.
-        self.thread.push_message(message);
-
         Ok(())
     }
 
@@ -177,12 +389,33 @@ impl<M> Dreamer<M> {
         // Add message to the thread.
         self.thread.push_message(message);
 
+        // Start tracking the work on the message.
+        self.tracker.reset(&self.usage.total());
+
         // Make the agent busy.
         self.make_busy();
 
         Ok(())
     }
 
+    /// Handles the budget being exceeded by stopping the agent.
+    fn handle_budget_exceeded(
+        &mut self,
+        limit: BudgetLimit,
+        metrics_tx: &mpsc::UnboundedSender<Metrics>,
+    ) -> DreamerResult<()> {
+        // Let the agent know why it was stopped when it wakes up again.
+        self.thread
+            .push_message(ThreadMessage::notification(format!("Stopped: {limit}.")));
+
+        // Send metrics to the metrics channel.
+        metrics_tx.send(Metrics::BudgetExceeded(limit))?;
+
+        self.make_idle();
+
+        Ok(())
+    }
+
     /// Handles the thought message.
     fn handle_thought(
         &mut self,
@@ -194,6 +427,8 @@ impl<M> Dreamer<M> {
             thought.clone(),
         )))?;
 
+        self.tracker.thought();
+
         if thought.is_incomplete() {
             self.make_busy();
         } else {
@@ -218,44 +453,194 @@ impl<M> Dreamer<M> {
             action.clone(),
         )))?;
 
-        let (name, _args) = tools::parse_tool(action.get_main_content())?;
+        self.tracker.action();
 
-        match name.as_str() {
-            "inbox" => {
-                // Execute the tool and get the observation.
-                let observation = self.inbox.execute(Map::new())?;
+        match tools::parse_tool(action.get_main_content()) {
+            // The tool is run by the main loop so that it can be interrupted.
+            Ok((name, args)) => {
+                self.pending_call = Some(PendingToolCall { name, args });
+                self.make_busy();
+            }
+            // An action that can't be parsed is reported back so the agent can correct it.
+            Err(error) => self.handle_tool_result("", Err(error), metrics_tx)?,
+        }
 
-                // Create the observation message.
-                let message = ThreadMessage::observation(observation);
+        Ok(())
+    }
 
-                // Send metrics to the metrics channel.
-                metrics_tx.send(Metrics::ThreadMessage(message.clone()))?;
+    /// Handles a reply that is neither a thought nor an action.
+    ///
+    /// The reply is kept in the thread as an action, so the agent sees what the error it is told
+    /// about refers to.
+    fn handle_invalid_reply(
+        &mut self,
+        reply: String,
+        error: ToolError,
+        metrics_tx: &mpsc::UnboundedSender<Metrics>,
+    ) -> DreamerResult<()> {
+        let action = ThreadMessage::action(reply);
 
-                // Add observation to the thread.
-                self.thread.push_message(message);
+        // Send metrics to the metrics channel.
+        metrics_tx.send(Metrics::ThreadMessage(action.clone()))?;
 
-                // Make the agent busy.
-                self.make_busy();
+        // Add the reply to the thread.
+        self.thread.push_message(action);
+
+        self.handle_tool_result("", Err(error), metrics_tx)
+    }
+
+    /// Handles the result of a tool call.
+    fn handle_tool_result(
+        &mut self,
+        name: &str,
+        result: ToolResult<String>,
+        metrics_tx: &mpsc::UnboundedSender<Metrics>,
+    ) -> DreamerResult<()> {
+        // Failures become an observation the agent can react to.
+        let observation = result.unwrap_or_else(|error| error.to_observation());
+        let observation = self
+            .window
+            .truncate_observation(observation, self.counter.as_ref());
 
-                return Ok(());
+        // Create the observation message.
+        let message = ThreadMessage::observation(observation);
+
+        // Send metrics to the metrics channel.
+        metrics_tx.send(Metrics::ThreadMessage(message.clone()))?;
+
+        // Add observation to the thread.
+        self.thread.push_message(message);
+
+        // A reply hands the turn back to the user, otherwise the agent reacts to the observation.
+        if name == OUTBOX_TOOL_NAME {
+            self.make_idle();
+        } else {
+            self.make_busy();
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the context with the items the providers find most relevant to the latest
+    /// observation.
+    ///
+    /// A provider that fails is skipped so that the agent can carry on without its context.
+    async fn update_context(&mut self) {
+        if self.context_providers.is_empty() {
+            return;
+        }
+
+        let Some(query) = self.thread.history().iter().rev().find_map(|entry| {
+            matches!(entry.message, ThreadMessage::Observation(_))
+                .then(|| entry.message.get_main_content().to_string())
+        }) else {
+            return;
+        };
+
+        let mut items = Vec::new();
+        for provider in &self.context_providers {
+            match provider
+                .retrieve(&query, &self.thread, self.context_limit)
+                .await
+            {
+                Ok(found) => items.extend(found),
+                Err(error) => tracing::warn!("context provider failed: {error}"),
             }
-            "memories" => {}
-            "outbox" => {}
-            _ => {}
         }
 
-        self.make_idle();
+        // The most relevant first, without repeating the same item from different providers.
+        items.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let mut seen = HashSet::new();
+        items.retain(|item| seen.insert(item.content.clone()));
+        items.truncate(self.context_limit);
+
+        // Context that is no longer relevant is dropped rather than left to mislead the agent.
+        if items.is_empty() {
+            self.thread.clear_context();
+        } else {
+            self.thread
+                .update_context(ContextMessage::from_items(&items, SystemTime::now()));
+        }
+    }
+
+    /// Evicts the oldest messages from the thread if it no longer fits the window, replacing them
+    /// with a summary if the window asks for one.
+    async fn compact_thread(
+        &mut self,
+        metrics_tx: &mpsc::UnboundedSender<Metrics>,
+    ) -> DreamerResult<()>
+    where
+        M: TextModel + Send + Sync + 'static,
+    {
+        let Some(indices) = self.window.plan(&self.thread, self.counter.as_ref()) else {
+            return Ok(());
+        };
+
+        let summary = match self.window.strategy {
+            CompactionStrategy::Drop => None,
+            CompactionStrategy::Summarize => match self.summarize(&indices, metrics_tx).await {
+                Ok(summary) => (!summary.is_empty()).then(|| ThreadMessage::summary(summary)),
+                Err(error) => {
+                    tracing::warn!("failed to summarize the thread, dropping instead: {error}");
+                    None
+                }
+            },
+        };
+
+        if let Some(summary) = &summary {
+            metrics_tx.send(Metrics::ThreadMessage(summary.clone()))?;
+        }
+
+        self.thread.compact(&indices, summary);
 
         Ok(())
     }
 
+    /// Asks the model to summarize the messages of the thread at the given indices.
+    ///
+    /// The messages are summarized a part at a time, each along with the summary of the parts
+    /// before it, so that no request outgrows the window.
+    async fn summarize(
+        &mut self,
+        indices: &[usize],
+        metrics_tx: &mpsc::UnboundedSender<Metrics>,
+    ) -> DreamerResult<String>
+    where
+        M: TextModel + Send + Sync + 'static,
+    {
+        let parts = self
+            .window
+            .summary_parts(&self.thread, indices, self.counter.as_ref());
+
+        let mut summary = String::new();
+        for part in parts {
+            let transcript = match summary.is_empty() {
+                true => part,
+                false => format!(
+                    "{}\n\n{part}",
+                    ThreadMessage::summary(summary).get_full_content()
+                ),
+            };
+
+            let mut prompt = Prompt::new();
+            prompt.push(PromptMessage::system(SUMMARY_INSTRUCTION));
+            prompt.push(PromptMessage::user(transcript));
+
+            let response = self.model.prompt_with_usage(prompt).await?;
+            self.record_usage(&response, metrics_tx)?;
+            summary = response.content.trim().to_string();
+        }
+
+        Ok(summary)
+    }
+
     /// Calls the model by sending the thread to the model and receiving a response.
-    async fn call(&self) -> DreamerResult<String>
+    async fn call(&self) -> DreamerResult<ModelResponse>
     where
         M: TextModel + Send + Sync + 'static,
     {
         self.model
-            .prompt(self.thread.clone())
+            .prompt_with_usage(self.thread.clone())
             .await
             .map_err(Into::into)
     }
@@ -271,14 +656,465 @@ impl<M> Dreamer<M> {
     }
 }
 
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Registers the tools every dreamer has.
+///
+/// The outbox starts out disconnected and is replaced by one connected to the action channel when
+/// the agent runs.
+pub(crate) fn register_builtin_tools(tools: &mut ToolRegistry, inbox: &Inbox) {
+    tools.register(inbox.clone());
+    tools.register(Outbox::<AgentAction>::default());
+}
+
+/// Waits until the deadline, or forever if there is none.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Tests
 //--------------------------------------------------------------------------------------------------
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use crate::models::{ModelResult, Prompt, TokenUsage};
+
+    use super::*;
+
+    /// A model that never stops thinking.
+    struct Overthinker;
+
+    impl TextModel for Overthinker {
+        async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
+            Ok(self.prompt_with_usage(prompt).await?.content)
+        }
+
+        async fn prompt_with_usage(
+            &self,
+            _prompt: impl Into<Prompt> + Send,
+        ) -> ModelResult<ModelResponse> {
+            Ok(ModelResponse {
+                content: "[thought]\nI need to think about this some more...".to_string(),
+                model: "overthinker".to_string(),
+                usage: Some(TokenUsage::new(8, 2)),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_stops_when_budget_exceeded() {
+        let agent = Dreamer::builder()
+            .model(Overthinker)
+            .budget(DreamerBudget::default().max_consecutive_thoughts(3))
+            .build();
+
+        let (agent_channels, mut external_channels) = super::super::channels::create();
+        let handle = agent.run(agent_channels);
+        external_channels
+            .message_tx
+            .send("Hello".to_string())
+            .unwrap();
+
+        let mut thoughts = 0;
+        let mut usage = None;
+        let limit = loop {
+            match external_channels.metrics_rx.recv().await.unwrap() {
+                Metrics::ThreadMessage(ThreadMessage::Thought(_)) => thoughts += 1,
+                Metrics::Usage(metrics) => usage = Some(metrics.run),
+                Metrics::BudgetExceeded(limit) => break limit,
+                _ => {}
+            }
+        };
+
+        assert_eq!(limit, BudgetLimit::ConsecutiveThoughts(3));
+        assert_eq!(thoughts, 3);
+        assert_eq!(usage.unwrap().total_tokens, 30);
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_token_budget_resets_per_message() {
+        let agent = Dreamer::builder()
+            .model(Overthinker)
+            .budget(DreamerBudget::default().max_tokens(20))
+            .build();
+
+        let (agent_channels, mut external_channels) = super::super::channels::create();
+        let handle = agent.run(agent_channels);
+
+        // Each message gets its own 20 tokens, which take two calls of 10 tokens to use up.
+        let mut run_tokens = Vec::new();
+        for message in ["Hello", "Are you still there?"] {
+            external_channels
+                .message_tx
+                .send(message.to_string())
+                .unwrap();
+
+            let mut calls = 0;
+            let mut usage = None;
+            let limit = loop {
+                match external_channels.metrics_rx.recv().await.unwrap() {
+                    Metrics::Usage(metrics) => {
+                        calls += 1;
+                        usage = Some(metrics.run);
+                    }
+                    Metrics::BudgetExceeded(limit) => break limit,
+                    _ => {}
+                }
+            };
+
+            assert_eq!(limit, BudgetLimit::Tokens(20));
+            assert_eq!(calls, 2);
+            run_tokens.push(usage.unwrap().total_tokens);
+        }
+
+        assert_eq!(run_tokens, [20, 40]);
+
+        handle.abort();
+    }
+
+    /// A model that always responds with the same message.
+    struct Scripted(&'static str);
+
+    impl TextModel for Scripted {
+        async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
+            Ok(self.prompt_with_usage(prompt).await?.content)
+        }
+
+        async fn prompt_with_usage(
+            &self,
+            _prompt: impl Into<Prompt> + Send,
+        ) -> ModelResult<ModelResponse> {
+            Ok(ModelResponse {
+                content: self.0.to_string(),
+                model: "scripted".to_string(),
+                usage: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_delivers_replies() {
+        let agent = Dreamer::builder()
+            .model(Scripted(
+                r#"[action]
+{"name":"response_channel","args":{"message":"The answer is 1.5"}}"#,
+            ))
+            .build();
+
+        let (agent_channels, mut external_channels) = super::super::channels::create();
+        let handle = agent.run(agent_channels);
+        external_channels
+            .message_tx
+            .send("Solve 1 + 1 / 2".to_string())
+            .unwrap();
+
+        let AgentAction::Reply(reply) = external_channels.action_rx.recv().await.unwrap();
+        assert_eq!(reply.message, "The answer is 1.5");
+
+        let observation = loop {
+            if let Metrics::ThreadMessage(ThreadMessage::Observation(observation)) =
+                external_channels.metrics_rx.recv().await.unwrap()
+            {
+                break observation;
+            }
+        };
+        assert_eq!(
+            observation.get_main_content(),
+            tools::outbox::REPLY_SENT_OBSERVATION
+        );
 
-    // #[test]
-    // fn test_agent_dreamer() { }
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_runs_tools_after_sender_dropped() {
+        let agent = Dreamer::builder()
+            .model(Scripted(
+                r#"[action]
+{"name":"response_channel","args":{"message":"The answer is 1.5"}}"#,
+            ))
+            .budget(DreamerBudget::default().max_steps(1))
+            .build();
+
+        let (agent_channels, mut external_channels) = super::super::channels::create();
+        let handle = agent.run(agent_channels);
+        let message_tx = external_channels.message_tx;
+        message_tx.send("Solve 1 + 1 / 2".to_string()).unwrap();
+        drop(message_tx);
+
+        let observation = loop {
+            if let Metrics::ThreadMessage(ThreadMessage::Observation(observation)) =
+                external_channels.metrics_rx.recv().await.unwrap()
+            {
+                break observation;
+            }
+        };
+        assert_eq!(
+            observation.get_main_content(),
+            tools::outbox::REPLY_SENT_OBSERVATION
+        );
+
+        let AgentAction::Reply(reply) = external_channels.action_rx.recv().await.unwrap();
+        assert_eq!(reply.message, "The answer is 1.5");
+
+        // With no more messages to wait for, the agent stops once it is idle.
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_observes_unknown_tools() {
+        let agent = Dreamer::builder()
+            .model(Scripted(
+                r#"[action]
+{"name":"calculator","args":{"expression":"1 + 1 / 2"}}"#,
+            ))
+            .budget(DreamerBudget::default().max_steps(1))
+            .build();
+
+        let system = Prompt::from(agent.thread().clone());
+        let system = format!("{system:?}");
+        assert!(system.contains("- `inbox`:"));
+        assert!(system.contains("- `response_channel`:"));
+        assert!(!system.contains(tools::registry::TOOLS_PLACEHOLDER));
+
+        let (agent_channels, mut external_channels) = super::super::channels::create();
+        let handle = agent.run(agent_channels);
+        external_channels
+            .message_tx
+            .send("Solve 1 + 1 / 2".to_string())
+            .unwrap();
+
+        let observation = loop {
+            if let Metrics::ThreadMessage(ThreadMessage::Observation(observation)) =
+                external_channels.metrics_rx.recv().await.unwrap()
+            {
+                break observation;
+            }
+        };
+        let observation: serde_json::Value =
+            serde_json::from_str(observation.get_main_content()).unwrap();
+        assert_eq!(observation["error"]["kind"], "unknown_tool");
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_observes_invalid_actions() {
+        // An action that isn't a tool call, a reply without a tag and a reply with a tag only the
+        // runtime writes.
+        let replies = [
+            "[action]\nI will check the inbox now.",
+            "I will check the inbox now.",
+            "[observation]\nThe inbox is empty.",
+        ];
+
+        for reply in replies {
+            let agent = Dreamer::builder()
+                .model(Scripted(reply))
+                .budget(DreamerBudget::default().max_steps(1))
+                .build();
+
+            let (agent_channels, mut external_channels) = super::super::channels::create();
+            let handle = agent.run(agent_channels);
+            external_channels
+                .message_tx
+                .send("Solve 1 + 1 / 2".to_string())
+                .unwrap();
+
+            // The reply is kept as an action, followed by the error it caused.
+            let mut messages = Vec::new();
+            while messages.len() < 3 {
+                if let Metrics::ThreadMessage(message) =
+                    external_channels.metrics_rx.recv().await.unwrap()
+                {
+                    messages.push(message);
+                }
+            }
+            let (ThreadMessage::Action(action), ThreadMessage::Observation(observation)) =
+                (&messages[1], &messages[2])
+            else {
+                panic!("expected an action and its observation, got {messages:?}");
+            };
+            assert!(action.get_full_content().ends_with(reply), "{reply}");
+
+            let observation: serde_json::Value =
+                serde_json::from_str(observation.get_main_content()).unwrap();
+            assert_eq!(observation["error"]["kind"], "invalid_action", "{reply}");
+
+            // The agent keeps running instead of failing on the bad reply.
+            assert!(matches!(
+                external_channels.metrics_rx.recv().await.unwrap(),
+                Metrics::BudgetExceeded(BudgetLimit::Steps(1))
+            ));
+            assert!(!handle.is_finished());
+
+            handle.abort();
+        }
+    }
+
+    /// A context provider that reports the queries it is asked about.
+    struct Recorder(mpsc::UnboundedSender<String>);
+
+    impl ContextProvider for Recorder {
+        fn retrieve<'a>(
+            &'a self,
+            query: &'a str,
+            _: &'a Thread,
+            _: usize,
+        ) -> futures::future::BoxFuture<'a, DreamerResult<Vec<super::super::ContextItem>>> {
+            self.0.send(query.to_string()).unwrap();
+            Box::pin(futures::future::ready(Ok(Vec::new())))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_retrieves_context_after_observations() {
+        let (query_tx, mut query_rx) = mpsc::unbounded_channel();
+        let agent = Dreamer::builder()
+            .model(Scripted(
+                r#"[action]
+{"name":"inbox","args":{}}"#,
+            ))
+            .context_provider(Recorder(query_tx))
+            .budget(DreamerBudget::default().max_steps(1))
+            .build();
+
+        let (agent_channels, external_channels) = super::super::channels::create();
+        let handle = agent.run(agent_channels);
+        external_channels
+            .message_tx
+            .send("Solve 1 + 1 / 2".to_string())
+            .unwrap();
+
+        assert_eq!(query_rx.recv().await.unwrap(), "Solve 1 + 1 / 2");
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_saves_and_resumes_threads() -> anyhow::Result<()> {
+        let store = Arc::new(super::super::SqliteThreadStore::new()?);
+        let agent = Dreamer::builder()
+            .model(Scripted(
+                r#"[action]
+{"name":"response_channel","args":{"message":"The answer is 1.5"}}"#,
+            ))
+            .thread_store(store.clone())
+            .build();
+        let id = agent.thread().id().to_string();
+
+        let (agent_channels, mut external_channels) = super::super::channels::create();
+        let handle = agent.run(agent_channels);
+        external_channels
+            .message_tx
+            .send("Solve 1 + 1 / 2".to_string())
+            .unwrap();
+        external_channels.action_rx.recv().await.unwrap();
+
+        // The notification, the action and its observation are saved once the agent settles.
+        let saved = loop {
+            let saved = store.load(&id)?;
+            if saved.history().len() == 3 {
+                break saved;
+            }
+
+            time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        handle.abort();
+        assert_eq!(saved.model(), Some("scripted"));
+
+        let resumed = Dreamer::builder()
+            .model(Scripted("[thought]\nDone."))
+            .system_instruction("ignored".to_string())
+            .thread(saved)
+            .build();
+        assert_eq!(resumed.thread().id(), id);
+        assert_eq!(resumed.thread().history().len(), 3);
+        assert!(resumed.thread().system_instruction().contains("- `inbox`:"));
+
+        Ok(())
+    }
+
+    /// A model that summarizes when asked to and otherwise finishes its thought.
+    struct Summarizer;
+
+    impl TextModel for Summarizer {
+        async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
+            let summarizing = prompt.into().into_iter().next()
+                == Some(PromptMessage::system(SUMMARY_INSTRUCTION));
+            let content = match summarizing {
+                true => "The inbox was read twice.",
+                false => "[thought]\nI am done.",
+            };
+
+            Ok(content.to_string())
+        }
+
+        async fn prompt_with_usage(
+            &self,
+            prompt: impl Into<Prompt> + Send,
+        ) -> ModelResult<ModelResponse> {
+            Ok(ModelResponse {
+                content: self.prompt(prompt).await?,
+                model: "summarizer".to_string(),
+                usage: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_summarizes_long_threads() {
+        let mut thread = Thread::new("You are a test.");
+        for _ in 0..2 {
+            thread.push_message(ThreadMessage::action(r#"{"name":"inbox","args":{}}"#));
+            thread.push_message(ThreadMessage::observation("a".repeat(400)));
+        }
+
+        let agent = Dreamer::builder()
+            .model(Summarizer)
+            .thread(thread)
+            .context_window(
+                ContextWindow::default()
+                    .max_tokens(100)
+                    .keep_recent(1)
+                    .strategy(CompactionStrategy::Summarize),
+            )
+            .build();
+
+        let (agent_channels, mut external_channels) = super::super::channels::create();
+        let handle = agent.run(agent_channels);
+        external_channels
+            .message_tx
+            .send("Hello".to_string())
+            .unwrap();
+
+        let summary = loop {
+            if let Metrics::ThreadMessage(ThreadMessage::Summary(summary)) =
+                external_channels.metrics_rx.recv().await.unwrap()
+            {
+                break summary;
+            }
+        };
+        assert_eq!(summary.get_main_content(), "The inbox was read twice.");
+
+        // The agent carries on with the compacted thread.
+        let thought = loop {
+            if let Metrics::ThreadMessage(ThreadMessage::Thought(thought)) =
+                external_channels.metrics_rx.recv().await.unwrap()
+            {
+                break thought;
+            }
+        };
+        assert_eq!(thought.get_main_content(), "I am done.");
+
+        handle.abort();
+    }
 }
@@ -0,0 +1,265 @@
+use std::{fmt, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::models::UsageTotals;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `DreamerBudget` puts hard limits on how much work the dreamer does.
+///
+/// All limits are counted from the user message that woke the agent up. When a limit is exceeded
+/// the agent stops, reports `Metrics::BudgetExceeded` and goes back to idle until the next message,
+/// which starts counting afresh.
+///
+/// No limits are set by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DreamerBudget {
+    /// The maximum number of model calls per user message.
+    pub max_steps: Option<u32>,
+
+    /// The maximum number of thoughts in a row without an action.
+    pub max_consecutive_thoughts: Option<u32>,
+
+    /// The maximum number of tokens used per user message.
+    pub max_tokens: Option<u64>,
+
+    /// The maximum estimated spend in US dollars per user message.
+    pub max_cost: Option<f64>,
+
+    /// How long the agent may work on a user message.
+    pub deadline: Option<Duration>,
+}
+
+/// A limit of a `DreamerBudget` that was exceeded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetLimit {
+    /// The maximum number of model calls per user message.
+    Steps(u32),
+
+    /// The maximum number of thoughts in a row without an action.
+    ConsecutiveThoughts(u32),
+
+    /// The maximum number of tokens used per user message.
+    Tokens(u64),
+
+    /// The maximum estimated spend in US dollars per user message.
+    Cost(f64),
+
+    /// How long the agent may work on a user message.
+    Deadline(Duration),
+}
+
+/// Tracks the work done since the agent last woke up.
+#[derive(Debug, Clone)]
+pub(crate) struct BudgetTracker {
+    steps: u32,
+    consecutive_thoughts: u32,
+    started_at: Instant,
+    tokens_at_start: u64,
+    cost_at_start: f64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl DreamerBudget {
+    /// Sets the maximum number of model calls per user message.
+    pub fn max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Sets the maximum number of thoughts in a row without an action.
+    pub fn max_consecutive_thoughts(mut self, max_consecutive_thoughts: u32) -> Self {
+        self.max_consecutive_thoughts = Some(max_consecutive_thoughts);
+        self
+    }
+
+    /// Sets the maximum number of tokens used per user message.
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Sets the maximum estimated spend in US dollars per user message.
+    pub fn max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    /// Sets how long the agent may work on a user message.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Returns the first limit exceeded by the work tracked so far, given the usage of the run.
+    pub(crate) fn check(
+        &self,
+        tracker: &BudgetTracker,
+        usage: &UsageTotals,
+    ) -> Option<BudgetLimit> {
+        if let Some(max) = self.max_steps.filter(|max| tracker.steps >= *max) {
+            return Some(BudgetLimit::Steps(max));
+        }
+
+        if let Some(max) = self
+            .max_consecutive_thoughts
+            .filter(|max| tracker.consecutive_thoughts >= *max)
+        {
+            return Some(BudgetLimit::ConsecutiveThoughts(max));
+        }
+
+        let tokens = usage.total_tokens.saturating_sub(tracker.tokens_at_start);
+        if let Some(max) = self.max_tokens.filter(|max| tokens >= *max) {
+            return Some(BudgetLimit::Tokens(max));
+        }
+
+        let cost = usage.cost.map(|cost| cost - tracker.cost_at_start);
+        if let Some(max) = self
+            .max_cost
+            .filter(|max| cost.is_some_and(|cost| cost >= *max))
+        {
+            return Some(BudgetLimit::Cost(max));
+        }
+
+        if let Some(deadline) = self
+            .deadline
+            .filter(|deadline| tracker.started_at.elapsed() >= *deadline)
+        {
+            return Some(BudgetLimit::Deadline(deadline));
+        }
+
+        None
+    }
+
+    /// Returns the instant the agent must stop working on the current user message, if any.
+    pub(crate) fn deadline_at(&self, tracker: &BudgetTracker) -> Option<Instant> {
+        self.deadline.map(|deadline| tracker.started_at + deadline)
+    }
+}
+
+impl BudgetTracker {
+    /// Starts tracking the work on a new user message, counting usage from the given usage of
+    /// the run.
+    pub(crate) fn reset(&mut self, usage: &UsageTotals) {
+        *self = Self {
+            tokens_at_start: usage.total_tokens,
+            cost_at_start: usage.cost.unwrap_or_default(),
+            ..Self::default()
+        };
+    }
+
+    /// Counts a model call.
+    pub(crate) fn step(&mut self) {
+        self.steps += 1;
+    }
+
+    /// Counts a thought.
+    pub(crate) fn thought(&mut self) {
+        self.consecutive_thoughts += 1;
+    }
+
+    /// Counts an action, which ends a run of thoughts.
+    pub(crate) fn action(&mut self) {
+        self.consecutive_thoughts = 0;
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for BudgetTracker {
+    fn default() -> Self {
+        Self {
+            steps: 0,
+            consecutive_thoughts: 0,
+            started_at: Instant::now(),
+            tokens_at_start: 0,
+            cost_at_start: 0.0,
+        }
+    }
+}
+
+impl fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetLimit::Steps(max) => write!(f, "reached the limit of {max} steps"),
+            BudgetLimit::ConsecutiveThoughts(max) => {
+                write!(f, "reached the limit of {max} thoughts without an action")
+            }
+            BudgetLimit::Tokens(max) => write!(f, "reached the limit of {max} tokens"),
+            BudgetLimit::Cost(max) => write!(f, "reached the spending limit of ${max}"),
+            BudgetLimit::Deadline(deadline) => {
+                write!(f, "ran out of time after {}s", deadline.as_secs_f64())
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_dreamer_budget_check() {
+        let budget = DreamerBudget::default()
+            .max_steps(3)
+            .max_consecutive_thoughts(2)
+            .max_tokens(1000)
+            .max_cost(0.5);
+
+        let mut tracker = BudgetTracker::default();
+        let mut usage = UsageTotals::default();
+        assert_eq!(budget.check(&tracker, &usage), None);
+
+        tracker.step();
+        tracker.thought();
+        tracker.step();
+        tracker.thought();
+        assert_eq!(
+            budget.check(&tracker, &usage),
+            Some(BudgetLimit::ConsecutiveThoughts(2))
+        );
+
+        tracker.action();
+        assert_eq!(budget.check(&tracker, &usage), None);
+
+        tracker.step();
+        assert_eq!(budget.check(&tracker, &usage), Some(BudgetLimit::Steps(3)));
+
+        tracker.reset(&usage);
+        usage.total_tokens = 1200;
+        assert_eq!(
+            budget.check(&tracker, &usage),
+            Some(BudgetLimit::Tokens(1000))
+        );
+
+        // Usage is counted from the user message the tracker was reset on.
+        tracker.reset(&usage);
+        usage.total_tokens = 1500;
+        assert_eq!(budget.check(&tracker, &usage), None);
+
+        usage.cost = Some(0.75);
+        assert_eq!(budget.check(&tracker, &usage), Some(BudgetLimit::Cost(0.5)));
+
+        tracker.reset(&usage);
+        usage.cost = Some(1.0);
+        assert_eq!(budget.check(&tracker, &usage), None);
+
+        let budget = DreamerBudget::default().deadline(Duration::ZERO);
+        assert_eq!(
+            budget.check(&BudgetTracker::default(), &UsageTotals::default()),
+            Some(BudgetLimit::Deadline(Duration::ZERO))
+        );
+    }
+}
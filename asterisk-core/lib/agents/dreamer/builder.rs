@@ -1,11 +1,14 @@
-use std::collections::HashMap;
+use std::sync::Arc;
 
 use crate::{
-    models::TextModel,
-    tools::{inbox::Inbox, Tool},
+    models::{EstimatingCounter, PriceTable, TextModel, TokenCounter, UsageLedger},
+    tools::{inbox::Inbox, registry::ToolRegistry, Tool},
 };
 
-use super::{Dreamer, Thread, DREAMER_SYSTEM_INSTRUCTION};
+use super::{
+    register_builtin_tools, BudgetTracker, ContextProvider, ContextWindow, Dreamer, DreamerBudget,
+    Thread, ThreadStore, DEFAULT_CONTEXT_LIMIT, DREAMER_SYSTEM_INSTRUCTION,
+};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -14,13 +17,37 @@ use super::{Dreamer, Thread, DREAMER_SYSTEM_INSTRUCTION};
 /// The builder for a `Dreamer`.
 pub struct DreamerBuilder<M> {
     /// The tools for the dreamer.
-    tools: HashMap<String, Box<dyn Tool + Send + Sync>>,
+    tools: ToolRegistry,
 
     /// The model for the dreamer.
     model: M,
 
     /// The system instruction for the dreamer.
     system_instruction: Option<String>,
+
+    /// The sources of the context shown to the dreamer.
+    context_providers: Vec<Box<dyn ContextProvider>>,
+
+    /// The most context items shown to the dreamer at once.
+    context_limit: usize,
+
+    /// The thread to resume, if any.
+    thread: Option<Thread>,
+
+    /// Where the thread is saved as it changes, if anywhere.
+    store: Option<Arc<dyn ThreadStore>>,
+
+    /// The limits on the size of the thread sent to the model.
+    window: ContextWindow,
+
+    /// Counts the tokens of the thread sent to the model.
+    counter: Arc<dyn TokenCounter>,
+
+    /// The prices used to estimate the spend of the dreamer.
+    prices: PriceTable,
+
+    /// The limits on the work the dreamer does.
+    budget: DreamerBudget,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -28,23 +55,34 @@ pub struct DreamerBuilder<M> {
 //--------------------------------------------------------------------------------------------------
 
 impl<M> DreamerBuilder<M> {
-    /// Sets the tools for the dreamer.
-    pub fn tools(
-        self,
-        tools: impl IntoIterator<Item = (String, Box<dyn Tool + Send + Sync>)>,
-    ) -> Self {
+    /// Sets the tools for the dreamer, keyed by their names.
+    pub fn tools(self, tools: impl IntoIterator<Item = Box<dyn Tool + Send + Sync>>) -> Self {
         DreamerBuilder {
             tools: tools.into_iter().collect(),
             ..self
         }
     }
 
+    /// Adds a tool for the dreamer, replacing any tool with the same name.
+    pub fn tool(mut self, tool: impl Tool + Send + Sync + 'static) -> Self {
+        self.tools.register(tool);
+        self
+    }
+
     /// Sets the model for the dreamer.
     pub fn model<N: TextModel>(self, model: N) -> DreamerBuilder<N> {
         DreamerBuilder {
             model,
             tools: self.tools,
             system_instruction: self.system_instruction,
+            context_providers: self.context_providers,
+            context_limit: self.context_limit,
+            thread: self.thread,
+            store: self.store,
+            window: self.window,
+            counter: self.counter,
+            prices: self.prices,
+            budget: self.budget,
         }
     }
 
@@ -55,19 +93,102 @@ impl<M> DreamerBuilder<M> {
             ..self
         }
     }
+
+    /// Adds a source of the context shown to the dreamer after each observation.
+    pub fn context_provider(mut self, provider: impl ContextProvider + 'static) -> Self {
+        self.context_providers.push(Box::new(provider));
+        self
+    }
+
+    /// Sets the most context items shown to the dreamer at once.
+    ///
+    /// Defaults to `DEFAULT_CONTEXT_LIMIT`.
+    pub fn context_limit(self, context_limit: usize) -> Self {
+        DreamerBuilder {
+            context_limit,
+            ..self
+        }
+    }
+
+    /// Resumes the given thread instead of starting a new one.
+    ///
+    /// The thread keeps the system instruction it was started with, so `system_instruction` has
+    /// no effect on it.
+    pub fn thread(self, thread: Thread) -> Self {
+        DreamerBuilder {
+            thread: Some(thread),
+            ..self
+        }
+    }
+
+    /// Sets where the thread is saved every time it changes, so it can be resumed after a crash
+    /// or restart.
+    pub fn thread_store(self, store: impl ThreadStore + 'static) -> Self {
+        DreamerBuilder {
+            store: Some(Arc::new(store)),
+            ..self
+        }
+    }
+
+    /// Sets the limits on the size of the thread sent to the model.
+    pub fn context_window(self, window: ContextWindow) -> Self {
+        DreamerBuilder { window, ..self }
+    }
+
+    /// Sets how the tokens of the thread are counted against the context window.
+    ///
+    /// Defaults to an `EstimatingCounter`, which is close enough for most models.
+    pub fn token_counter(self, counter: impl TokenCounter + 'static) -> Self {
+        DreamerBuilder {
+            counter: Arc::new(counter),
+            ..self
+        }
+    }
+
+    /// Sets the prices used to estimate the spend of the dreamer.
+    pub fn prices(self, prices: PriceTable) -> Self {
+        DreamerBuilder { prices, ..self }
+    }
+
+    /// Sets the limits on the work the dreamer does.
+    pub fn budget(self, budget: DreamerBudget) -> Self {
+        DreamerBuilder { budget, ..self }
+    }
 }
 
 impl<M: TextModel> DreamerBuilder<M> {
     /// Builds the dreamer.
+    ///
+    /// The inbox and outbox are always available and take precedence over provided tools with
+    /// the same names. The tools section of the system instruction is generated from the tools.
     pub fn build(self) -> Dreamer<M> {
+        let inbox = Inbox::default();
+        let mut tools = self.tools;
+        register_builtin_tools(&mut tools, &inbox);
+
+        let thread = self.thread.unwrap_or_else(|| {
+            let system_instruction = self
+                .system_instruction
+                .unwrap_or(DREAMER_SYSTEM_INSTRUCTION.to_string());
+
+            Thread::new(tools.render_into(&system_instruction))
+        });
+
         Dreamer {
-            provided_tools: self.tools,
             model: self.model,
-            thread: Thread::new(
-                self.system_instruction
-                    .unwrap_or(DREAMER_SYSTEM_INSTRUCTION.to_string()),
-            ),
-            inbox: Inbox::default(),
+            thread,
+            inbox,
+            tools,
+            context_providers: self.context_providers,
+            context_limit: self.context_limit,
+            store: self.store,
+            saved_at: None,
+            window: self.window,
+            counter: self.counter,
+            usage: UsageLedger::new(self.prices),
+            budget: self.budget,
+            tracker: BudgetTracker::default(),
+            pending_call: None,
             idle: true,
         }
     }
@@ -80,9 +201,17 @@ impl<M: TextModel> DreamerBuilder<M> {
 impl Default for DreamerBuilder<()> {
     fn default() -> Self {
         DreamerBuilder {
-            tools: HashMap::new(),
+            tools: ToolRegistry::new(),
             model: (),
             system_instruction: None,
+            context_providers: Vec::new(),
+            context_limit: DEFAULT_CONTEXT_LIMIT,
+            thread: None,
+            store: None,
+            window: ContextWindow::default(),
+            counter: Arc::new(EstimatingCounter::default()),
+            prices: PriceTable::default(),
+            budget: DreamerBudget::default(),
         }
     }
 }
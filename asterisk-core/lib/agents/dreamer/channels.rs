@@ -2,7 +2,9 @@
 
 use tokio::sync::mpsc;
 
-use super::{ActionMessage, Metrics};
+use crate::tools::outbox::Reply;
+
+use super::Metrics;
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -16,7 +18,7 @@ pub struct AgentSideChannels {
     pub message_rx: mpsc::UnboundedReceiver<String>,
 
     /// The channel for sending action requests to the outside world.
-    pub action_tx: mpsc::UnboundedSender<ActionMessage>,
+    pub action_tx: mpsc::UnboundedSender<AgentAction>,
 
     /// The channel for sending metrics to the outside world.
     pub metrics_tx: mpsc::UnboundedSender<Metrics>,
@@ -30,12 +32,19 @@ pub struct ExternalSideChannels {
     pub message_tx: mpsc::UnboundedSender<String>,
 
     /// The channel for receiving action requests from the outside world.
-    pub action_rx: mpsc::UnboundedReceiver<ActionMessage>,
+    pub action_rx: mpsc::UnboundedReceiver<AgentAction>,
 
     /// The channel for receiving metrics from the outside world.
     pub metrics_rx: mpsc::UnboundedReceiver<Metrics>,
 }
 
+/// An action the agent asks the outside world to carry out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentAction {
+    /// A reply to the user.
+    Reply(Reply),
+}
+
 /// The channel handles for the agent and outside world.
 pub type Channels = (AgentSideChannels, ExternalSideChannels);
 
@@ -61,3 +70,13 @@ pub fn create() -> Channels {
         },
     )
 }
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl From<Reply> for AgentAction {
+    fn from(reply: Reply) -> Self {
+        AgentAction::Reply(reply)
+    }
+}
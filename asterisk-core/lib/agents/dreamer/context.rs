@@ -1,8 +1,18 @@
-use std::str::FromStr;
+use std::{
+    collections::HashSet,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crate::models::PromptMessage;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
 
-use super::DreamerError;
+use crate::{
+    models::PromptMessage,
+    tools::memories::{Memories, MemoryEntry},
+};
+
+use super::{DreamerError, DreamerResult, Thread, ThreadMessage};
 
 //--------------------------------------------------------------------------------------------------
 // Constant
@@ -11,16 +21,69 @@ use super::DreamerError;
 /// The tag for context messages.
 pub const CONTEXT_TAG: &str = "[context]";
 
+/// The number of context items shown to the agent by default.
+pub const DEFAULT_CONTEXT_LIMIT: usize = 5;
+
+/// The longest an item is rendered in a context message, in characters.
+const MAX_CONTEXT_ITEM_LENGTH: usize = 280;
+
+/// The most memories matched against the query by keyword when there is no embedder, the most
+/// important first.
+const MAX_KEYWORD_MEMORIES: usize = 1000;
+
+//--------------------------------------------------------------------------------------------------
+// Traits
+//--------------------------------------------------------------------------------------------------
+
+/// A source of context for the agent.
+///
+/// After each observation, the agent asks its providers for the items most relevant to it and
+/// shows the best of them in the `[context]` message.
+pub trait ContextProvider: Send + Sync {
+    /// Returns up to `limit` items relevant to the query, which is the latest observation.
+    fn retrieve<'a>(
+        &'a self,
+        query: &'a str,
+        thread: &'a Thread,
+        limit: usize,
+    ) -> BoxFuture<'a, DreamerResult<Vec<ContextItem>>>;
+}
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
 /// A message containing context.
-#[derive(Debug, Clone)]
+///
+/// It serializes as its full, tagged content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
 pub struct ContextMessage {
     content: String,
 }
 
+/// A piece of context retrieved by a `ContextProvider`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextItem {
+    /// The content of the item.
+    pub content: String,
+
+    /// When the item came about, used to label how recent it is.
+    pub created_at: SystemTime,
+
+    /// How relevant the item is to the query. Higher is more relevant.
+    pub score: f32,
+}
+
+/// Provides the past thread messages that share the most words with the latest observation.
+///
+/// The most recent messages are skipped as the agent can already see them.
+#[derive(Debug, Clone)]
+pub struct HistoryContext {
+    /// The number of most recent messages to skip.
+    skip_recent: usize,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Methods
 //--------------------------------------------------------------------------------------------------
@@ -42,18 +105,202 @@ impl ContextMessage {
     pub fn get_main_content(&self) -> &str {
         self.content[CONTEXT_TAG.len()..].trim_start()
     }
+
+    /// Renders the items as a numbered list, each labelled with how long before `now` it came
+    /// about.
+    pub fn from_items(items: &[ContextItem], now: SystemTime) -> Self {
+        let list = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                format!(
+                    "{}. \"{}\" [{}]",
+                    index + 1,
+                    shorten(&item.content),
+                    recency_label(item.created_at, now)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Self::new(list)
+    }
+}
+
+impl HistoryContext {
+    /// Creates a provider that skips the given number of most recent messages.
+    pub fn new(skip_recent: usize) -> Self {
+        Self { skip_recent }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns a label like `today` or `3 days ago` for how long before `now` a time is.
+pub fn recency_label(time: SystemTime, now: SystemTime) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+
+    let elapsed = now.duration_since(time).unwrap_or_default().as_secs();
+    let plural =
+        |count: u64, unit: &str| format!("{count} {unit}{} ago", if count == 1 { "" } else { "s" });
+
+    match elapsed {
+        _ if elapsed < MINUTE => "just now".to_string(),
+        _ if elapsed < HOUR => plural(elapsed / MINUTE, "minute"),
+        _ if elapsed < DAY => "today".to_string(),
+        _ if elapsed < 2 * DAY => "yesterday".to_string(),
+        _ if elapsed < 14 * DAY => plural(elapsed / DAY, "day"),
+        _ if elapsed < 60 * DAY => plural(elapsed / (7 * DAY), "week"),
+        _ => plural(elapsed / (30 * DAY), "month"),
+    }
+}
+
+/// Returns the share of the query keywords found in the text, or `None` if there are none.
+fn keyword_score(query: &HashSet<String>, text: &str) -> Option<f32> {
+    let shared = query.intersection(&keywords(text)).count();
+    (shared > 0).then(|| shared as f32 / query.len() as f32)
+}
+
+/// Returns the distinct lowercase words of three or more characters in the text.
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Collapses the whitespace in the text and cuts it to `MAX_CONTEXT_ITEM_LENGTH` characters.
+fn shorten(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= MAX_CONTEXT_ITEM_LENGTH {
+        return text;
+    }
+
+    let cut: String = text.chars().take(MAX_CONTEXT_ITEM_LENGTH).collect();
+    format!("{}...", cut.trim_end())
 }
 
 //--------------------------------------------------------------------------------------------------
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
 
+impl Default for HistoryContext {
+    fn default() -> Self {
+        Self::new(4)
+    }
+}
+
+impl ContextProvider for HistoryContext {
+    fn retrieve<'a>(
+        &'a self,
+        query: &'a str,
+        thread: &'a Thread,
+        limit: usize,
+    ) -> BoxFuture<'a, DreamerResult<Vec<ContextItem>>> {
+        let query = keywords(query);
+        let history = thread.history();
+        let past = &history[..history.len().saturating_sub(self.skip_recent)];
+
+        let mut items: Vec<_> = past
+            .iter()
+            .filter(|entry| !matches!(entry.message, ThreadMessage::Notification(_)))
+            .filter_map(|entry| {
+                let score = keyword_score(&query, entry.message.get_main_content())?;
+                Some(ContextItem {
+                    content: entry.message.get_full_content().to_string(),
+                    created_at: entry.created_at,
+                    score,
+                })
+            })
+            .collect();
+
+        // The most relevant first, the most recent first among equals.
+        items.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(b.created_at.cmp(&a.created_at))
+        });
+        items.truncate(limit);
+
+        Box::pin(futures::future::ready(Ok(items)))
+    }
+}
+
+impl ContextProvider for Memories {
+    fn retrieve<'a>(
+        &'a self,
+        query: &'a str,
+        _: &'a Thread,
+        limit: usize,
+    ) -> BoxFuture<'a, DreamerResult<Vec<ContextItem>>> {
+        let item = |entry: MemoryEntry, score: f32| ContextItem {
+            content: format!("[memory] {}: {}", entry.name, entry.value),
+            created_at: UNIX_EPOCH + Duration::from_secs(entry.updated_at.max(0) as u64),
+            score,
+        };
+
+        Box::pin(async move {
+            if self.has_embedder() {
+                let entries = self.recall(query, limit).await?;
+                return Ok(entries
+                    .into_iter()
+                    .map(|entry| {
+                        // Cosine distance is between 0 and 2, lower being closer.
+                        let score = entry
+                            .distance
+                            .map_or(0.5, |distance| 1.0 - distance as f32 / 2.0);
+                        item(entry, score)
+                    })
+                    .collect());
+            }
+
+            // The observation as a whole rarely appears in a memory, so its words are matched
+            // instead, as `HistoryContext` does.
+            let query = keywords(query);
+            let mut items: Vec<_> = self
+                .list(MAX_KEYWORD_MEMORIES)
+                .await?
+                .into_iter()
+                .filter_map(|entry| {
+                    let text = format!("{} {}", entry.name, entry.value);
+                    let score = keyword_score(&query, &text)?;
+                    Some(item(entry, score))
+                })
+                .collect();
+
+            // The most relevant first, keeping the most important first among equals.
+            items.sort_by(|a, b| b.score.total_cmp(&a.score));
+            items.truncate(limit);
+
+            Ok(items)
+        })
+    }
+}
+
 impl From<ContextMessage> for PromptMessage {
     fn from(message: ContextMessage) -> Self {
         PromptMessage::assistant(message.content)
     }
 }
 
+impl From<ContextMessage> for String {
+    fn from(message: ContextMessage) -> Self {
+        message.content
+    }
+}
+
+impl TryFrom<String> for ContextMessage {
+    type Error = DreamerError;
+
+    fn try_from(content: String) -> Result<Self, Self::Error> {
+        content.parse()
+    }
+}
+
 impl FromStr for ContextMessage {
     type Err = DreamerError;
 
@@ -69,3 +316,85 @@ impl FromStr for ContextMessage {
         })
     }
 }
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_dreamer_context_recency_labels() {
+        let now = SystemTime::now();
+        let ago = |secs| now - Duration::from_secs(secs);
+
+        assert_eq!(recency_label(ago(5), now), "just now");
+        assert_eq!(recency_label(ago(60), now), "1 minute ago");
+        assert_eq!(recency_label(ago(45 * 60), now), "45 minutes ago");
+        assert_eq!(recency_label(ago(5 * 3600), now), "today");
+        assert_eq!(recency_label(ago(30 * 3600), now), "yesterday");
+        assert_eq!(recency_label(ago(3 * 86400), now), "3 days ago");
+        assert_eq!(recency_label(ago(21 * 86400), now), "3 weeks ago");
+        assert_eq!(recency_label(ago(400 * 86400), now), "13 months ago");
+        assert_eq!(recency_label(now + Duration::from_secs(5), now), "just now");
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_history_context() -> anyhow::Result<()> {
+        let mut thread = Thread::new("");
+        thread.push_message(ThreadMessage::observation(
+            "The capital of Nigeria is Abuja",
+        ));
+        thread.push_message(ThreadMessage::thought("I like turtles"));
+        thread.push_message(ThreadMessage::notification("Message from the user!"));
+        thread.push_message(ThreadMessage::observation(
+            "What is the capital of Nigeria?",
+        ));
+
+        let provider = HistoryContext::new(1);
+        let items = provider
+            .retrieve("What is the capital of Nigeria?", &thread, 5)
+            .await?;
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].content,
+            "[observation]\nThe capital of Nigeria is Abuja"
+        );
+
+        let context = ContextMessage::from_items(&items, items[0].created_at);
+        assert_eq!(
+            context.get_full_content(),
+            "[context]\n1. \"[observation] The capital of Nigeria is Abuja\" [just now]"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_agent_dreamer_memories_context_without_embedder() -> anyhow::Result<()> {
+        let memories = Memories::new()?;
+        memories
+            .remember("nigeria", "The capital of Nigeria is Abuja", 5)
+            .await?;
+        memories
+            .remember("pets", "The user has two turtles", 8)
+            .await?;
+
+        let thread = Thread::new("");
+        let items = memories
+            .retrieve("Found 3 results for: capital of Nigeria", &thread, 5)
+            .await?;
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].content,
+            "[memory] nigeria: The capital of Nigeria is Abuja"
+        );
+
+        let items = memories.retrieve("Sunny weather today", &thread, 5).await?;
+        assert!(items.is_empty());
+
+        Ok(())
+    }
+}
@@ -58,6 +58,22 @@ pub enum DreamerError {
     /// Tool error.
     #[error("tool error: {0}")]
     ToolError(#[from] tools::ToolError),
+
+    /// No saved thread has the given ID.
+    #[error("thread not found: {0}")]
+    ThreadNotFound(String),
+
+    /// The thread store's database lock was poisoned by a panic.
+    #[error("the thread store is poisoned")]
+    ThreadStorePoisoned,
+
+    /// I/O error.
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// SQLite error.
+    #[error("sqlite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
 }
 
 //--------------------------------------------------------------------------------------------------
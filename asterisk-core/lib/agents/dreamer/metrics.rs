@@ -1,4 +1,6 @@
-use super::ThreadMessage;
+use crate::models::{TokenUsage, UsageTotals};
+
+use super::{BudgetLimit, ThreadMessage};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -8,4 +10,32 @@ use super::ThreadMessage;
 pub enum Metrics {
     /// The thread message.
     ThreadMessage(ThreadMessage),
+
+    /// The token usage of a model call.
+    Usage(Box<UsageMetrics>),
+
+    /// A limit of the budget was exceeded and the agent stopped.
+    BudgetExceeded(BudgetLimit),
+}
+
+/// The token usage of a model call along with the running totals of the agent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageMetrics {
+    /// The ID of the thread the call was made for.
+    pub thread_id: String,
+
+    /// The ID of the model that was called.
+    pub model: String,
+
+    /// The token usage of the call.
+    pub usage: TokenUsage,
+
+    /// The estimated cost of the call in US dollars, if the model has a known price.
+    pub cost: Option<f64>,
+
+    /// The totals since the agent started running.
+    pub run: UsageTotals,
+
+    /// The totals of the thread the call was made for.
+    pub thread: UsageTotals,
 }
@@ -1,11 +1,14 @@
 //! Agents
 
 mod agent;
+mod budget;
 mod builder;
 mod context;
 mod error;
 mod metrics;
+mod store;
 mod thread;
+mod window;
 
 //--------------------------------------------------------------------------------------------------
 // Exports
@@ -14,9 +17,12 @@ mod thread;
 pub mod channels;
 
 pub use agent::*;
+pub use budget::*;
 pub use builder::*;
 pub use channels::*;
 pub use context::*;
 pub use error::*;
 pub use metrics::*;
+pub use store::*;
 pub use thread::*;
+pub use window::*;
@@ -0,0 +1,424 @@
+//! Persistence for dreamer threads.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{DreamerError, DreamerResult, Thread, ThreadMessage};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The longest preview shown for a saved thread, in characters.
+const MAX_PREVIEW_LENGTH: usize = 80;
+
+/// The schema migrations, in order. The database's `user_version` is the number applied so far.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE threads (
+        id TEXT PRIMARY KEY,
+        model TEXT,
+        messages INTEGER NOT NULL,
+        preview TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX threads_updated_at ON threads (updated_at DESC);
+"#];
+
+//--------------------------------------------------------------------------------------------------
+// Traits
+//--------------------------------------------------------------------------------------------------
+
+/// A place to save threads to and resume them from.
+pub trait ThreadStore: Send + Sync {
+    /// Saves the thread, replacing any saved thread with the same ID.
+    fn save(&self, thread: &Thread) -> DreamerResult<()>;
+
+    /// Loads the thread with the given ID.
+    fn load(&self, id: &str) -> DreamerResult<Thread>;
+
+    /// Lists the saved threads, most recently updated first.
+    fn list(&self) -> DreamerResult<Vec<ThreadSummary>>;
+
+    /// Deletes the thread with the given ID, returning whether it existed.
+    fn delete(&self, id: &str) -> DreamerResult<bool>;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A short description of a saved thread.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadSummary {
+    /// The ID of the thread.
+    pub id: String,
+
+    /// The model that last responded in the thread.
+    pub model: Option<String>,
+
+    /// The number of messages in the thread.
+    pub messages: usize,
+
+    /// The start of the first observation in the thread.
+    pub preview: Option<String>,
+
+    /// When the thread was created.
+    pub created_at: SystemTime,
+
+    /// When the thread last changed.
+    pub updated_at: SystemTime,
+}
+
+/// Saves each thread as a pretty-printed JSON file named after its ID.
+#[derive(Debug, Clone)]
+pub struct FileThreadStore {
+    dir: PathBuf,
+}
+
+/// Saves threads in a SQLite database.
+pub struct SqliteThreadStore {
+    database: Mutex<Connection>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ThreadSummary {
+    /// Summarizes the thread.
+    pub fn of(thread: &Thread) -> Self {
+        let preview = thread
+            .history()
+            .iter()
+            .find_map(|entry| match &entry.message {
+                ThreadMessage::Observation(observation) => {
+                    let content = observation.get_main_content();
+                    let preview: String = content.chars().take(MAX_PREVIEW_LENGTH).collect();
+                    if preview.len() < content.len() {
+                        Some(format!("{}...", preview.trim_end()))
+                    } else {
+                        Some(preview)
+                    }
+                }
+                _ => None,
+            });
+
+        Self {
+            id: thread.id().to_string(),
+            model: thread.model().map(str::to_string),
+            messages: thread.history().len(),
+            preview,
+            created_at: thread.created_at(),
+            updated_at: thread.updated_at(),
+        }
+    }
+}
+
+impl FileThreadStore {
+    /// Creates a store in the given directory, creating the directory if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> DreamerResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the directory the threads are saved in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the path of the file the thread with the given ID is saved to.
+    pub fn path(&self, id: &str) -> DreamerResult<PathBuf> {
+        // IDs are used as file names, so they must not be able to point anywhere else.
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(DreamerError::ThreadNotFound(id.to_string()));
+        }
+
+        Ok(self.dir.join(format!("{id}.json")))
+    }
+}
+
+impl SqliteThreadStore {
+    /// Creates a store with an in-memory database.
+    pub fn new() -> DreamerResult<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Creates a store with a database at the given path.
+    pub fn with_path(path: impl AsRef<Path>) -> DreamerResult<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Migrates the database and wraps it in a store.
+    fn from_connection(database: Connection) -> DreamerResult<Self> {
+        let version: usize = database.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            database.execute_batch(&format!(
+                "BEGIN; {migration} PRAGMA user_version = {}; COMMIT;",
+                index + 1
+            ))?;
+        }
+
+        Ok(Self {
+            database: Mutex::new(database),
+        })
+    }
+
+    /// Locks the database connection.
+    fn database(&self) -> DreamerResult<MutexGuard<'_, Connection>> {
+        self.database
+            .lock()
+            .map_err(|_| DreamerError::ThreadStorePoisoned)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Converts a time to milliseconds since the Unix epoch.
+fn to_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Converts milliseconds since the Unix epoch to a time.
+fn from_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<T: ThreadStore + ?Sized> ThreadStore for Arc<T> {
+    fn save(&self, thread: &Thread) -> DreamerResult<()> {
+        (**self).save(thread)
+    }
+
+    fn load(&self, id: &str) -> DreamerResult<Thread> {
+        (**self).load(id)
+    }
+
+    fn list(&self) -> DreamerResult<Vec<ThreadSummary>> {
+        (**self).list()
+    }
+
+    fn delete(&self, id: &str) -> DreamerResult<bool> {
+        (**self).delete(id)
+    }
+}
+
+impl ThreadStore for FileThreadStore {
+    fn save(&self, thread: &Thread) -> DreamerResult<()> {
+        let path = self.path(thread.id())?;
+
+        // Write to a temporary file first so a crash mid-write can't corrupt the saved thread.
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(thread)?)?;
+        fs::rename(temp, path)?;
+
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> DreamerResult<Thread> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            return Err(DreamerError::ThreadNotFound(id.to_string()));
+        }
+
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    fn list(&self) -> DreamerResult<Vec<ThreadSummary>> {
+        let mut summaries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+
+            match serde_json::from_slice::<Thread>(&fs::read(&path)?) {
+                Ok(thread) => summaries.push(ThreadSummary::of(&thread)),
+                Err(error) => tracing::warn!("skipping unreadable thread {path:?}: {error}"),
+            }
+        }
+
+        summaries.sort_by_key(|summary| std::cmp::Reverse(summary.updated_at));
+        Ok(summaries)
+    }
+
+    fn delete(&self, id: &str) -> DreamerResult<bool> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        fs::remove_file(path)?;
+        Ok(true)
+    }
+}
+
+impl ThreadStore for SqliteThreadStore {
+    fn save(&self, thread: &Thread) -> DreamerResult<()> {
+        let summary = ThreadSummary::of(thread);
+        self.database()?.execute(
+            "INSERT INTO threads (id, model, messages, preview, created_at, updated_at, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (id) DO UPDATE SET
+                model = excluded.model,
+                messages = excluded.messages,
+                preview = excluded.preview,
+                updated_at = excluded.updated_at,
+                data = excluded.data",
+            params![
+                summary.id,
+                summary.model,
+                summary.messages,
+                summary.preview,
+                to_millis(summary.created_at),
+                to_millis(summary.updated_at),
+                serde_json::to_string(thread)?,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> DreamerResult<Thread> {
+        let data: Option<String> = self
+            .database()?
+            .query_row(
+                "SELECT data FROM threads WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let data = data.ok_or_else(|| DreamerError::ThreadNotFound(id.to_string()))?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    fn list(&self) -> DreamerResult<Vec<ThreadSummary>> {
+        let database = self.database()?;
+        let mut statement = database.prepare(
+            "SELECT id, model, messages, preview, created_at, updated_at FROM threads
+             ORDER BY updated_at DESC",
+        )?;
+
+        let summaries = statement
+            .query_map([], |row| {
+                Ok(ThreadSummary {
+                    id: row.get(0)?,
+                    model: row.get(1)?,
+                    messages: row.get(2)?,
+                    preview: row.get(3)?,
+                    created_at: from_millis(row.get(4)?),
+                    updated_at: from_millis(row.get(5)?),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(summaries)
+    }
+
+    fn delete(&self, id: &str) -> DreamerResult<bool> {
+        let deleted = self
+            .database()?
+            .execute("DELETE FROM threads WHERE id = ?1", params![id])?;
+
+        Ok(deleted > 0)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::agents::dreamer::ContextMessage;
+
+    use super::*;
+
+    fn thread() -> Thread {
+        let mut thread = Thread::new("You are the Dreamer.");
+        thread.push_message(ThreadMessage::notification("Message from the user!"));
+        thread.push_message(ThreadMessage::action(r#"{"name":"inbox","args":{}}"#));
+        thread.push_message(ThreadMessage::observation("Solve 1 + 1 / 2"));
+        thread.update_context(ContextMessage::new("1. \"[observation] Hi\" [yesterday]"));
+        thread.set_model("gpt-4o-mini");
+        thread
+    }
+
+    fn assert_round_trip(store: &dyn ThreadStore) -> anyhow::Result<()> {
+        let thread = thread();
+        store.save(&thread)?;
+
+        let loaded = store.load(thread.id())?;
+        assert_eq!(loaded.id(), thread.id());
+        assert_eq!(loaded.system_instruction(), "You are the Dreamer.");
+        assert_eq!(loaded.model(), Some("gpt-4o-mini"));
+        assert_eq!(loaded.history().len(), 3);
+        assert_eq!(
+            loaded.history()[2].message.get_full_content(),
+            "[observation]\nSolve 1 + 1 / 2"
+        );
+        assert_eq!(
+            loaded.history()[2].created_at,
+            thread.history()[2].created_at
+        );
+        assert_eq!(
+            loaded.context().unwrap().get_main_content(),
+            "1. \"[observation] Hi\" [yesterday]"
+        );
+
+        let summaries = store.list()?;
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].messages, 3);
+        assert_eq!(summaries[0].preview.as_deref(), Some("Solve 1 + 1 / 2"));
+
+        assert!(store.delete(thread.id())?);
+        assert!(!store.delete(thread.id())?);
+        assert!(matches!(
+            store.load(thread.id()),
+            Err(DreamerError::ThreadNotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_agent_dreamer_sqlite_thread_store() -> anyhow::Result<()> {
+        assert_round_trip(&SqliteThreadStore::new()?)
+    }
+
+    #[test]
+    fn test_agent_dreamer_file_thread_store() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("threads-{}", uuid::Uuid::new_v4()));
+        let store = FileThreadStore::new(&dir)?;
+        assert_round_trip(&store)?;
+
+        assert!(matches!(
+            store.load("../secrets"),
+            Err(DreamerError::ThreadNotFound(_))
+        ));
+
+        fs::remove_dir_all(dir)?;
+
+        Ok(())
+    }
+}
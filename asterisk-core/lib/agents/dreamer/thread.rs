@@ -1,4 +1,6 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
 
 use crate::models::{Prompt, PromptMessage, SystemMessage};
 
@@ -20,20 +22,53 @@ pub const OBSERVATION_TAG: &str = "[observation]";
 /// The tag for notification messages.
 pub const NOTIFICATION_TAG: &str = "[notification]";
 
+/// The tag for summary messages.
+pub const SUMMARY_TAG: &str = "[summary]";
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
 /// A history of agent interactions.
-#[derive(Debug, Clone)]
+///
+/// A thread serializes to and from JSON, so it can be saved to a `ThreadStore` and resumed later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Thread {
+    id: String,
     system: SystemMessage,
-    history: Vec<ThreadMessage>,
+    history: Vec<ThreadEntry>,
     context: Option<ContextMessage>,
+
+    /// The model that last responded in the thread.
+    #[serde(default)]
+    model: Option<String>,
+
+    /// When the thread was created.
+    created_at: SystemTime,
+
+    /// When the thread last changed.
+    updated_at: SystemTime,
+}
+
+/// A message in the thread along with when it was added.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadEntry {
+    /// The message.
+    pub message: ThreadMessage,
+
+    /// When the message was added to the thread.
+    pub created_at: SystemTime,
+
+    /// Whether the message is kept in the thread when it is compacted.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
 }
 
 /// A message in the thread.
-#[derive(Debug, Clone)]
+///
+/// It serializes as its full, tagged content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
 pub enum ThreadMessage {
     /// A thought message.
     Thought(ThoughtMessage),
@@ -46,6 +81,9 @@ pub enum ThreadMessage {
 
     /// A notification message.
     Notification(NotificationMessage),
+
+    /// A summary message.
+    Summary(SummaryMessage),
 }
 
 /// `ThoughtMessage` is produced by the agent showing its thought process.
@@ -81,8 +119,8 @@ pub struct ActionMessage {
 /// An observation message is always a direct response to previous action
 /// messages.
 ///
-/// An observation message can be incomplete if it exceeds a specified
-/// user-defined length. However, right now, that has not been implemented.
+/// An observation message is incomplete if it was cut short for exceeding
+/// `ContextWindow::max_observation_tokens`.
 #[derive(Debug, Clone)]
 pub struct ObservationMessage {
     /// The content of the observation.
@@ -99,6 +137,16 @@ pub struct NotificationMessage {
     content: String,
 }
 
+/// `SummaryMessage` is produced by the system when the thread is compacted,
+/// standing in for the older messages it replaced.
+///
+/// This message type is prefixed with `[summary]` tag.
+#[derive(Debug, Clone)]
+pub struct SummaryMessage {
+    /// The content of the summary.
+    content: String,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Method
 //--------------------------------------------------------------------------------------------------
@@ -106,21 +154,122 @@ pub struct NotificationMessage {
 impl Thread {
     /// Creates a new thread.
     pub fn new(system_instruction: impl Into<String>) -> Self {
+        let now = SystemTime::now();
         Self {
+            id: uuid::Uuid::new_v4().to_string(),
             system: SystemMessage::new(system_instruction),
             history: Vec::new(),
             context: None,
+            model: None,
+            created_at: now,
+            updated_at: now,
         }
     }
 
+    /// Returns the ID of the thread.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the system instruction of the thread.
+    pub fn system_instruction(&self) -> &str {
+        &self.system.content
+    }
+
+    /// Returns the model that last responded in the thread, if any has.
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Sets the model that last responded in the thread.
+    pub fn set_model(&mut self, model: impl Into<String>) {
+        self.model = Some(model.into());
+        self.updated_at = SystemTime::now();
+    }
+
+    /// Returns when the thread was created.
+    pub fn created_at(&self) -> SystemTime {
+        self.created_at
+    }
+
+    /// Returns when the thread last changed.
+    pub fn updated_at(&self) -> SystemTime {
+        self.updated_at
+    }
+
     /// Updates the context.
     pub fn update_context(&mut self, message: impl Into<ContextMessage>) {
         self.context = Some(message.into());
+        self.updated_at = SystemTime::now();
+    }
+
+    /// Removes the context, if any.
+    pub fn clear_context(&mut self) {
+        if self.context.take().is_some() {
+            self.updated_at = SystemTime::now();
+        }
+    }
+
+    /// Returns the context, if any.
+    pub fn context(&self) -> Option<&ContextMessage> {
+        self.context.as_ref()
+    }
+
+    /// Returns the messages in the thread, oldest first.
+    pub fn history(&self) -> &[ThreadEntry] {
+        &self.history
     }
 
     /// Pushes a message to the thread.
     pub fn push_message(&mut self, message: ThreadMessage) {
-        self.history.push(message);
+        let now = SystemTime::now();
+        self.history.push(ThreadEntry {
+            message,
+            created_at: now,
+            pinned: false,
+        });
+        self.updated_at = now;
+    }
+
+    /// Pins or unpins the message at the given index so that compaction keeps or evicts it.
+    ///
+    /// Returns false if there is no message at the index.
+    pub fn set_pinned(&mut self, index: usize, pinned: bool) -> bool {
+        let Some(entry) = self.history.get_mut(index) else {
+            return false;
+        };
+
+        entry.pinned = pinned;
+        self.updated_at = SystemTime::now();
+        true
+    }
+
+    /// Removes the messages at the given indices, putting the summary in place of the first one if
+    /// there is one.
+    pub fn compact(&mut self, indices: &[usize], summary: Option<ThreadMessage>) {
+        let Some(&first) = indices.iter().min() else {
+            return;
+        };
+
+        let now = SystemTime::now();
+        let mut index = 0;
+        self.history.retain(|_| {
+            index += 1;
+            !indices.contains(&(index - 1))
+        });
+
+        if let Some(message) = summary {
+            self.history.insert(
+                first,
+                ThreadEntry {
+                    message,
+                    created_at: now,
+                    pinned: false,
+                },
+            );
+        }
+
+        self.updated_at = now;
     }
 }
 
@@ -144,6 +293,33 @@ impl ThreadMessage {
     pub fn notification(content: impl Into<String>) -> Self {
         Self::Notification(NotificationMessage::new(content))
     }
+
+    /// Creates a new summary message and tags it.
+    pub fn summary(content: impl Into<String>) -> Self {
+        Self::Summary(SummaryMessage::new(content))
+    }
+
+    /// Returns the full content of the message, including its tag.
+    pub fn get_full_content(&self) -> &str {
+        match self {
+            ThreadMessage::Thought(message) => message.get_full_content(),
+            ThreadMessage::Action(message) => message.get_full_content(),
+            ThreadMessage::Observation(message) => message.get_full_content(),
+            ThreadMessage::Notification(message) => message.get_full_content(),
+            ThreadMessage::Summary(message) => message.get_full_content(),
+        }
+    }
+
+    /// Returns the main content of the message.
+    pub fn get_main_content(&self) -> &str {
+        match self {
+            ThreadMessage::Thought(message) => message.get_main_content(),
+            ThreadMessage::Action(message) => message.get_main_content(),
+            ThreadMessage::Observation(message) => message.get_main_content(),
+            ThreadMessage::Notification(message) => message.get_main_content(),
+            ThreadMessage::Summary(message) => message.get_main_content(),
+        }
+    }
 }
 
 impl ThoughtMessage {
@@ -232,6 +408,25 @@ impl NotificationMessage {
     }
 }
 
+impl SummaryMessage {
+    /// Creates a new summary message and tags it.
+    pub fn new(content: impl Into<String>) -> Self {
+        Self {
+            content: SUMMARY_TAG.to_string() + "\n" + &content.into(),
+        }
+    }
+
+    /// Returns the full content of the summary.
+    pub fn get_full_content(&self) -> &str {
+        &self.content
+    }
+
+    /// Returns the main content of the summary.
+    pub fn get_main_content(&self) -> &str {
+        self.content[SUMMARY_TAG.len()..].trim_start()
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
@@ -243,17 +438,38 @@ impl From<ThreadMessage> for PromptMessage {
             ThreadMessage::Action(message) => PromptMessage::assistant(message.content),
             ThreadMessage::Observation(message) => PromptMessage::user(message.content),
             ThreadMessage::Notification(message) => PromptMessage::user(message.content),
+            ThreadMessage::Summary(message) => PromptMessage::user(message.content),
+        }
+    }
+}
+
+impl From<ThreadMessage> for String {
+    fn from(message: ThreadMessage) -> Self {
+        match message {
+            ThreadMessage::Thought(message) => message.content,
+            ThreadMessage::Action(message) => message.content,
+            ThreadMessage::Observation(message) => message.content,
+            ThreadMessage::Notification(message) => message.content,
+            ThreadMessage::Summary(message) => message.content,
         }
     }
 }
 
+impl TryFrom<String> for ThreadMessage {
+    type Error = DreamerError;
+
+    fn try_from(content: String) -> Result<Self, Self::Error> {
+        content.parse()
+    }
+}
+
 impl From<Thread> for Prompt {
     fn from(thread: Thread) -> Self {
         let mut prompt = Prompt::new();
         prompt.push(PromptMessage::system(thread.system.content));
 
-        for message in thread.history {
-            prompt.push(message.into());
+        for entry in thread.history {
+            prompt.push(entry.message.into());
         }
 
         if let Some(context) = thread.context {
@@ -294,6 +510,12 @@ impl FromStr for ThreadMessage {
             }));
         }
 
+        if s.starts_with(SUMMARY_TAG) {
+            return Ok(ThreadMessage::Summary(SummaryMessage {
+                content: s.to_string(),
+            }));
+        }
+
         Err(DreamerError::InvalidThreadMessage(s.to_string()))
     }
 }
@@ -0,0 +1,317 @@
+use crate::models::{Prompt, PromptMessage, TokenCounter};
+
+use super::{Thread, ThreadMessage};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The number of most recent messages that are never evicted by default.
+pub const DEFAULT_KEEP_RECENT: usize = 4;
+
+/// The instruction given to the model when it summarizes evicted messages.
+pub const SUMMARY_INSTRUCTION: &str = "You summarize part of a conversation between an AI \
+assistant and the outside world so that the assistant can carry on without it. The messages are \
+tagged: [thought] and [action] are the assistant's own, [observation] and [notification] come \
+from the outside world and [summary] is an earlier summary. Keep every fact, decision, open \
+question and result the assistant may still need, drop the rest, and write it as a few short \
+sentences of plain text without tags.";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `ContextWindow` keeps the thread sent to the model within a number of tokens.
+///
+/// Before each model call, if the prompt is longer than `max_tokens`, the oldest messages are
+/// evicted until it is down to three quarters of that, leaving room for the thread to grow again
+/// before the next compaction. An action is always evicted together with its observation. Pinned
+/// messages and the `keep_recent` most recent messages are never evicted.
+///
+/// No limits are set by default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextWindow {
+    /// The maximum number of tokens in the prompt.
+    pub max_tokens: Option<usize>,
+
+    /// The maximum number of tokens in an observation, beyond which it is cut short.
+    pub max_observation_tokens: Option<usize>,
+
+    /// The number of most recent messages that are never evicted.
+    pub keep_recent: usize,
+
+    /// What happens to the evicted messages.
+    pub strategy: CompactionStrategy,
+}
+
+/// What happens to the messages evicted from the thread.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompactionStrategy {
+    /// The messages are dropped.
+    #[default]
+    Drop,
+
+    /// The messages are replaced with a `[summary]` of them written by the model.
+    ///
+    /// If the summary can't be written, the messages are dropped instead.
+    Summarize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ContextWindow {
+    /// Sets the maximum number of tokens in the prompt.
+    pub fn max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Sets the maximum number of tokens in an observation, beyond which it is cut short.
+    pub fn max_observation_tokens(mut self, max_observation_tokens: usize) -> Self {
+        self.max_observation_tokens = Some(max_observation_tokens);
+        self
+    }
+
+    /// Sets the number of most recent messages that are never evicted.
+    ///
+    /// Defaults to `DEFAULT_KEEP_RECENT`.
+    pub fn keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// Sets what happens to the evicted messages.
+    pub fn strategy(mut self, strategy: CompactionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Returns the indices of the messages to evict from the thread, oldest first, or `None` if
+    /// the thread fits or nothing can be evicted.
+    pub(crate) fn plan(&self, thread: &Thread, counter: &dyn TokenCounter) -> Option<Vec<usize>> {
+        let max_tokens = self.max_tokens?;
+        let mut total = counter.count_prompt(&Prompt::from(thread.clone()));
+        if total <= max_tokens {
+            return None;
+        }
+
+        let target = max_tokens / 4 * 3;
+        let history = thread.history();
+        let cost = |index: usize| {
+            counter.count_message(&PromptMessage::from(history[index].message.clone()))
+        };
+
+        let mut evicted = Vec::new();
+        let mut index = 0;
+        let end = history.len().saturating_sub(self.keep_recent);
+        while index < end && total > target {
+            // An observation makes no sense without the action it is the result of, so the two
+            // are evicted together or not at all.
+            let paired = matches!(history[index].message, ThreadMessage::Action(_))
+                && history
+                    .get(index + 1)
+                    .is_some_and(|entry| matches!(entry.message, ThreadMessage::Observation(_)));
+            let span = index..index + if paired { 2 } else { 1 };
+
+            if span
+                .clone()
+                .all(|index| index < end && !history[index].pinned)
+            {
+                for index in span.clone() {
+                    evicted.push(index);
+                    total = total.saturating_sub(cost(index));
+                }
+            }
+
+            index = span.end;
+        }
+
+        (!evicted.is_empty()).then_some(evicted)
+    }
+
+    /// Splits the transcript of the messages at the given indices into parts that are summarized
+    /// one after the other, so that a summary request never outgrows the window however much is
+    /// evicted at once.
+    ///
+    /// Each part is at most half of `max_tokens` long, leaving the rest for the instruction, the
+    /// summary of the parts before it and the reply. A message too long for a part of its own is
+    /// cut short.
+    pub(crate) fn summary_parts(
+        &self,
+        thread: &Thread,
+        indices: &[usize],
+        counter: &dyn TokenCounter,
+    ) -> Vec<String> {
+        let history = thread.history();
+        let messages = indices
+            .iter()
+            .map(|&index| history[index].message.get_full_content());
+
+        let Some(max_tokens) = self.max_tokens else {
+            return vec![messages.collect::<Vec<_>>().join("\n\n")];
+        };
+
+        let max_part_tokens = max_tokens / 2;
+        let mut parts = Vec::new();
+        let mut part = String::new();
+        for message in messages {
+            let message = counter.truncate(message, max_part_tokens);
+            if !part.is_empty() {
+                if counter.count(&part) + counter.count(message) + 1 > max_part_tokens {
+                    parts.push(std::mem::take(&mut part));
+                } else {
+                    part.push_str("\n\n");
+                }
+            }
+
+            part.push_str(message);
+        }
+
+        if !part.is_empty() {
+            parts.push(part);
+        }
+
+        parts
+    }
+
+    /// Cuts the observation short if it is longer than `max_observation_tokens`, ending it with
+    /// `...` to mark it incomplete.
+    pub(crate) fn truncate_observation(
+        &self,
+        observation: String,
+        counter: &dyn TokenCounter,
+    ) -> String {
+        match self.max_observation_tokens {
+            Some(max_tokens) if counter.count(&observation) > max_tokens => {
+                format!(
+                    "{}...",
+                    counter.truncate(&observation, max_tokens).trim_end()
+                )
+            }
+            _ => observation,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for ContextWindow {
+    fn default() -> Self {
+        Self {
+            max_tokens: None,
+            max_observation_tokens: None,
+            keep_recent: DEFAULT_KEEP_RECENT,
+            strategy: CompactionStrategy::default(),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::models::EstimatingCounter;
+
+    use super::*;
+
+    #[test]
+    fn test_agent_dreamer_context_window_plan() {
+        let counter = EstimatingCounter::default();
+        let mut thread = Thread::new("You are a test.");
+        thread.push_message(ThreadMessage::notification("Message from the user!"));
+        thread.push_message(ThreadMessage::action(r#"{"name":"inbox","args":{}}"#));
+        thread.push_message(ThreadMessage::observation("a".repeat(400)));
+        thread.push_message(ThreadMessage::thought("b".repeat(400)));
+        thread.push_message(ThreadMessage::thought("c".repeat(400)));
+        thread.push_message(ThreadMessage::thought("d".repeat(400)));
+        thread.set_pinned(0, true);
+
+        let window = ContextWindow::default().keep_recent(2);
+        assert_eq!(window.plan(&thread, &counter), None);
+
+        // The pinned notification stays, and the action goes together with its observation.
+        let window = window.max_tokens(400);
+        assert_eq!(window.plan(&thread, &counter), Some(vec![1, 2, 3]));
+
+        // The most recent messages are never evicted, however long the thread is.
+        let window = window.max_tokens(10);
+        assert_eq!(window.plan(&thread, &counter), Some(vec![1, 2, 3]));
+
+        let window = window.keep_recent(1);
+        assert_eq!(window.plan(&thread, &counter), Some(vec![1, 2, 3, 4]));
+
+        thread.compact(
+            &[1, 2, 3, 4],
+            Some(ThreadMessage::summary("The user said hi.")),
+        );
+        assert_eq!(thread.history().len(), 3);
+        assert!(thread.history()[0].pinned);
+        assert!(matches!(
+            thread.history()[1].message,
+            ThreadMessage::Summary(_)
+        ));
+    }
+
+    #[test]
+    fn test_agent_dreamer_context_window_keeps_pairs_at_boundary() {
+        let counter = EstimatingCounter::default();
+        let mut thread = Thread::new("You are a test.");
+        thread.push_message(ThreadMessage::thought("a".repeat(400)));
+        thread.push_message(ThreadMessage::action(r#"{"name":"inbox","args":{}}"#));
+        thread.push_message(ThreadMessage::observation("b".repeat(400)));
+        thread.push_message(ThreadMessage::thought("c".repeat(400)));
+
+        // The observation is one of the two most recent messages, so its action stays as well.
+        let window = ContextWindow::default().keep_recent(2).max_tokens(10);
+        assert_eq!(window.plan(&thread, &counter), Some(vec![0]));
+
+        // The same goes for an observation that is pinned.
+        thread.set_pinned(2, true);
+        let window = window.keep_recent(1);
+        assert_eq!(window.plan(&thread, &counter), Some(vec![0]));
+
+        thread.set_pinned(2, false);
+        assert_eq!(window.plan(&thread, &counter), Some(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn test_agent_dreamer_context_window_summary_parts() {
+        let counter = EstimatingCounter::default();
+        let mut thread = Thread::new("You are a test.");
+        thread.push_message(ThreadMessage::thought("a".repeat(40)));
+        thread.push_message(ThreadMessage::thought("b".repeat(40)));
+        thread.push_message(ThreadMessage::observation("c".repeat(400)));
+
+        let window = ContextWindow::default();
+        assert_eq!(window.summary_parts(&thread, &[0, 1], &counter).len(), 1);
+
+        // Every part fits in half the window, the long observation cut short to do so.
+        let window = window.max_tokens(60);
+        let parts = window.summary_parts(&thread, &[0, 1, 2], &counter);
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].starts_with("[thought]") && parts[0].ends_with('b'));
+        assert!(parts[1].starts_with("[observation]"));
+        assert!(parts.iter().all(|part| counter.count(part) <= 30));
+    }
+
+    #[test]
+    fn test_agent_dreamer_context_window_truncates_observations() {
+        let counter = EstimatingCounter::default();
+        let window = ContextWindow::default().max_observation_tokens(2);
+
+        let observation = window.truncate_observation("Hello, world".to_string(), &counter);
+        assert_eq!(observation, "Hello, w...");
+        assert!(ThreadMessage::observation(observation)
+            .get_main_content()
+            .ends_with("..."));
+
+        let observation = window.truncate_observation("Hello".to_string(), &counter);
+        assert_eq!(observation, "Hello");
+    }
+}
@@ -0,0 +1,161 @@
+use std::{borrow::Cow, env};
+
+use crate::models::{http, retry::RetryPolicy};
+
+use super::{
+    AnthropicModel, Config, Metadata, ModelType, Tool, ToolChoice, ANTHROPIC_API_KEY,
+    ANTHROPIC_API_URL, DEFAULT_MAX_TOKENS,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A builder for an Anthropic model.
+#[derive(Debug, Clone, Default)]
+pub struct ModelBuilder {
+    model: Option<String>,
+    base_url: Option<String>,
+    api_key: Option<String>,
+    max_tokens: Option<u32>,
+    metadata: Option<Metadata>,
+    stop_sequences: Option<Vec<String>>,
+    stream: Option<bool>,
+    temperature: Option<f32>,
+    top_k: Option<u32>,
+    top_p: Option<f32>,
+    tools: Option<Vec<Tool>>,
+    tool_choice: Option<ToolChoice>,
+    retry: RetryPolicy,
+    client: Option<reqwest::Client>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ModelBuilder {
+    /// The ID of the model to use.
+    ///
+    /// Defaults to `ModelType::Claude3_5Sonnet`.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// The base URL for making requests to the Anthropic API.
+    ///
+    /// Defaults to `ANTHROPIC_API_URL`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// The API key for making requests to the Anthropic API.
+    ///
+    /// Defaults to the `ANTHROPIC_API_KEY` environment variable.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// The maximum number of tokens to generate before stopping.
+    ///
+    /// Defaults to `DEFAULT_MAX_TOKENS`.
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// An object describing metadata about the request.
+    pub fn metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Custom text sequences that will cause the model to stop generating.
+    pub fn stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+        self.stop_sequences = Some(stop_sequences);
+        self
+    }
+
+    /// Whether to stream the response.
+    pub fn stream(mut self, stream: bool) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    /// The amount of randomness injected into the response, between 0.0 and 1.0.
+    ///
+    /// Defaults to `1`.
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Only sample from the top K options for each subsequent token.
+    pub fn top_k(mut self, top_k: u32) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// An alternative to sampling with temperature, called nucleus sampling, where the model
+    /// considers the results of the tokens with top_p probability mass.
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// A list of tools the model may use.
+    pub fn tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// How the model should use the provided tools.
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// The policy for retrying failed requests and timing them out.
+    ///
+    /// Defaults to `RetryPolicy::default()`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The HTTP client to send requests with, for example one built from an
+    /// `http::HttpClientConfig` with a proxy or extra CA roots.
+    ///
+    /// Defaults to a pooled client shared by all models.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Builds the Anthropic model.
+    pub fn build(self) -> AnthropicModel {
+        let config = Config {
+            model: self.model.unwrap_or(ModelType::Claude3_5Sonnet.to_string()),
+            api_key: self.api_key.or_else(|| env::var(ANTHROPIC_API_KEY).ok()),
+            max_tokens: self.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            metadata: self.metadata,
+            stop_sequences: self.stop_sequences,
+            stream: self.stream,
+            temperature: self.temperature,
+            top_k: self.top_k,
+            top_p: self.top_p,
+            tools: self.tools,
+            tool_choice: self.tool_choice,
+        };
+
+        AnthropicModel {
+            config: Cow::Owned(config),
+            base_url: self.base_url.unwrap_or(ANTHROPIC_API_URL.to_string()),
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::env;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The environment variable for the Anthropic API key.
+pub const ANTHROPIC_API_KEY: &str = "ANTHROPIC_API_KEY";
+
+/// The URL for the Anthropic Messages API.
+pub const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1/messages";
+
+/// The version of the Anthropic API sent in the `anthropic-version` header.
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// The maximum number of tokens generated when none is set.
+///
+/// Unlike other APIs, the Messages API requires `max_tokens` in every request.
+pub const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The configuration for the Anthropic model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Config {
+    /// The ID of the model to use.
+    pub model: String,
+
+    /// The API key for making requests to the Anthropic API.
+    #[serde(skip)]
+    pub api_key: Option<String>,
+
+    /// The maximum number of tokens to generate before stopping.
+    pub max_tokens: u32,
+
+    /// An object describing metadata about the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+
+    /// Custom text sequences that will cause the model to stop generating.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+
+    /// Whether to incrementally stream the response using server-sent events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+
+    /// The amount of randomness injected into the response, between 0.0 and 1.0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// Only sample from the top K options for each subsequent token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+
+    /// An alternative to sampling with temperature, called nucleus sampling, where the model
+    /// considers the results of the tokens with top_p probability mass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// A list of tools the model may use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+
+    /// How the model should use the provided tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+}
+
+/// The model type.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Display, Default)]
+pub enum ModelType {
+    /// The latest Claude 3.5 Sonnet model.
+    #[default]
+    #[serde(rename = "claude-3-5-sonnet-latest")]
+    #[strum(to_string = "claude-3-5-sonnet-latest")]
+    Claude3_5Sonnet,
+
+    /// The latest Claude 3.5 Haiku model.
+    #[serde(rename = "claude-3-5-haiku-latest")]
+    #[strum(to_string = "claude-3-5-haiku-latest")]
+    Claude3_5Haiku,
+
+    /// The latest Claude 3 Opus model.
+    #[serde(rename = "claude-3-opus-latest")]
+    #[strum(to_string = "claude-3-opus-latest")]
+    Claude3Opus,
+}
+
+/// An object describing metadata about the request.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// An external identifier for the user associated with the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// A tool that the model may use.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Tool {
+    /// The name of the tool.
+    pub name: String,
+
+    /// A description of what the tool does, used by the model to choose when and how to use it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The input the tool accepts, described as a JSON schema.
+    pub input_schema: serde_json::Value,
+}
+
+/// How the model should use the provided tools.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to use a tool.
+    Auto,
+
+    /// The model must use one of the tools.
+    Any,
+
+    /// The model must use the named tool.
+    Tool {
+        /// The name of the tool to use.
+        name: String,
+    },
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model: ModelType::Claude3_5Sonnet.to_string(),
+            api_key: env::var(ANTHROPIC_API_KEY).ok(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            metadata: None,
+            stop_sequences: None,
+            stream: None,
+            temperature: None,
+            top_k: None,
+            top_p: None,
+            tools: None,
+            tool_choice: None,
+        }
+    }
+}
+
+impl From<ModelType> for String {
+    fn from(value: ModelType) -> Self {
+        value.to_string()
+    }
+}
@@ -0,0 +1,620 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::{
+    self, AssistantMessage, ContentPart, ImageContent, MessageContent, Prompt, PromptMessage,
+    SystemMessage, TokenUsage, ToolMessage, UserMessage,
+};
+
+use super::Config;
+
+//--------------------------------------------------------------------------------------------------
+// Types: Request
+//--------------------------------------------------------------------------------------------------
+
+/// The body of a request to the Anthropic API.
+#[derive(Debug, Serialize)]
+pub struct RequestBody {
+    /// The system prompt and the messages to send to the model.
+    #[serde(flatten)]
+    pub messages: RequestMessages,
+
+    /// The model's configuration.
+    #[serde(flatten)]
+    pub config: Config,
+}
+
+/// A conversation with the model.
+///
+/// The Messages API has no system role, so the system prompt is sent apart from the messages,
+/// which must alternate between the user and the assistant.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RequestMessages {
+    /// The system prompt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+
+    /// The messages of the conversation.
+    pub messages: Vec<RequestMessage>,
+}
+
+/// A message in a chat conversation with the model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RequestMessage {
+    /// The role of the message's author.
+    pub role: Role,
+
+    /// The content blocks of the message.
+    pub content: Vec<ContentBlock>,
+}
+
+/// The role of a message's author.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// The user, which also sends the results of tool calls.
+    User,
+
+    /// The assistant.
+    Assistant,
+}
+
+/// A block of content in a message.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    /// A text block.
+    Text {
+        /// The text content.
+        text: String,
+    },
+
+    /// An image block.
+    Image {
+        /// The image to send to the model.
+        source: ImageSource,
+    },
+
+    /// A tool use requested by the assistant.
+    ToolUse {
+        /// The id of the tool use.
+        id: String,
+
+        /// The name of the tool.
+        name: String,
+
+        /// The input to call the tool with.
+        input: serde_json::Value,
+    },
+
+    /// The result of a tool use, sent back by the user.
+    ToolResult {
+        /// The id of the tool use this is the result of.
+        tool_use_id: String,
+
+        /// The output of the tool.
+        content: String,
+
+        /// Whether the tool failed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        is_error: Option<bool>,
+    },
+}
+
+/// The source of an image sent to the model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    /// Base64 encoded image data.
+    Base64 {
+        /// The media type of the image, like `image/png`.
+        media_type: String,
+
+        /// The base64 encoded image data.
+        data: String,
+    },
+
+    /// A URL of the image.
+    Url {
+        /// The URL of the image.
+        url: String,
+    },
+}
+
+//--------------------------------------------------------------------------------------------------
+// Types: Response
+//--------------------------------------------------------------------------------------------------
+
+/// Response body.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ResponseBody {
+    /// A successful response.
+    Ok(Box<ResponseOk>),
+
+    /// An error response.
+    Error(Box<ResponseError>),
+}
+
+/// Represents an error response returned by the Anthropic API.
+#[derive(Debug, Deserialize, Error)]
+#[error(transparent)]
+pub struct ResponseError {
+    /// The error information.
+    pub error: ErrorInfo,
+}
+
+/// The error information.
+#[derive(Debug, Clone, Deserialize, Error)]
+pub struct ErrorInfo {
+    /// The type of error, like `overloaded_error`.
+    pub r#type: String,
+
+    /// The error message.
+    pub message: String,
+}
+
+/// A message generated by the model.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponseOk {
+    /// A unique identifier for the message.
+    pub id: String,
+
+    /// The role of the message, which is always `assistant`.
+    pub role: Role,
+
+    /// The model that generated the message.
+    pub model: String,
+
+    /// The content blocks generated by the model.
+    pub content: Vec<ContentBlock>,
+
+    /// The reason the model stopped generating tokens, like `end_turn`, `max_tokens`,
+    /// `stop_sequence` or `tool_use`.
+    ///
+    /// This is `None` in the `message_start` event of a streamed response.
+    pub stop_reason: Option<String>,
+
+    /// The custom stop sequence that was generated, if any.
+    pub stop_sequence: Option<String>,
+
+    /// Usage statistics for the request.
+    pub usage: Usage,
+}
+
+/// Usage statistics for the request.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+pub struct Usage {
+    /// Number of input tokens.
+    ///
+    /// Missing from the `message_delta` event of a streamed response.
+    #[serde(default)]
+    pub input_tokens: u64,
+
+    /// Number of output tokens.
+    pub output_tokens: u64,
+
+    /// Number of input tokens used to create a cache entry.
+    pub cache_creation_input_tokens: Option<u64>,
+
+    /// Number of input tokens read from the cache.
+    pub cache_read_input_tokens: Option<u64>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RequestMessages {
+    /// Appends a message to the conversation, merging it into the last message if both have the
+    /// same role.
+    pub fn push(&mut self, message: RequestMessage) {
+        match self.messages.last_mut() {
+            Some(last) if last.role == message.role => last.content.extend(message.content),
+            _ => self.messages.push(message),
+        }
+    }
+
+    /// Appends text to the system prompt.
+    pub fn push_system(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        match &mut self.system {
+            Some(system) => {
+                system.push_str("\n\n");
+                system.push_str(&text);
+            }
+            None => self.system = Some(text),
+        }
+    }
+}
+
+impl RequestMessage {
+    /// Creates a user message carrying the result of the tool use with the given id.
+    pub fn tool_result(tool_use_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: vec![ContentBlock::ToolResult {
+                tool_use_id: tool_use_id.into(),
+                content: content.into(),
+                is_error: None,
+            }],
+        }
+    }
+}
+
+impl ResponseOk {
+    /// Returns the text of all the text blocks.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the tool uses requested by the model as tool calls.
+    pub fn tool_calls(&self) -> Vec<models::ToolCall> {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, name, input } => {
+                    Some(models::ToolCall::new(id, name, input.to_string()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl ResponseBody {
+    /// Gets the error variant or panics.
+    pub fn unwrap_err(self) -> ResponseError {
+        match self {
+            ResponseBody::Error(error) => *error,
+            ResponseBody::Ok(_) => panic!("Called `unwrap_err()` on a `ResponseBody::Ok` value"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Display for ErrorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<Prompt> for RequestMessages {
+    fn from(prompt: Prompt) -> Self {
+        let mut messages = Self::default();
+        for message in prompt {
+            match message {
+                PromptMessage::System(SystemMessage { content, .. }) => {
+                    messages.push_system(content)
+                }
+                message => messages.push(message.into()),
+            }
+        }
+
+        // The API takes a conversation ending with an assistant turn as the start of the reply,
+        // which the model would carry on with instead of answering. Trailing assistant text, like
+        // the thoughts and context of an agent, is sent as the user's instead.
+        if let Some(last) = messages.messages.pop() {
+            let text_only = last
+                .content
+                .iter()
+                .all(|block| matches!(block, ContentBlock::Text { .. }));
+
+            match last.role {
+                Role::Assistant if text_only => messages.push(RequestMessage {
+                    role: Role::User,
+                    content: last.content,
+                }),
+                _ => messages.messages.push(last),
+            }
+        }
+
+        messages
+    }
+}
+
+impl From<PromptMessage> for RequestMessage {
+    fn from(message: PromptMessage) -> Self {
+        match message {
+            // System messages are only sent this way when converted one by one.
+            PromptMessage::System(SystemMessage { content, .. }) => RequestMessage {
+                role: Role::User,
+                content: vec![ContentBlock::Text { text: content }],
+            },
+            PromptMessage::User(UserMessage { content, .. }) => RequestMessage {
+                role: Role::User,
+                content: content.into(),
+            },
+            PromptMessage::Assistant(AssistantMessage {
+                content,
+                refusal,
+                tool_calls,
+                ..
+            }) => {
+                let text = content.or(refusal).filter(|text| !text.is_empty());
+                let tool_uses = tool_calls.into_iter().map(|tool_call| {
+                    // The API only accepts an object as input, which the model may have failed to
+                    // generate.
+                    let input = serde_json::from_str(&tool_call.arguments)
+                        .ok()
+                        .filter(serde_json::Value::is_object)
+                        .unwrap_or_else(|| serde_json::json!({}));
+
+                    ContentBlock::ToolUse {
+                        id: tool_call.id,
+                        name: tool_call.name,
+                        input,
+                    }
+                });
+
+                RequestMessage {
+                    role: Role::Assistant,
+                    content: text
+                        .map(|text| ContentBlock::Text { text })
+                        .into_iter()
+                        .chain(tool_uses)
+                        .collect(),
+                }
+            }
+            PromptMessage::Tool(ToolMessage {
+                tool_call_id,
+                content,
+                ..
+            }) => RequestMessage::tool_result(tool_call_id, content),
+        }
+    }
+}
+
+impl From<MessageContent> for Vec<ContentBlock> {
+    fn from(content: MessageContent) -> Self {
+        match content {
+            MessageContent::Text(text) => vec![ContentBlock::Text { text }],
+            MessageContent::Parts(parts) => parts.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<ContentPart> for ContentBlock {
+    fn from(part: ContentPart) -> Self {
+        match part {
+            ContentPart::Text(text) => ContentBlock::Text { text },
+            ContentPart::Image(image) => ContentBlock::Image {
+                source: image.into(),
+            },
+        }
+    }
+}
+
+impl From<ImageContent> for ImageSource {
+    fn from(image: ImageContent) -> Self {
+        let data_url = image
+            .url
+            .strip_prefix("data:")
+            .and_then(|rest| rest.split_once(";base64,"));
+
+        match data_url {
+            Some((media_type, data)) => ImageSource::Base64 {
+                media_type: media_type.to_string(),
+                data: data.to_string(),
+            },
+            None => ImageSource::Url { url: image.url },
+        }
+    }
+}
+
+impl From<&Usage> for TokenUsage {
+    fn from(usage: &Usage) -> Self {
+        TokenUsage::new(usage.input_tokens, usage.output_tokens)
+    }
+}
+
+impl From<ResponseOk> for RequestMessage {
+    fn from(response: ResponseOk) -> Self {
+        RequestMessage {
+            role: Role::Assistant,
+            content: response.content,
+        }
+    }
+}
+
+impl From<ResponseOk> for PromptMessage {
+    fn from(response: ResponseOk) -> Self {
+        let text = response.text();
+        PromptMessage::Assistant(AssistantMessage {
+            content: (!text.is_empty()).then_some(text),
+            name: None,
+            refusal: None,
+            tool_calls: response.tool_calls(),
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        models::{ImageDetail, ToolCall},
+        prompt,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_model_anthropic_request_from_prompt() -> anyhow::Result<()> {
+        let mut prompt = Prompt::new();
+        prompt.push(PromptMessage::system("You are a weather bot."));
+        prompt.push(PromptMessage::system("Answer briefly."));
+        prompt.push(PromptMessage::User(UserMessage {
+            content: MessageContent::Parts(vec![
+                ContentPart::Text("What's the weather here?".to_string()),
+                ContentPart::Image(ImageContent {
+                    url: "data:image/png;base64,iVBORw0K".to_string(),
+                    detail: Some(ImageDetail::Low),
+                }),
+            ]),
+            name: None,
+        }));
+        prompt.push(PromptMessage::Assistant(AssistantMessage {
+            content: None,
+            name: None,
+            refusal: None,
+            tool_calls: vec![
+                ToolCall::new("toolu_a", "get_weather", r#"{"city":"Tokyo"}"#),
+                ToolCall::new("toolu_b", "get_time", "not json"),
+            ],
+        }));
+        prompt.push(PromptMessage::tool("toolu_a", "Sunny"));
+        prompt.push(PromptMessage::tool("toolu_b", "Noon"));
+
+        let messages = RequestMessages::from(prompt);
+        assert_eq!(
+            serde_json::to_value(&messages)?,
+            json!({
+                "system": "You are a weather bot.\n\nAnswer briefly.",
+                "messages": [
+                    {
+                        "role": "user",
+                        "content": [
+                            { "type": "text", "text": "What's the weather here?" },
+                            {
+                                "type": "image",
+                                "source": {
+                                    "type": "base64",
+                                    "media_type": "image/png",
+                                    "data": "iVBORw0K"
+                                }
+                            }
+                        ]
+                    },
+                    {
+                        "role": "assistant",
+                        "content": [
+                            {
+                                "type": "tool_use",
+                                "id": "toolu_a",
+                                "name": "get_weather",
+                                "input": { "city": "Tokyo" }
+                            },
+                            { "type": "tool_use", "id": "toolu_b", "name": "get_time", "input": {} }
+                        ]
+                    },
+                    {
+                        "role": "user",
+                        "content": [
+                            { "type": "tool_result", "tool_use_id": "toolu_a", "content": "Sunny" },
+                            { "type": "tool_result", "tool_use_id": "toolu_b", "content": "Noon" }
+                        ]
+                    }
+                ]
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_anthropic_request_ends_with_user_turn() -> anyhow::Result<()> {
+        // The shape of a prompt made from a dreamer thread.
+        let prompt = prompt! {
+            system: "You are Dreamer.",
+            user: "[notification]\nMessage from the user!",
+            assistant: "[action]\n{\"name\":\"inbox\",\"args\":{}}",
+            user: "[observation]\nWhat is 1 + 1?",
+            assistant: "[thought]\nThe answer is 2.",
+            assistant: "[context]\n1. \"[memory] math: 1 + 1 = 2\" [today]"
+        };
+
+        let messages = RequestMessages::from(prompt);
+        assert_eq!(
+            serde_json::to_value(&messages)?,
+            json!({
+                "system": "You are Dreamer.",
+                "messages": [
+                    {
+                        "role": "user",
+                        "content": [
+                            { "type": "text", "text": "[notification]\nMessage from the user!" }
+                        ]
+                    },
+                    {
+                        "role": "assistant",
+                        "content": [
+                            { "type": "text", "text": "[action]\n{\"name\":\"inbox\",\"args\":{}}" }
+                        ]
+                    },
+                    {
+                        "role": "user",
+                        "content": [
+                            { "type": "text", "text": "[observation]\nWhat is 1 + 1?" },
+                            { "type": "text", "text": "[thought]\nThe answer is 2." },
+                            {
+                                "type": "text",
+                                "text": "[context]\n1. \"[memory] math: 1 + 1 = 2\" [today]"
+                            }
+                        ]
+                    }
+                ]
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_anthropic_response_tool_uses() -> anyhow::Result<()> {
+        let body = json!({
+            "id": "msg_123",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-5-sonnet-latest",
+            "content": [
+                { "type": "text", "text": "Let me check." },
+                {
+                    "type": "tool_use",
+                    "id": "toolu_a",
+                    "name": "get_weather",
+                    "input": { "city": "Tokyo" }
+                }
+            ],
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+            "usage": { "input_tokens": 40, "output_tokens": 12 }
+        });
+
+        let response: ResponseOk = serde_json::from_value(body)?;
+        assert_eq!(response.text(), "Let me check.");
+        assert_eq!(
+            response.tool_calls(),
+            vec![ToolCall::new(
+                "toolu_a",
+                "get_weather",
+                r#"{"city":"Tokyo"}"#
+            )]
+        );
+        assert_eq!(TokenUsage::from(&response.usage), TokenUsage::new(40, 12));
+
+        let error: ResponseBody = serde_json::from_value(json!({
+            "type": "error",
+            "error": { "type": "overloaded_error", "message": "Overloaded" }
+        }))?;
+        assert_eq!(error.unwrap_err().to_string(), "Overloaded");
+
+        Ok(())
+    }
+}
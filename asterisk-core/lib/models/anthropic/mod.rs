@@ -0,0 +1,17 @@
+//! Module for working with Anthropic models.
+
+mod builder;
+mod config;
+mod message;
+mod model;
+mod stream;
+
+//--------------------------------------------------------------------------------------------------
+// Exports
+//--------------------------------------------------------------------------------------------------
+
+pub use builder::*;
+pub use config::*;
+pub use message::*;
+pub use model::*;
+pub use stream::*;
@@ -0,0 +1,357 @@
+use std::borrow::Cow;
+
+use futures::{future::BoxFuture, stream::BoxStream};
+use reqwest::RequestBuilder;
+use tracing::debug;
+
+use crate::models::{
+    http,
+    retry::{self, RetryPolicy},
+    ChatModel, ModelError, ModelResponse, ModelResult, Prompt, StreamEvent, TextModel,
+    TextStreamModel, TokenUsage,
+};
+
+use super::{
+    Config, ModelBuilder, RequestBody, RequestMessages, ResponseBody, ResponseError, ResponseOk,
+    ResponseStream, ANTHROPIC_API_URL, ANTHROPIC_VERSION,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `AnthropicModel` is a type that can prompt and stream responses from models provided by
+/// Anthropic through the Messages API.
+#[derive(Debug, Clone)]
+pub struct AnthropicModel {
+    pub(crate) config: Cow<'static, Config>,
+    pub(crate) base_url: String,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) client: reqwest::Client,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl AnthropicModel {
+    /// Creates a builder for the model.
+    pub fn builder() -> ModelBuilder {
+        ModelBuilder::default()
+    }
+
+    /// Calls the API with the given request messages.
+    pub async fn call(&self, messages: impl Into<RequestMessages>) -> ModelResult<ResponseOk> {
+        let config = self.get_config_without_streaming();
+        debug!("config = {config:#?}");
+
+        let request = self.request(messages.into(), config)?;
+        let response = self
+            .retry
+            .send(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
+
+        let body = response.text().await?;
+        debug!("body = {body:#?}");
+
+        let body: ResponseBody = serde_json::from_str(&body)?;
+        let ResponseBody::Ok(body) = body else {
+            return Err(ModelError::AnthropicResponseError(body.unwrap_err()));
+        };
+
+        Ok(*body)
+    }
+
+    /// Calls the API with the given request messages and gets back a stream of response events.
+    pub fn call_streaming(
+        &self,
+        messages: impl Into<RequestMessages>,
+    ) -> ModelResult<ResponseStream> {
+        let config = self.get_config_with_streaming();
+        debug!("config = {config:#?}");
+
+        let request = self.request(messages.into(), config)?;
+        Ok(ResponseStream::new(request, self.retry.clone()))
+    }
+
+    /// Builds a request to the API with the given messages and configuration.
+    fn request(
+        &self,
+        messages: RequestMessages,
+        config: Cow<'_, Config>,
+    ) -> ModelResult<RequestBuilder> {
+        debug!("messages = {}", serde_json::to_string(&messages).unwrap());
+
+        let api_key = config.api_key.as_ref().ok_or(ModelError::NoAPIKeyFound)?;
+        Ok(self
+            .client
+            .post(&self.base_url)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&RequestBody {
+                messages,
+                config: config.into_owned(),
+            }))
+    }
+
+    /// Gets the model's configuration with streaming enabled.
+    fn get_config_with_streaming(&self) -> Cow<'_, Config> {
+        let mut config = Cow::Borrowed(self.config.as_ref());
+
+        if self.config.stream.is_none() {
+            config.to_mut().stream = Some(true);
+        }
+
+        config
+    }
+
+    /// Gets the model's configuration without streaming enabled.
+    fn get_config_without_streaming(&self) -> Cow<'_, Config> {
+        let mut config = Cow::Borrowed(self.config.as_ref());
+
+        if self.config.stream.is_some() {
+            config.to_mut().stream = Some(false);
+        }
+
+        config
+    }
+
+    /// Get the model's configuration
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Get the HTTP client the model sends requests with
+    pub fn get_client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Get the model's retry policy
+    pub fn get_retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl TextModel for AnthropicModel {
+    async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
+        let response = self.call(prompt.into()).await?;
+        Ok(response.text())
+    }
+
+    async fn prompt_with_usage(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<ModelResponse> {
+        let response = self.call(prompt.into()).await?;
+        Ok(ModelResponse {
+            content: response.text(),
+            usage: Some(TokenUsage::from(&response.usage)),
+            model: response.model,
+        })
+    }
+}
+
+impl TextStreamModel for AnthropicModel {
+    async fn prompt_stream(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<BoxStream<'static, ModelResult<StreamEvent>>> {
+        Ok(self.call_streaming(prompt.into())?.into_events())
+    }
+}
+
+impl ChatModel for AnthropicModel {
+    fn model_id(&self) -> &str {
+        &self.config.model
+    }
+
+    fn chat(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<String>> {
+        Box::pin(self.prompt(prompt))
+    }
+
+    fn chat_with_usage(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        Box::pin(self.prompt_with_usage(prompt))
+    }
+
+    fn chat_stream(
+        &self,
+        prompt: Prompt,
+    ) -> BoxFuture<'_, ModelResult<BoxStream<'static, ModelResult<StreamEvent>>>> {
+        Box::pin(self.prompt_stream(prompt))
+    }
+}
+
+impl Default for AnthropicModel {
+    fn default() -> Self {
+        Self {
+            config: Cow::Owned(Config::default()),
+            base_url: ANTHROPIC_API_URL.to_string(),
+            retry: RetryPolicy::default(),
+            client: http::default_client(),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use futures::StreamExt;
+
+    use crate::{
+        models::{
+            anthropic::{ModelType, DEFAULT_MAX_TOKENS},
+            retry::tests::{http_response, mock_server},
+            StreamAccumulator, ToolCall,
+        },
+        prompt,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_model_anthropic_builders() {
+        let model = AnthropicModel::builder().api_key("sk-ant-test").build();
+
+        assert_eq!(model.base_url, ANTHROPIC_API_URL.to_string());
+        assert_eq!(model.config.api_key.as_deref(), Some("sk-ant-test"));
+        assert_eq!(model.config.model, ModelType::Claude3_5Sonnet.to_string());
+        assert_eq!(model.config.max_tokens, DEFAULT_MAX_TOKENS);
+        assert_eq!(model.config.stream, None);
+        assert_eq!(model.config.temperature, None);
+        assert_eq!(model.config.tools, None);
+
+        let model: Box<dyn ChatModel> = Box::new(
+            AnthropicModel::builder()
+                .model(ModelType::Claude3_5Haiku)
+                .max_tokens(1024)
+                .build(),
+        );
+        assert_eq!(model.model_id(), "claude-3-5-haiku-latest");
+    }
+
+    #[tokio::test]
+    async fn test_model_anthropic_prompt_with_usage() {
+        let body = r#"{"id":"msg_1","type":"message","role":"assistant","model":"claude-3-5-sonnet-latest","content":[{"type":"text","text":"Hello!"}],"stop_reason":"end_turn","stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":3}}"#;
+        let (url, hits) = mock_server(vec![
+            http_response(
+                "529 Site Overloaded",
+                &[("retry-after", "0")],
+                r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            ),
+            http_response("200 OK", &[("content-type", "application/json")], body),
+        ])
+        .await;
+
+        let model = AnthropicModel::builder()
+            .base_url(url)
+            .api_key("sk-ant-test")
+            .retry_policy(RetryPolicy::default().backoff(Duration::ZERO, Duration::ZERO))
+            .build();
+
+        let response = model
+            .prompt_with_usage(prompt! { system: "Be nice.", user: "Hi" })
+            .await
+            .unwrap();
+        assert_eq!(response.content, "Hello!");
+        assert_eq!(response.model, "claude-3-5-sonnet-latest");
+        assert_eq!(response.usage, Some(TokenUsage::new(9, 3)));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_model_anthropic_prompt_stream() {
+        let events = [
+            r#"{"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","model":"claude-3-5-sonnet-latest","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":12,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"lo"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_a","name":"wave","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{}"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":5}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let body: String = events
+            .iter()
+            .map(|event| {
+                let name = event.split('"').nth(3).unwrap();
+                format!("event: {name}\ndata: {event}\n\n")
+            })
+            .collect();
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "text/event-stream")],
+            &body,
+        )])
+        .await;
+
+        let model = AnthropicModel::builder()
+            .base_url(url)
+            .api_key("sk-ant-test")
+            .build();
+
+        let stream = model.prompt_stream(prompt! { user: "Hi" }).await.unwrap();
+        let response = StreamAccumulator::collect(stream).await.unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("Hello"));
+        assert_eq!(
+            response.message.tool_calls,
+            vec![ToolCall::new("toolu_a", "wave", "{}")]
+        );
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_use"));
+        assert_eq!(response.usage, Some(TokenUsage::new(12, 5)));
+
+        let response = model
+            .call_streaming(prompt! { user: "Hi" })
+            .unwrap()
+            .collect_response()
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.tool_calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_model_anthropic_stream_error_event() {
+        let body = concat!(
+            "event: message_start\n",
+            r#"data: {"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","model":"claude-3-5-sonnet-latest","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":12,"output_tokens":1}}}"#,
+            "\n\nevent: error\n",
+            r#"data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            "\n\n",
+        );
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "text/event-stream")],
+            body,
+        )])
+        .await;
+
+        let model = AnthropicModel::builder()
+            .base_url(url)
+            .api_key("sk-ant-test")
+            .build();
+
+        let events: Vec<_> = model
+            .prompt_stream(prompt! { user: "Hi" })
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0],
+            Err(ModelError::AnthropicResponseError(error)) if error.error.r#type == "overloaded_error"
+        ));
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    collections::BTreeMap,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{ready, stream::BoxStream, Stream, StreamExt};
+use pin_project::pin_project;
+use reqwest::RequestBuilder;
+use serde::Deserialize;
+
+use crate::models::{
+    retry::{RetryEventSource, RetryPolicy},
+    stream, ModelError, ModelResult, StreamEvent, TokenUsage, ToolCallDelta,
+};
+
+use super::{ContentBlock, ErrorInfo, ResponseError, ResponseOk, Usage};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A stream of typed events from the Anthropic API using server-sent events.
+#[pin_project]
+pub struct ResponseStream {
+    #[pin]
+    stream: RetryEventSource,
+    done: bool,
+}
+
+/// An event in a streamed response from the Anthropic API.
+///
+/// A stream starts with `MessageStart`, followed by a `ContentBlockStart`, any number of
+/// `ContentBlockDelta`s and a `ContentBlockStop` for each content block, then `MessageDelta` and
+/// `MessageStop`. `Ping`s may be sent at any point.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseEvent {
+    /// The start of the message, with empty content.
+    MessageStart {
+        /// The message generated so far.
+        message: ResponseOk,
+    },
+
+    /// The start of a content block.
+    ContentBlockStart {
+        /// The position of the block in the message content.
+        index: usize,
+
+        /// The block, with empty text or input.
+        content_block: ContentBlock,
+    },
+
+    /// A piece of a content block.
+    ContentBlockDelta {
+        /// The position of the block in the message content.
+        index: usize,
+
+        /// The piece of the block.
+        delta: ContentDelta,
+    },
+
+    /// The end of a content block.
+    ContentBlockStop {
+        /// The position of the block in the message content.
+        index: usize,
+    },
+
+    /// A change to the top-level fields of the message.
+    MessageDelta {
+        /// The changed fields.
+        delta: MessageDelta,
+
+        /// The cumulative output token usage.
+        usage: Usage,
+    },
+
+    /// The end of the message.
+    MessageStop,
+
+    /// A keep-alive event.
+    Ping,
+
+    /// An error that interrupted the stream, like the API being overloaded.
+    Error {
+        /// The error information.
+        error: ErrorInfo,
+    },
+}
+
+/// A piece of a content block.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentDelta {
+    /// A piece of the text of a text block.
+    TextDelta {
+        /// The piece of text.
+        text: String,
+    },
+
+    /// A piece of the JSON input of a tool use block.
+    InputJsonDelta {
+        /// The piece of JSON.
+        partial_json: String,
+    },
+}
+
+/// A change to the top-level fields of the message.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MessageDelta {
+    /// The reason the model stopped generating tokens.
+    pub stop_reason: Option<String>,
+
+    /// The custom stop sequence that was generated, if any.
+    pub stop_sequence: Option<String>,
+}
+
+/// Folds the events of a streamed response back into the response `AnthropicModel::call` would
+/// have returned.
+#[derive(Debug, Default)]
+pub struct ResponseAccumulator {
+    message: Option<ResponseOk>,
+    partial_json: BTreeMap<usize, String>,
+}
+
+/// Turns response events into stream events.
+///
+/// The events of a tool use are keyed by the position of its content block, which counts the text
+/// blocks too, so they are renumbered among the tool uses. The input token usage is only sent at
+/// the start of the message, so it is kept until the output usage arrives at the end.
+#[derive(Debug, Default)]
+struct EventMapper {
+    input_tokens: u64,
+    tool_uses: BTreeMap<usize, usize>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ResponseStream {
+    /// Creates a new `ResponseStream` from the given request, reconnecting according to the
+    /// retry policy if the connection fails before the response starts.
+    pub fn new(request: RequestBuilder, retry: RetryPolicy) -> Self {
+        let stream = RetryEventSource::new(request, retry).error_body::<ResponseError>();
+        Self {
+            stream,
+            done: false,
+        }
+    }
+
+    /// Consumes the stream and folds the events into a whole response.
+    pub async fn collect_response(mut self) -> ModelResult<ResponseOk> {
+        let mut accumulator = ResponseAccumulator::new();
+        while let Some(event) = self.next().await {
+            accumulator.push(event?);
+        }
+
+        accumulator.finish()
+    }
+
+    /// Converts the stream into a stream of provider-independent events.
+    pub fn into_events(self) -> BoxStream<'static, ModelResult<StreamEvent>> {
+        let mut mapper = EventMapper::default();
+        stream::flatten_events(self.map(move |event| event.map(|event| mapper.map(event))))
+    }
+}
+
+impl ResponseAccumulator {
+    /// Creates a new, empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an event to the response.
+    pub fn push(&mut self, event: ResponseEvent) {
+        if let ResponseEvent::MessageStart { message } = event {
+            self.message = Some(message);
+            return;
+        }
+
+        let Some(message) = self.message.as_mut() else {
+            return;
+        };
+
+        match event {
+            ResponseEvent::ContentBlockStart {
+                index,
+                content_block,
+            } => {
+                if index >= message.content.len() {
+                    message.content.push(content_block);
+                }
+            }
+            ResponseEvent::ContentBlockDelta { index, delta } => match delta {
+                ContentDelta::TextDelta { text: delta } => {
+                    if let Some(ContentBlock::Text { text }) = message.content.get_mut(index) {
+                        text.push_str(&delta);
+                    }
+                }
+                ContentDelta::InputJsonDelta { partial_json } => {
+                    self.partial_json
+                        .entry(index)
+                        .or_default()
+                        .push_str(&partial_json);
+                }
+            },
+            ResponseEvent::ContentBlockStop { index } => {
+                let json = self.partial_json.remove(&index).unwrap_or_default();
+                if let Some(ContentBlock::ToolUse { input, .. }) = message.content.get_mut(index) {
+                    if let Ok(value) = serde_json::from_str(&json) {
+                        *input = value;
+                    }
+                }
+            }
+            ResponseEvent::MessageDelta { delta, usage } => {
+                message.stop_reason = delta.stop_reason.or(message.stop_reason.take());
+                message.stop_sequence = delta.stop_sequence.or(message.stop_sequence.take());
+                message.usage.output_tokens = usage.output_tokens;
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the response accumulated so far, failing if the stream never started a message.
+    pub fn finish(self) -> ModelResult<ResponseOk> {
+        self.message
+            .ok_or_else(|| ModelError::custom(anyhow::anyhow!("The stream sent no message")))
+    }
+}
+
+impl EventMapper {
+    fn map(&mut self, event: ResponseEvent) -> Vec<StreamEvent> {
+        match event {
+            ResponseEvent::MessageStart { message } => {
+                self.input_tokens = message.usage.input_tokens;
+                vec![]
+            }
+            ResponseEvent::ContentBlockStart {
+                index,
+                content_block: ContentBlock::ToolUse { id, name, .. },
+            } => {
+                let tool_index = self.tool_uses.len();
+                self.tool_uses.insert(index, tool_index);
+                vec![StreamEvent::ToolCallDelta(ToolCallDelta {
+                    index: tool_index,
+                    id: Some(id),
+                    name: Some(name),
+                    arguments: String::new(),
+                })]
+            }
+            ResponseEvent::ContentBlockDelta { index, delta } => match delta {
+                ContentDelta::TextDelta { text } if !text.is_empty() => {
+                    vec![StreamEvent::ContentDelta(text)]
+                }
+                ContentDelta::InputJsonDelta { partial_json } => match self.tool_uses.get(&index) {
+                    Some(&tool_index) if !partial_json.is_empty() => {
+                        vec![StreamEvent::ToolCallDelta(ToolCallDelta {
+                            index: tool_index,
+                            id: None,
+                            name: None,
+                            arguments: partial_json,
+                        })]
+                    }
+                    _ => vec![],
+                },
+                _ => vec![],
+            },
+            ResponseEvent::MessageDelta { delta, usage } => delta
+                .stop_reason
+                .map(StreamEvent::FinishReason)
+                .into_iter()
+                .chain([StreamEvent::Usage(TokenUsage::new(
+                    self.input_tokens,
+                    usage.output_tokens,
+                ))])
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Stream for ResponseStream {
+    type Item = ModelResult<ResponseEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+
+        let Some(data) = ready!(this.stream.poll_next(cx)) else {
+            return Poll::Ready(None);
+        };
+
+        let event: ResponseEvent = serde_json::from_str(&data?)?;
+        match event {
+            ResponseEvent::MessageStop => {
+                *this.done = true;
+                Poll::Ready(Some(Ok(event)))
+            }
+            ResponseEvent::Error { error } => {
+                *this.done = true;
+                Poll::Ready(Some(Err(ModelError::AnthropicResponseError(
+                    ResponseError { error },
+                ))))
+            }
+            event => Poll::Ready(Some(Ok(event))),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const EVENTS: [&str; 11] = [
+        r#"{"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","model":"claude-3-5-sonnet-latest","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":30,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        r#"{"type":"ping"}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me check."}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_a","name":"get_weather","input":{}}}"#,
+        r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"city\":"}}"#,
+        r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"Tokyo\"}"}}"#,
+        r#"{"type":"content_block_stop","index":1}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":9}}"#,
+        r#"{"type":"message_stop"}"#,
+    ];
+
+    fn events() -> Vec<ResponseEvent> {
+        EVENTS
+            .iter()
+            .map(|event| serde_json::from_str(event).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_anthropic_stream_events() {
+        let mut mapper = EventMapper::default();
+        let events: Vec<StreamEvent> = events()
+            .into_iter()
+            .flat_map(|event| mapper.map(event))
+            .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::ContentDelta("Let me check.".to_string()),
+                StreamEvent::ToolCallDelta(ToolCallDelta {
+                    index: 0,
+                    id: Some("toolu_a".to_string()),
+                    name: Some("get_weather".to_string()),
+                    arguments: String::new(),
+                }),
+                StreamEvent::ToolCallDelta(ToolCallDelta {
+                    index: 0,
+                    id: None,
+                    name: None,
+                    arguments: "{\"city\":".to_string(),
+                }),
+                StreamEvent::ToolCallDelta(ToolCallDelta {
+                    index: 0,
+                    id: None,
+                    name: None,
+                    arguments: "\"Tokyo\"}".to_string(),
+                }),
+                StreamEvent::FinishReason("tool_use".to_string()),
+                StreamEvent::Usage(TokenUsage::new(30, 9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_anthropic_stream_accumulator() -> anyhow::Result<()> {
+        let mut accumulator = ResponseAccumulator::new();
+        for event in events() {
+            accumulator.push(event);
+        }
+
+        let response = accumulator.finish()?;
+        assert_eq!(response.id, "msg_1");
+        assert_eq!(response.text(), "Let me check.");
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        assert_eq!(response.usage.input_tokens, 30);
+        assert_eq!(response.usage.output_tokens, 9);
+        assert_eq!(
+            response.content[1],
+            ContentBlock::ToolUse {
+                id: "toolu_a".to_string(),
+                name: "get_weather".to_string(),
+                input: json!({ "city": "Tokyo" }),
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+use super::{EmbeddingModel, ModelResult, TokenUsage};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The embeddings of a batch of texts, in the same order as the texts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmbeddingResponse {
+    /// One embedding vector per input text.
+    pub embeddings: Vec<Vec<f32>>,
+
+    /// The model that produced the embeddings.
+    pub model: String,
+
+    /// The token usage of the call, if the provider reports it.
+    pub usage: Option<TokenUsage>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl EmbeddingResponse {
+    /// Appends the embeddings of another batch, adding up the usage of both.
+    pub fn extend(&mut self, other: EmbeddingResponse) {
+        self.embeddings.extend(other.embeddings);
+        self.model = other.model;
+        self.usage = match (self.usage, other.usage) {
+            (Some(a), Some(b)) => Some(TokenUsage {
+                prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+                completion_tokens: a.completion_tokens + b.completion_tokens,
+                total_tokens: a.total_tokens + b.total_tokens,
+            }),
+            (usage, None) | (None, usage) => usage,
+        };
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<T: EmbeddingModel + ?Sized> EmbeddingModel for Box<T> {
+    fn model_id(&self) -> &str {
+        (**self).model_id()
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        (**self).dimensions()
+    }
+
+    fn batch_size(&self) -> usize {
+        (**self).batch_size()
+    }
+
+    fn embed_batch(&self, texts: Vec<String>) -> BoxFuture<'_, ModelResult<EmbeddingResponse>> {
+        (**self).embed_batch(texts)
+    }
+}
+
+impl<T: EmbeddingModel + ?Sized> EmbeddingModel for Arc<T> {
+    fn model_id(&self) -> &str {
+        (**self).model_id()
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        (**self).dimensions()
+    }
+
+    fn batch_size(&self) -> usize {
+        (**self).batch_size()
+    }
+
+    fn embed_batch(&self, texts: Vec<String>) -> BoxFuture<'_, ModelResult<EmbeddingResponse>> {
+        (**self).embed_batch(texts)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::future;
+
+    use super::*;
+
+    /// A model that embeds each text as its length and records the batches it was called with.
+    #[derive(Default)]
+    struct Lengths {
+        batches: Mutex<Vec<usize>>,
+    }
+
+    impl EmbeddingModel for Lengths {
+        fn model_id(&self) -> &str {
+            "lengths"
+        }
+
+        fn dimensions(&self) -> Option<usize> {
+            Some(1)
+        }
+
+        fn batch_size(&self) -> usize {
+            2
+        }
+
+        fn embed_batch(&self, texts: Vec<String>) -> BoxFuture<'_, ModelResult<EmbeddingResponse>> {
+            self.batches.lock().unwrap().push(texts.len());
+            Box::pin(future::ready(Ok(EmbeddingResponse {
+                embeddings: texts.iter().map(|text| vec![text.len() as f32]).collect(),
+                model: "lengths".to_string(),
+                usage: Some(TokenUsage::new(texts.len() as u64, 0)),
+            })))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_models_embedding_batches() -> anyhow::Result<()> {
+        let model = Arc::new(Lengths::default());
+
+        let texts = ["a", "bb", "ccc", "dddd", "eeeee"]
+            .map(String::from)
+            .to_vec();
+        let response = model.embed_all(texts).await?;
+        assert_eq!(
+            response.embeddings,
+            vec![vec![1.0], vec![2.0], vec![3.0], vec![4.0], vec![5.0]]
+        );
+        assert_eq!(response.usage.unwrap().total_tokens, 5);
+        assert_eq!(*model.batches.lock().unwrap(), vec![2, 2, 1]);
+
+        assert_eq!(model.embed("hello".to_string()).await?, vec![5.0]);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,9 @@
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, time::Duration};
 
-use reqwest::Response;
+use reqwest::StatusCode;
 use thiserror::Error;
 
-use super::{ollama, openai};
+use super::{anthropic, gemini, ollama, openai, StructuredOutputError};
 
 //-------------------------------------------------------------------------------------------------
 // Types
@@ -19,6 +19,14 @@ pub enum ModelError {
     #[error("Failed to send request to API")]
     RequestError(#[from] reqwest::Error),
 
+    /// Error that occurs when the API responds with an unsuccessful HTTP status.
+    #[error(transparent)]
+    StatusError(#[from] StatusError),
+
+    /// Error that occurs when a streaming response goes quiet for longer than the timeout.
+    #[error("Timed out after {0:?} waiting for the API")]
+    Timeout(Duration),
+
     /// Error that occurs when the API returns an error from OpenAI.
     #[error("OpenAI error: {0}")]
     OpenAIResponseError(#[from] openai::ResponseError),
@@ -27,6 +35,23 @@ pub enum ModelError {
     #[error("Ollama error: {0}")]
     OllamaResponseError(#[from] ollama::ResponseError),
 
+    /// Error that occurs when the API returns an error from Anthropic.
+    #[error("Anthropic error: {0}")]
+    AnthropicResponseError(#[from] anthropic::ResponseError),
+
+    /// Error that occurs when the API returns an error from Gemini.
+    #[error("Gemini error: {0}")]
+    GeminiResponseError(#[from] gemini::ResponseError),
+
+    /// Error that occurs when Gemini blocks the prompt, usually because of the safety settings.
+    #[error("Gemini blocked the prompt: {0}")]
+    GeminiPromptBlocked(gemini::BlockedError),
+
+    /// Error that occurs when Gemini stops generating a response, usually because of the safety
+    /// settings.
+    #[error("Gemini blocked the response: {0}")]
+    GeminiResponseBlocked(gemini::BlockedError),
+
     /// Error that occurs when the API key is not found.
     #[error("No API key found")]
     NoAPIKeyFound,
@@ -39,21 +64,75 @@ pub enum ModelError {
     #[error("Failed to parse response from API")]
     ParseError(#[from] serde_json::Error),
 
+    /// Error that occurs when a model profile cannot be found in the registry.
+    #[error("Model profile not found: {0}")]
+    ProfileNotFound(String),
+
+    /// Error that occurs when a model profile is missing required information.
+    #[error("Invalid model profile `{0}`: {1}")]
+    InvalidProfile(String, String),
+
+    /// Error that occurs when an environment variable the model relies on is not set.
+    #[error("Environment variable not set: {0}")]
+    EnvVarNotFound(String),
+
+    /// Error that occurs when the HTTP client configuration is invalid.
+    #[error("Invalid HTTP client config: {0}")]
+    InvalidHttpConfig(String),
+
+    /// Error that occurs when reading a file fails.
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// Error that occurs when parsing a TOML file fails.
+    #[error("Failed to parse TOML: {0}")]
+    TomlParseError(#[from] toml::de::Error),
+
+    /// Error that occurs when the model keeps making tool calls without producing a final response.
+    #[error("Model did not produce a final response after {0} tool call rounds")]
+    ToolCallRoundsExceeded(usize),
+
+    /// Error that occurs when a structured reply is still invalid after the repair attempts.
+    #[error("Invalid structured output: {0}")]
+    StructuredOutputError(#[from] StructuredOutputError),
+
+    /// Error that occurs when an image is given by a URL the backend can't fetch, so it must be
+    /// sent as a `data:` URL instead.
+    #[error("Unsupported image URL, send the image as a data URL instead: {0}")]
+    UnsupportedImageUrl(String),
+
+    /// Error that occurs when the API returns fewer embeddings than texts it was given.
+    #[error("The API returned no embedding for one or more texts")]
+    EmptyEmbeddingResponse,
+
     /// Custom error.
     #[error(transparent)]
     Custom(#[from] AnyError),
 }
 
+/// Error that occurs when the API responds with an unsuccessful HTTP status.
+#[derive(Debug, Error)]
+#[error("API responded with {status}: {message}")]
+pub struct StatusError {
+    /// The HTTP status of the response.
+    pub status: StatusCode,
+
+    /// How long the server asked the client to wait before retrying, if it said.
+    pub retry_after: Option<Duration>,
+
+    /// The error message from the response body, or the status reason if there was none.
+    pub message: String,
+
+    /// The response body, or an empty string if it was not available.
+    pub body: String,
+}
+
 /// Error type for the response stream from the OpenAI API.
 #[derive(Debug, Error)]
 pub enum ResponseStreamError {
-    /// Error related to the OpenAI API.
-    #[error("API response error")]
-    APIResponse(Response),
-
     /// Other errors related to the SSE.
     #[error("EventSource error: {0}")]
-    EventSourceError(#[from] reqwest_eventsource::Error),
+    EventSourceError(Box<reqwest_eventsource::Error>),
 }
 
 /// An error that can represent any error.
@@ -73,6 +152,35 @@ impl ModelError {
             error: error.into(),
         })
     }
+
+    /// Whether the error is transient, so sending the same request again may succeed.
+    ///
+    /// Timeouts, connection failures, rate limits and server errors are retryable. Everything
+    /// else, like an invalid API key or a malformed request, is fatal.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ModelError::RequestError(error) => error.is_timeout() || error.is_connect(),
+            ModelError::StatusError(error) => error.is_retryable(),
+            ModelError::Timeout(_) => true,
+            ModelError::ResponseStreamError(ResponseStreamError::EventSourceError(error)) => {
+                match error.as_ref() {
+                    reqwest_eventsource::Error::Transport(error) => {
+                        error.is_timeout() || error.is_connect()
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// How long the server asked the client to wait before retrying, if it said.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ModelError::StatusError(error) => error.retry_after,
+            _ => None,
+        }
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -89,6 +197,12 @@ pub fn Ok<T>(value: T) -> ModelResult<T> {
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
 
+impl From<reqwest_eventsource::Error> for ResponseStreamError {
+    fn from(error: reqwest_eventsource::Error) -> Self {
+        ResponseStreamError::EventSourceError(Box::new(error))
+    }
+}
+
 impl PartialEq for AnyError {
     fn eq(&self, other: &Self) -> bool {
         self.error.to_string() == other.error.to_string()
@@ -0,0 +1,172 @@
+use std::{borrow::Cow, env};
+
+use crate::models::{http, retry::RetryPolicy};
+
+use super::{
+    Config, GeminiModel, GenerationConfig, ModelType, SafetySetting, Tool, ToolConfig,
+    GEMINI_API_KEY, GEMINI_API_URL,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A builder for a Gemini model.
+#[derive(Debug, Clone, Default)]
+pub struct ModelBuilder {
+    model: Option<String>,
+    base_url: Option<String>,
+    api_key: Option<String>,
+    generation_config: GenerationConfig,
+    safety_settings: Option<Vec<SafetySetting>>,
+    tools: Option<Vec<Tool>>,
+    tool_config: Option<ToolConfig>,
+    retry: RetryPolicy,
+    client: Option<reqwest::Client>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ModelBuilder {
+    /// The ID of the model to use.
+    ///
+    /// Defaults to `ModelType::Gemini1_5Flash`.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// The base URL for making requests to the Gemini API.
+    ///
+    /// Defaults to `GEMINI_API_URL`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// The API key for making requests to the Gemini API.
+    ///
+    /// Defaults to the `GEMINI_API_KEY` environment variable.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Up to 5 sequences where the API will stop generating further tokens.
+    pub fn stop(mut self, stop: Vec<String>) -> Self {
+        self.generation_config.stop_sequences = Some(stop);
+        self
+    }
+
+    /// The MIME type of the generated text, like `application/json`.
+    pub fn response_mime_type(mut self, response_mime_type: impl Into<String>) -> Self {
+        self.generation_config.response_mime_type = Some(response_mime_type.into());
+        self
+    }
+
+    /// The schema the generated JSON must follow, when the response MIME type is
+    /// `application/json`.
+    pub fn response_schema(mut self, response_schema: serde_json::Value) -> Self {
+        self.generation_config.response_schema = Some(response_schema);
+        self
+    }
+
+    /// The maximum number of tokens to generate.
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.generation_config.max_output_tokens = Some(max_tokens);
+        self
+    }
+
+    /// The sampling temperature to use.
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.generation_config.temperature = Some(temperature);
+        self
+    }
+
+    /// An alternative to sampling with temperature, called nucleus sampling, where the model
+    /// considers the results of the tokens with top_p probability mass.
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.generation_config.top_p = Some(top_p);
+        self
+    }
+
+    /// Only sample from the top K options for each subsequent token.
+    pub fn top_k(mut self, top_k: u32) -> Self {
+        self.generation_config.top_k = Some(top_k);
+        self
+    }
+
+    /// A best effort to sample deterministically.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.generation_config.seed = Some(seed);
+        self
+    }
+
+    /// Penalizes new tokens based on whether they appear in the text so far.
+    pub fn presence_penalty(mut self, presence_penalty: f32) -> Self {
+        self.generation_config.presence_penalty = Some(presence_penalty);
+        self
+    }
+
+    /// Penalizes new tokens based on their frequency in the text so far.
+    pub fn frequency_penalty(mut self, frequency_penalty: f32) -> Self {
+        self.generation_config.frequency_penalty = Some(frequency_penalty);
+        self
+    }
+
+    /// Settings for blocking unsafe content, overriding the defaults for their categories.
+    pub fn safety_settings(mut self, safety_settings: Vec<SafetySetting>) -> Self {
+        self.safety_settings = Some(safety_settings);
+        self
+    }
+
+    /// A list of tools the model may call.
+    pub fn tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Controls which (if any) function is called by the model.
+    pub fn tool_config(mut self, tool_config: ToolConfig) -> Self {
+        self.tool_config = Some(tool_config);
+        self
+    }
+
+    /// The policy for retrying failed requests and timing them out.
+    ///
+    /// Defaults to `RetryPolicy::default()`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The HTTP client to send requests with, for example one built from an
+    /// `http::HttpClientConfig` with a proxy or extra CA roots.
+    ///
+    /// Defaults to a pooled client shared by all models.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Builds the Gemini model.
+    pub fn build(self) -> GeminiModel {
+        let config = Config {
+            model: self.model.unwrap_or(ModelType::Gemini1_5Flash.to_string()),
+            api_key: self.api_key.or_else(|| env::var(GEMINI_API_KEY).ok()),
+            generation_config: self.generation_config,
+            safety_settings: self.safety_settings,
+            tools: self.tools,
+            tool_config: self.tool_config,
+        };
+
+        GeminiModel {
+            config: Cow::Owned(config),
+            base_url: self.base_url.unwrap_or(GEMINI_API_URL.to_string()),
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
+        }
+    }
+}
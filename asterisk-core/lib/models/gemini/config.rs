@@ -0,0 +1,251 @@
+use std::env;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The environment variable for the Gemini API key.
+pub const GEMINI_API_KEY: &str = "GEMINI_API_KEY";
+
+/// The URL for the Gemini API, which the model ID and method are appended to.
+pub const GEMINI_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The configuration for the Gemini model.
+///
+/// The model ID is part of the request URL rather than the body.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// The ID of the model to use.
+    #[serde(skip)]
+    pub model: String,
+
+    /// The API key for making requests to the Gemini API.
+    #[serde(skip)]
+    pub api_key: Option<String>,
+
+    /// The options for generating the response.
+    pub generation_config: GenerationConfig,
+
+    /// Settings for blocking unsafe content, overriding the defaults for their categories.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_settings: Option<Vec<SafetySetting>>,
+
+    /// A list of tools the model may call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+
+    /// Controls which (if any) function is called by the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<ToolConfig>,
+}
+
+/// The options for generating the response.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    /// Up to 5 sequences where the API will stop generating further tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+
+    /// The MIME type of the generated text, like `application/json`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+
+    /// The schema the generated JSON must follow, when `response_mime_type` is
+    /// `application/json`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
+
+    /// The maximum number of tokens to generate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+
+    /// The sampling temperature to use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// An alternative to sampling with temperature, called nucleus sampling, where the model
+    /// considers the results of the tokens with top_p probability mass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// Only sample from the top K options for each subsequent token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+
+    /// A best effort to sample deterministically.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+
+    /// Penalizes new tokens based on whether they appear in the text so far.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+
+    /// Penalizes new tokens based on their frequency in the text so far.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+}
+
+/// The model type.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Display, Default)]
+pub enum ModelType {
+    /// The Gemini 1.5 Flash model.
+    #[default]
+    #[serde(rename = "gemini-1.5-flash")]
+    #[strum(to_string = "gemini-1.5-flash")]
+    Gemini1_5Flash,
+
+    /// The Gemini 1.5 Flash 8B model.
+    #[serde(rename = "gemini-1.5-flash-8b")]
+    #[strum(to_string = "gemini-1.5-flash-8b")]
+    Gemini1_5Flash8B,
+
+    /// The Gemini 1.5 Pro model.
+    #[serde(rename = "gemini-1.5-pro")]
+    #[strum(to_string = "gemini-1.5-pro")]
+    Gemini1_5Pro,
+}
+
+/// A setting for blocking unsafe content of a category.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SafetySetting {
+    /// The category the setting applies to.
+    pub category: HarmCategory,
+
+    /// The probability of harm at and above which content is blocked.
+    pub threshold: HarmBlockThreshold,
+}
+
+/// A category of harmful content.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum HarmCategory {
+    /// Negative or harmful comments targeting identity or protected attributes.
+    HarmCategoryHarassment,
+
+    /// Content that is rude, disrespectful, or profane.
+    HarmCategoryHateSpeech,
+
+    /// Content that contains references to sexual acts or other lewd content.
+    HarmCategorySexuallyExplicit,
+
+    /// Content that promotes, facilitates, or encourages harmful acts.
+    HarmCategoryDangerousContent,
+
+    /// Content that may be used to harm civic integrity.
+    HarmCategoryCivicIntegrity,
+
+    /// Any category this client doesn't know about.
+    #[serde(other)]
+    HarmCategoryUnspecified,
+}
+
+/// The probability of harm at and above which content is blocked.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HarmBlockThreshold {
+    /// Block content with a low or higher probability of harm.
+    BlockLowAndAbove,
+
+    /// Block content with a medium or higher probability of harm.
+    BlockMediumAndAbove,
+
+    /// Block content with a high probability of harm.
+    BlockOnlyHigh,
+
+    /// Block no content.
+    BlockNone,
+
+    /// Turn the safety filter off.
+    Off,
+}
+
+/// A tool that the model may call.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Tool {
+    /// The functions that the tool provides.
+    pub function_declarations: Vec<FunctionDeclaration>,
+}
+
+/// A function that the model may call.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct FunctionDeclaration {
+    /// The name of the function.
+    pub name: String,
+
+    /// A description of what the function does, used by the model to choose when and how to call
+    /// the function.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The parameters that the function accepts, described as an OpenAPI schema object.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
+}
+
+/// Controls which (if any) function is called by the model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolConfig {
+    /// The function calling configuration.
+    pub function_calling_config: FunctionCallingConfig,
+}
+
+/// The function calling configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionCallingConfig {
+    /// How the model calls functions.
+    pub mode: FunctionCallingMode,
+
+    /// The functions the model may call when `mode` is `Any`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_function_names: Option<Vec<String>>,
+}
+
+/// How the model calls functions.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum FunctionCallingMode {
+    /// The model decides whether to call a function or respond with text.
+    Auto,
+
+    /// The model must call a function.
+    Any,
+
+    /// The model will not call any function.
+    None,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model: ModelType::Gemini1_5Flash.to_string(),
+            api_key: env::var(GEMINI_API_KEY).ok(),
+            generation_config: GenerationConfig::default(),
+            safety_settings: None,
+            tools: None,
+            tool_config: None,
+        }
+    }
+}
+
+impl From<ModelType> for String {
+    fn from(value: ModelType) -> Self {
+        value.to_string()
+    }
+}
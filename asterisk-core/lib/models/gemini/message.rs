@@ -0,0 +1,720 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::{
+    self, AssistantMessage, ContentPart, ImageContent, MessageContent, ModelError, ModelResult,
+    Prompt, PromptMessage, SystemMessage, TokenUsage, ToolMessage, UserMessage,
+};
+
+use super::{Config, HarmCategory};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The finish reasons of a candidate whose content was blocked rather than completed.
+pub const BLOCKED_FINISH_REASONS: [&str; 5] = [
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+];
+
+//--------------------------------------------------------------------------------------------------
+// Types: Request
+//--------------------------------------------------------------------------------------------------
+
+/// The body of a request to the Gemini API.
+#[derive(Debug, Serialize)]
+pub struct RequestBody {
+    /// The system instruction and the contents to send to the model.
+    #[serde(flatten)]
+    pub contents: RequestContents,
+
+    /// The model's configuration.
+    #[serde(flatten)]
+    pub config: Config,
+}
+
+/// A conversation with the model.
+///
+/// The Gemini API has no system role, so the system prompt is sent apart from the contents as the
+/// system instruction.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestContents {
+    /// The system instruction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<Content>,
+
+    /// The contents of the conversation.
+    pub contents: Vec<Content>,
+}
+
+/// A message in a chat conversation with the model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Content {
+    /// The role of the message's author. Not set on the system instruction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+
+    /// The parts of the message.
+    #[serde(default)]
+    pub parts: Vec<Part>,
+}
+
+/// The role of a message's author.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// The user, which also sends the results of function calls.
+    User,
+
+    /// The model.
+    Model,
+}
+
+/// A part of a message.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Part {
+    /// A text part.
+    Text(String),
+
+    /// Inline media bytes.
+    InlineData(Blob),
+
+    /// Media referred to by URI.
+    FileData(FileData),
+
+    /// A function call made by the model.
+    FunctionCall(FunctionCall),
+
+    /// The result of a function call, sent back by the user.
+    FunctionResponse(FunctionResponse),
+}
+
+/// Inline media bytes.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Blob {
+    /// The media type of the data, like `image/png`.
+    pub mime_type: String,
+
+    /// The base64 encoded data.
+    pub data: String,
+}
+
+/// Media referred to by URI.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileData {
+    /// The media type of the file, like `image/png`.
+    pub mime_type: String,
+
+    /// The URI of the file.
+    pub file_uri: String,
+}
+
+/// A function call made by the model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionCall {
+    /// The ID of the function call, if the API assigned one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// The name of the function.
+    pub name: String,
+
+    /// The arguments to call the function with.
+    #[serde(default)]
+    pub args: serde_json::Value,
+}
+
+/// The result of a function call.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionResponse {
+    /// The ID of the function call this is the result of, if the API assigned one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// The name of the function that was called.
+    pub name: String,
+
+    /// The output of the function, as a JSON object.
+    pub response: serde_json::Value,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Types: Response
+//--------------------------------------------------------------------------------------------------
+
+/// Response body.
+///
+/// The error variant comes first since every field of a successful response is optional.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ResponseBody {
+    /// An error response.
+    Error(Box<ResponseError>),
+
+    /// A successful response.
+    Ok(Box<ResponseOk>),
+}
+
+/// Represents an error response returned by the Gemini API.
+#[derive(Debug, Deserialize, Error)]
+#[error(transparent)]
+pub struct ResponseError {
+    /// The error information.
+    pub error: ErrorInfo,
+}
+
+/// The error information.
+#[derive(Debug, Deserialize, Error)]
+pub struct ErrorInfo {
+    /// The HTTP status code of the error.
+    pub code: u16,
+
+    /// The error message.
+    pub message: String,
+
+    /// The status of the error, like `INVALID_ARGUMENT`.
+    pub status: String,
+}
+
+/// A response generated by the model, or a chunk of one when streaming.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseOk {
+    /// The candidate responses. Empty if the prompt was blocked.
+    #[serde(default)]
+    pub candidates: Vec<Candidate>,
+
+    /// Feedback on the prompt, saying why it was blocked if it was.
+    pub prompt_feedback: Option<PromptFeedback>,
+
+    /// Usage statistics for the request.
+    pub usage_metadata: Option<UsageMetadata>,
+
+    /// The version of the model that generated the response.
+    pub model_version: Option<String>,
+}
+
+/// A candidate response generated by the model.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    /// The content of the candidate. Missing if it was blocked.
+    pub content: Option<Content>,
+
+    /// The reason the model stopped generating tokens, like `STOP`, `MAX_TOKENS` or `SAFETY`.
+    pub finish_reason: Option<String>,
+
+    /// The ratings of the candidate's safety.
+    #[serde(default)]
+    pub safety_ratings: Vec<SafetyRating>,
+
+    /// The index of the candidate among the candidates.
+    pub index: Option<u32>,
+}
+
+/// Feedback on the prompt.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptFeedback {
+    /// The reason the prompt was blocked, like `SAFETY` or `OTHER`.
+    pub block_reason: Option<String>,
+
+    /// The ratings of the prompt's safety.
+    #[serde(default)]
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+/// The rating of a piece of content's safety in a category.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct SafetyRating {
+    /// The category of harm.
+    pub category: HarmCategory,
+
+    /// The probability of the content being harmful, like `NEGLIGIBLE` or `HIGH`.
+    pub probability: String,
+
+    /// Whether the content was blocked because of this rating.
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+/// Usage statistics for the request.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    /// Number of tokens in the prompt.
+    #[serde(default)]
+    pub prompt_token_count: u64,
+
+    /// Number of tokens in the candidates.
+    #[serde(default)]
+    pub candidates_token_count: u64,
+
+    /// Total number of tokens used in the request.
+    #[serde(default)]
+    pub total_token_count: u64,
+}
+
+/// The prompt or the response was blocked, usually by the safety settings.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct BlockedError {
+    /// The reason for blocking, like `SAFETY`.
+    pub reason: String,
+
+    /// The safety ratings of the blocked content.
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RequestContents {
+    /// Appends a message to the conversation, merging it into the last message if both have the
+    /// same role.
+    pub fn push(&mut self, content: Content) {
+        match self.contents.last_mut() {
+            Some(last) if last.role == content.role => last.parts.extend(content.parts),
+            _ => self.contents.push(content),
+        }
+    }
+
+    /// Appends text to the system instruction.
+    pub fn push_system(&mut self, text: impl Into<String>) {
+        self.system_instruction
+            .get_or_insert_with(|| Content {
+                role: None,
+                parts: Vec::new(),
+            })
+            .parts
+            .push(Part::Text(text.into()));
+    }
+}
+
+impl Content {
+    /// Creates a user message carrying the result of a call to the named function.
+    pub fn function_response(name: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            role: Some(Role::User),
+            parts: vec![Part::FunctionResponse(FunctionResponse {
+                id: None,
+                name: name.into(),
+                response: serde_json::json!({ "content": content.into() }),
+            })],
+        }
+    }
+}
+
+impl ResponseOk {
+    /// Fails with `ModelError::GeminiPromptBlocked` if the prompt was blocked, or with
+    /// `ModelError::GeminiResponseBlocked` if the first candidate was.
+    pub fn check(self) -> ModelResult<Self> {
+        if let Some(feedback) = &self.prompt_feedback {
+            if let Some(reason) = &feedback.block_reason {
+                return Err(ModelError::GeminiPromptBlocked(BlockedError {
+                    reason: reason.clone(),
+                    safety_ratings: feedback.safety_ratings.clone(),
+                }));
+            }
+        }
+
+        if let Some(candidate) = self.candidates.first() {
+            if let Some(reason) = candidate
+                .finish_reason
+                .as_ref()
+                .filter(|reason| BLOCKED_FINISH_REASONS.contains(&reason.as_str()))
+            {
+                return Err(ModelError::GeminiResponseBlocked(BlockedError {
+                    reason: reason.clone(),
+                    safety_ratings: candidate.safety_ratings.clone(),
+                }));
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the parts of the first candidate.
+    pub fn parts(&self) -> &[Part] {
+        self.candidates
+            .first()
+            .and_then(|candidate| candidate.content.as_ref())
+            .map(|content| content.parts.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the text of the first candidate.
+    pub fn text(&self) -> String {
+        self.parts()
+            .iter()
+            .filter_map(|part| match part {
+                Part::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the function calls made in the first candidate as tool calls.
+    pub fn tool_calls(&self) -> Vec<models::ToolCall> {
+        self.parts()
+            .iter()
+            .filter_map(|part| match part {
+                Part::FunctionCall(call) => Some(call.clone().into()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl ResponseBody {
+    /// Gets the error variant or panics.
+    pub fn unwrap_err(self) -> ResponseError {
+        match self {
+            ResponseBody::Error(error) => *error,
+            ResponseBody::Ok(_) => panic!("Called `unwrap_err()` on a `ResponseBody::Ok` value"),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Guesses the media type of an image from the extension of its URL.
+fn image_mime_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    match path
+        .rsplit('.')
+        .next()
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("heic") => "image/heic",
+        _ => "image/jpeg",
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Display for ErrorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Display for BlockedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)?;
+
+        let categories: Vec<String> = self
+            .safety_ratings
+            .iter()
+            .filter(|rating| rating.blocked)
+            .map(|rating| rating.category.to_string())
+            .collect();
+        if !categories.is_empty() {
+            write!(f, " ({})", categories.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Prompt> for RequestContents {
+    fn from(prompt: Prompt) -> Self {
+        // Function responses are matched to their calls by name, so remember the name of each
+        // tool call for the tool messages that only carry its id.
+        let mut names = HashMap::new();
+        let mut contents = Self::default();
+        for message in prompt {
+            match message {
+                PromptMessage::System(SystemMessage { content, .. }) => {
+                    contents.push_system(content)
+                }
+                PromptMessage::Assistant(message) => {
+                    for tool_call in &message.tool_calls {
+                        names.insert(tool_call.id.clone(), tool_call.name.clone());
+                    }
+
+                    contents.push(PromptMessage::Assistant(message).into());
+                }
+                PromptMessage::Tool(ToolMessage {
+                    tool_call_id,
+                    content,
+                    name,
+                }) => {
+                    let name = name
+                        .or_else(|| names.get(&tool_call_id).cloned())
+                        .unwrap_or(tool_call_id);
+                    contents.push(Content::function_response(name, content));
+                }
+                message => contents.push(message.into()),
+            }
+        }
+
+        contents
+    }
+}
+
+impl From<PromptMessage> for Content {
+    fn from(message: PromptMessage) -> Self {
+        match message {
+            // System messages are only sent this way when converted one by one.
+            PromptMessage::System(SystemMessage { content, .. }) => Content {
+                role: Some(Role::User),
+                parts: vec![Part::Text(content)],
+            },
+            PromptMessage::User(UserMessage { content, .. }) => Content {
+                role: Some(Role::User),
+                parts: content.into(),
+            },
+            PromptMessage::Assistant(AssistantMessage {
+                content,
+                refusal,
+                tool_calls,
+                ..
+            }) => {
+                let text = content.or(refusal).filter(|text| !text.is_empty());
+                let calls = tool_calls.into_iter().map(|tool_call| {
+                    Part::FunctionCall(FunctionCall {
+                        id: None,
+                        args: serde_json::from_str(&tool_call.arguments)
+                            .unwrap_or_else(|_| serde_json::json!({})),
+                        name: tool_call.name,
+                    })
+                });
+
+                Content {
+                    role: Some(Role::Model),
+                    parts: text.map(Part::Text).into_iter().chain(calls).collect(),
+                }
+            }
+            PromptMessage::Tool(ToolMessage {
+                tool_call_id,
+                content,
+                name,
+            }) => Content::function_response(name.unwrap_or(tool_call_id), content),
+        }
+    }
+}
+
+impl From<MessageContent> for Vec<Part> {
+    fn from(content: MessageContent) -> Self {
+        match content {
+            MessageContent::Text(text) => vec![Part::Text(text)],
+            MessageContent::Parts(parts) => parts.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<ContentPart> for Part {
+    fn from(part: ContentPart) -> Self {
+        match part {
+            ContentPart::Text(text) => Part::Text(text),
+            ContentPart::Image(image) => image.into(),
+        }
+    }
+}
+
+impl From<ImageContent> for Part {
+    fn from(image: ImageContent) -> Self {
+        let data_url = image
+            .url
+            .strip_prefix("data:")
+            .and_then(|rest| rest.split_once(";base64,"));
+
+        match data_url {
+            Some((mime_type, data)) => Part::InlineData(Blob {
+                mime_type: mime_type.to_string(),
+                data: data.to_string(),
+            }),
+            None => Part::FileData(FileData {
+                mime_type: image_mime_type(&image.url).to_string(),
+                file_uri: image.url,
+            }),
+        }
+    }
+}
+
+impl From<FunctionCall> for models::ToolCall {
+    fn from(call: FunctionCall) -> Self {
+        // Calls without an ID are identified by the function name, which is what their response
+        // is matched by anyway.
+        models::ToolCall {
+            id: call.id.unwrap_or_else(|| call.name.clone()),
+            arguments: call.args.to_string(),
+            name: call.name,
+        }
+    }
+}
+
+impl From<&UsageMetadata> for TokenUsage {
+    fn from(usage: &UsageMetadata) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_token_count,
+            completion_tokens: usage.candidates_token_count,
+            total_tokens: usage.total_token_count,
+        }
+    }
+}
+
+impl From<ResponseOk> for PromptMessage {
+    fn from(response: ResponseOk) -> Self {
+        let text = response.text();
+        PromptMessage::Assistant(AssistantMessage {
+            content: (!text.is_empty()).then_some(text),
+            name: None,
+            refusal: None,
+            tool_calls: response.tool_calls(),
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::models::ToolCall;
+
+    use super::*;
+
+    #[test]
+    fn test_model_gemini_request_from_prompt() -> anyhow::Result<()> {
+        let mut prompt = Prompt::new();
+        prompt.push(PromptMessage::system("You are a weather bot."));
+        prompt.push(PromptMessage::User(UserMessage::new(
+            MessageContent::Parts(vec![
+                ContentPart::text("What's the weather here?"),
+                ContentPart::image("https://example.com/sky.png"),
+            ]),
+        )));
+        prompt.push(PromptMessage::Assistant(AssistantMessage::with_tool_calls(
+            [
+                ToolCall::new("get_weather", "get_weather", r#"{"city":"Tokyo"}"#),
+                ToolCall::new("call_1", "get_time", "{}"),
+            ],
+        )));
+        prompt.push(PromptMessage::tool("get_weather", "Sunny"));
+        prompt.push(PromptMessage::tool("call_1", "Noon"));
+
+        let contents = RequestContents::from(prompt);
+        assert_eq!(
+            serde_json::to_value(&contents)?,
+            json!({
+                "systemInstruction": { "parts": [{ "text": "You are a weather bot." }] },
+                "contents": [
+                    {
+                        "role": "user",
+                        "parts": [
+                            { "text": "What's the weather here?" },
+                            {
+                                "fileData": {
+                                    "mimeType": "image/png",
+                                    "fileUri": "https://example.com/sky.png"
+                                }
+                            }
+                        ]
+                    },
+                    {
+                        "role": "model",
+                        "parts": [
+                            { "functionCall": { "name": "get_weather", "args": { "city": "Tokyo" } } },
+                            { "functionCall": { "name": "get_time", "args": {} } }
+                        ]
+                    },
+                    {
+                        "role": "user",
+                        "parts": [
+                            {
+                                "functionResponse": {
+                                    "name": "get_weather",
+                                    "response": { "content": "Sunny" }
+                                }
+                            },
+                            {
+                                "functionResponse": {
+                                    "name": "get_time",
+                                    "response": { "content": "Noon" }
+                                }
+                            }
+                        ]
+                    }
+                ]
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_gemini_response_blocked() -> anyhow::Result<()> {
+        let body: ResponseBody = serde_json::from_value(json!({
+            "promptFeedback": {
+                "blockReason": "SAFETY",
+                "safetyRatings": [
+                    {
+                        "category": "HARM_CATEGORY_DANGEROUS_CONTENT",
+                        "probability": "HIGH",
+                        "blocked": true
+                    },
+                    { "category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE" }
+                ]
+            },
+            "usageMetadata": { "promptTokenCount": 8, "totalTokenCount": 8 }
+        }))?;
+
+        let ResponseBody::Ok(response) = body else {
+            panic!("expected a successful response");
+        };
+        let error = response.check().unwrap_err();
+        assert!(matches!(error, ModelError::GeminiPromptBlocked(_)));
+        assert_eq!(
+            error.to_string(),
+            "Gemini blocked the prompt: SAFETY (HARM_CATEGORY_DANGEROUS_CONTENT)"
+        );
+
+        let response: ResponseOk = serde_json::from_value(json!({
+            "candidates": [{
+                "finishReason": "SAFETY",
+                "safetyRatings": [
+                    { "category": "HARM_CATEGORY_NEW_THING", "probability": "HIGH", "blocked": true }
+                ]
+            }]
+        }))?;
+        assert!(matches!(
+            response.check(),
+            Err(ModelError::GeminiResponseBlocked(BlockedError { reason, safety_ratings }))
+                if reason == "SAFETY"
+                    && safety_ratings[0].category == HarmCategory::HarmCategoryUnspecified
+        ));
+
+        let error: ResponseBody = serde_json::from_value(json!({
+            "error": { "code": 400, "message": "API key not valid.", "status": "INVALID_ARGUMENT" }
+        }))?;
+        assert_eq!(error.unwrap_err().to_string(), "API key not valid.");
+
+        Ok(())
+    }
+}
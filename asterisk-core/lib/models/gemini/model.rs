@@ -0,0 +1,335 @@
+use std::borrow::Cow;
+
+use futures::{future::BoxFuture, stream::BoxStream};
+use reqwest::RequestBuilder;
+use tracing::debug;
+
+use crate::models::{
+    http,
+    retry::{self, RetryPolicy},
+    ChatModel, ModelError, ModelResponse, ModelResult, OutputSchema, Prompt, StreamEvent,
+    TextModel, TextStreamModel, TokenUsage,
+};
+
+use super::{
+    Config, ModelBuilder, RequestBody, RequestContents, ResponseBody, ResponseError, ResponseOk,
+    ResponseStream, GEMINI_API_URL,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `GeminiModel` is a type that can prompt and stream responses from models provided by Google
+/// through the native Gemini API.
+#[derive(Debug, Clone)]
+pub struct GeminiModel {
+    pub(crate) config: Cow<'static, Config>,
+    pub(crate) base_url: String,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) client: reqwest::Client,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl GeminiModel {
+    /// Creates a builder for the model.
+    pub fn builder() -> ModelBuilder {
+        ModelBuilder::default()
+    }
+
+    /// Calls the `generateContent` method with the given request contents.
+    ///
+    /// Fails with `ModelError::GeminiPromptBlocked` or `ModelError::GeminiResponseBlocked` if the
+    /// prompt or the response was blocked.
+    pub async fn call(&self, contents: impl Into<RequestContents>) -> ModelResult<ResponseOk> {
+        let url = format!(
+            "{}/models/{}:generateContent",
+            self.base_url, self.config.model
+        );
+        let request = self.request(&url, contents.into())?;
+        let response = self
+            .retry
+            .send(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
+
+        let body = response.text().await?;
+        debug!("body = {body:#?}");
+
+        let body: ResponseBody = serde_json::from_str(&body)?;
+        let ResponseBody::Ok(body) = body else {
+            return Err(ModelError::GeminiResponseError(body.unwrap_err()));
+        };
+
+        body.check()
+    }
+
+    /// Calls the `streamGenerateContent` method with the given request contents and gets back a
+    /// stream of response chunks.
+    pub fn call_streaming(
+        &self,
+        contents: impl Into<RequestContents>,
+    ) -> ModelResult<ResponseStream> {
+        let url = format!(
+            "{}/models/{}:streamGenerateContent?alt=sse",
+            self.base_url, self.config.model
+        );
+        let request = self.request(&url, contents.into())?;
+        Ok(ResponseStream::new(request, self.retry.clone()))
+    }
+
+    /// Builds a request to the given URL with the given contents and the model's configuration.
+    fn request(&self, url: &str, contents: RequestContents) -> ModelResult<RequestBuilder> {
+        debug!("config = {:#?}", self.config);
+        debug!("contents = {}", serde_json::to_string(&contents).unwrap());
+
+        let api_key = self
+            .config
+            .api_key
+            .as_ref()
+            .ok_or(ModelError::NoAPIKeyFound)?;
+        Ok(self
+            .client
+            .post(url)
+            .header("x-goog-api-key", api_key)
+            .json(&RequestBody {
+                contents,
+                config: self.config.clone().into_owned(),
+            }))
+    }
+
+    /// Get the model's configuration
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Get the HTTP client the model sends requests with
+    pub fn get_client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Get the model's retry policy
+    pub fn get_retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl TextModel for GeminiModel {
+    async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
+        let response = self.call(prompt.into()).await?;
+        Ok(response.text())
+    }
+
+    async fn prompt_with_usage(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<ModelResponse> {
+        let response = self.call(prompt.into()).await?;
+        Ok(ModelResponse {
+            content: response.text(),
+            usage: response.usage_metadata.as_ref().map(TokenUsage::from),
+            model: response
+                .model_version
+                .unwrap_or_else(|| self.config.model.clone()),
+        })
+    }
+}
+
+impl TextStreamModel for GeminiModel {
+    async fn prompt_stream(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<BoxStream<'static, ModelResult<StreamEvent>>> {
+        Ok(self.call_streaming(prompt.into())?.into_events())
+    }
+}
+
+impl ChatModel for GeminiModel {
+    fn model_id(&self) -> &str {
+        &self.config.model
+    }
+
+    fn chat(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<String>> {
+        Box::pin(self.prompt(prompt))
+    }
+
+    fn chat_with_usage(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        Box::pin(self.prompt_with_usage(prompt))
+    }
+
+    fn chat_stream(
+        &self,
+        prompt: Prompt,
+    ) -> BoxFuture<'_, ModelResult<BoxStream<'static, ModelResult<StreamEvent>>>> {
+        Box::pin(self.prompt_stream(prompt))
+    }
+
+    /// Gemini's response schemas are a subset of OpenAPI that derived JSON schemas don't fit, so
+    /// the model is put in JSON mode and given the schema in the prompt instead.
+    fn chat_with_schema(
+        &self,
+        prompt: Prompt,
+        schema: OutputSchema,
+    ) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        let mut model = self.clone();
+        model.config.to_mut().generation_config.response_mime_type =
+            Some("application/json".to_string());
+        Box::pin(async move { model.prompt_with_usage(schema.instruct(prompt)).await })
+    }
+}
+
+impl Default for GeminiModel {
+    fn default() -> Self {
+        Self {
+            config: Cow::Owned(Config::default()),
+            base_url: GEMINI_API_URL.to_string(),
+            retry: RetryPolicy::default(),
+            client: http::default_client(),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use crate::{
+        models::{
+            gemini::{FunctionDeclaration, ModelType, Tool},
+            retry::tests::{http_response, mock_server},
+            StreamAccumulator, ToolCall,
+        },
+        prompt,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_model_gemini_builders() -> anyhow::Result<()> {
+        let model = GeminiModel::builder()
+            .api_key("gm-test")
+            .max_tokens(256)
+            .tools(vec![Tool {
+                function_declarations: vec![FunctionDeclaration {
+                    name: "get_weather".to_string(),
+                    ..Default::default()
+                }],
+            }])
+            .build();
+
+        assert_eq!(model.base_url, GEMINI_API_URL.to_string());
+        assert_eq!(model.config.api_key.as_deref(), Some("gm-test"));
+        assert_eq!(model.config.model, ModelType::Gemini1_5Flash.to_string());
+        assert_eq!(model.config.generation_config.max_output_tokens, Some(256));
+        assert_eq!(model.config.safety_settings, None);
+
+        let body = serde_json::to_value(RequestBody {
+            contents: prompt! { user: "Hi" }.into(),
+            config: model.config.into_owned(),
+        })?;
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 256);
+        assert_eq!(
+            body["tools"][0]["functionDeclarations"][0]["name"],
+            "get_weather"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_model_gemini_prompt_with_usage() {
+        let body = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"Hello!"}]},"finishReason":"STOP","index":0}],"usageMetadata":{"promptTokenCount":9,"candidatesTokenCount":3,"totalTokenCount":12},"modelVersion":"gemini-1.5-flash-002"}"#;
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "application/json")],
+            body,
+        )])
+        .await;
+
+        let model = GeminiModel::builder()
+            .base_url(url)
+            .api_key("gm-test")
+            .build();
+
+        let response = model
+            .prompt_with_usage(prompt! { system: "Be nice.", user: "Hi" })
+            .await
+            .unwrap();
+        assert_eq!(response.content, "Hello!");
+        assert_eq!(response.model, "gemini-1.5-flash-002");
+        assert_eq!(response.usage, Some(TokenUsage::new(9, 3)));
+    }
+
+    #[tokio::test]
+    async fn test_model_gemini_prompt_blocked() {
+        let body = r#"{"promptFeedback":{"blockReason":"SAFETY","safetyRatings":[{"category":"HARM_CATEGORY_HARASSMENT","probability":"HIGH","blocked":true}]}}"#;
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "application/json")],
+            body,
+        )])
+        .await;
+
+        let model = GeminiModel::builder()
+            .base_url(url)
+            .api_key("gm-test")
+            .build();
+
+        let error = model.prompt(prompt! { user: "Hi" }).await.unwrap_err();
+        assert!(matches!(error, ModelError::GeminiPromptBlocked(_)));
+        assert!(!error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_model_gemini_prompt_stream() {
+        let chunks = [
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"Hel"}]},"index":0}]}"#,
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"lo"},{"functionCall":{"name":"wave","args":{}}}]},"index":0}]}"#,
+            r#"{"candidates":[{"content":{"role":"model","parts":[{"text":""}]},"finishReason":"STOP","index":0}],"usageMetadata":{"promptTokenCount":12,"candidatesTokenCount":5,"totalTokenCount":17}}"#,
+        ];
+        let body: String = chunks
+            .iter()
+            .map(|chunk| format!("data: {chunk}\r\n\r\n"))
+            .collect();
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "text/event-stream")],
+            &body,
+        )])
+        .await;
+
+        let model = GeminiModel::builder()
+            .base_url(url)
+            .api_key("gm-test")
+            .build();
+
+        let stream = model.prompt_stream(prompt! { user: "Hi" }).await.unwrap();
+        let response = StreamAccumulator::collect(stream).await.unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("Hello"));
+        assert_eq!(
+            response.message.tool_calls,
+            vec![ToolCall::new("wave", "wave", "{}")]
+        );
+        assert_eq!(response.finish_reason.as_deref(), Some("STOP"));
+        assert_eq!(response.usage, Some(TokenUsage::new(12, 5)));
+
+        let events: Vec<_> = model
+            .call_streaming(prompt! { user: "Hi" })
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(Result::is_ok));
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{ready, stream::BoxStream, Stream, StreamExt};
+use pin_project::pin_project;
+use reqwest::RequestBuilder;
+
+use crate::models::{
+    retry::{RetryEventSource, RetryPolicy},
+    stream, ModelResult, StreamEvent, TokenUsage, ToolCall, ToolCallDelta,
+};
+
+use super::{Candidate, Content, Part, ResponseError, ResponseOk, Role};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A stream of chunked responses from the Gemini API using server-sent events.
+///
+/// Each chunk is a whole `ResponseOk` carrying a piece of the first candidate. Function calls are
+/// never split across chunks. The stream ends when the server closes the connection.
+#[pin_project]
+pub struct ResponseStream {
+    #[pin]
+    stream: RetryEventSource,
+}
+
+/// Folds the chunks of a streamed response back into the response `GeminiModel::call` would have
+/// returned.
+#[derive(Debug, Default)]
+pub struct ResponseAccumulator {
+    response: ResponseOk,
+}
+
+/// Turns response chunks into stream events, numbering the function calls across chunks.
+#[derive(Debug, Default)]
+struct EventMapper {
+    tool_calls: usize,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ResponseStream {
+    /// Creates a new `ResponseStream` from the given request, reconnecting according to the
+    /// retry policy if the connection fails before the response starts.
+    pub fn new(request: RequestBuilder, retry: RetryPolicy) -> Self {
+        // Gemini ends the stream by closing the connection rather than with a final event.
+        let stream = RetryEventSource::new(request, retry)
+            .ends_on_close()
+            .error_body::<ResponseError>();
+        Self { stream }
+    }
+
+    /// Consumes the stream and folds the chunks into a whole response.
+    ///
+    /// Fails if the prompt or the response was blocked, like `GeminiModel::call` does.
+    pub async fn collect_response(mut self) -> ModelResult<ResponseOk> {
+        let mut accumulator = ResponseAccumulator::new();
+        while let Some(chunk) = self.next().await {
+            accumulator.push(chunk?);
+        }
+
+        accumulator.finish().check()
+    }
+
+    /// Converts the stream into a stream of provider-independent events.
+    ///
+    /// A blocked prompt or response ends the stream with an error.
+    pub fn into_events(self) -> BoxStream<'static, ModelResult<StreamEvent>> {
+        let mut mapper = EventMapper::default();
+        stream::flatten_events(self.map(move |chunk| mapper.map(chunk?)))
+    }
+}
+
+impl ResponseAccumulator {
+    /// Creates a new, empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk to the response.
+    pub fn push(&mut self, chunk: ResponseOk) {
+        let response = &mut self.response;
+        response.prompt_feedback = chunk.prompt_feedback.or(response.prompt_feedback.take());
+        response.usage_metadata = chunk.usage_metadata.or(response.usage_metadata.take());
+        response.model_version = chunk.model_version.or(response.model_version.take());
+
+        let Some(chunk) = chunk.candidates.into_iter().next() else {
+            return;
+        };
+
+        if response.candidates.is_empty() {
+            response.candidates.push(Candidate::default());
+        }
+
+        let candidate = &mut response.candidates[0];
+        candidate.finish_reason = chunk.finish_reason.or(candidate.finish_reason.take());
+        candidate.index = chunk.index.or(candidate.index);
+        if !chunk.safety_ratings.is_empty() {
+            candidate.safety_ratings = chunk.safety_ratings;
+        }
+
+        let content = candidate.content.get_or_insert_with(|| Content {
+            role: Some(Role::Model),
+            parts: Vec::new(),
+        });
+        for part in chunk
+            .content
+            .map(|content| content.parts)
+            .unwrap_or_default()
+        {
+            match (content.parts.last_mut(), part) {
+                (Some(Part::Text(text)), Part::Text(delta)) => text.push_str(&delta),
+                (_, part) => content.parts.push(part),
+            }
+        }
+    }
+
+    /// Returns the response accumulated so far.
+    pub fn finish(self) -> ResponseOk {
+        self.response
+    }
+}
+
+impl EventMapper {
+    fn map(&mut self, chunk: ResponseOk) -> ModelResult<Vec<StreamEvent>> {
+        let chunk = chunk.check()?;
+
+        let mut events = Vec::new();
+        for part in chunk.parts() {
+            match part {
+                Part::Text(text) if !text.is_empty() => {
+                    events.push(StreamEvent::ContentDelta(text.clone()))
+                }
+                Part::FunctionCall(call) => {
+                    let tool_call = ToolCall::from(call.clone());
+                    events.push(StreamEvent::ToolCallDelta(ToolCallDelta {
+                        index: self.tool_calls,
+                        id: Some(tool_call.id),
+                        name: Some(tool_call.name),
+                        arguments: tool_call.arguments,
+                    }));
+                    self.tool_calls += 1;
+                }
+                _ => {}
+            }
+        }
+
+        // Every chunk carries the usage so far, so only the final one is passed on.
+        if let Some(reason) = chunk
+            .candidates
+            .first()
+            .and_then(|candidate| candidate.finish_reason.clone())
+        {
+            events.push(StreamEvent::FinishReason(reason));
+            if let Some(usage) = &chunk.usage_metadata {
+                events.push(StreamEvent::Usage(TokenUsage::from(usage)));
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Stream for ResponseStream {
+    type Item = ModelResult<ResponseOk>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let Some(data) = ready!(this.stream.poll_next(cx)) else {
+            return Poll::Ready(None);
+        };
+
+        let chunk: ResponseOk = serde_json::from_str(&data?)?;
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ModelError;
+
+    use super::*;
+
+    const CHUNKS: [&str; 3] = [
+        r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"Let me "}]},"index":0}],"usageMetadata":{"promptTokenCount":30,"candidatesTokenCount":2,"totalTokenCount":32},"modelVersion":"gemini-1.5-flash"}"#,
+        r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"check."},{"functionCall":{"name":"get_weather","args":{"city":"Tokyo"}}}]},"index":0}],"usageMetadata":{"promptTokenCount":30,"candidatesTokenCount":7,"totalTokenCount":37},"modelVersion":"gemini-1.5-flash"}"#,
+        r#"{"candidates":[{"content":{"role":"model","parts":[{"text":""}]},"finishReason":"STOP","index":0}],"usageMetadata":{"promptTokenCount":30,"candidatesTokenCount":9,"totalTokenCount":39},"modelVersion":"gemini-1.5-flash"}"#,
+    ];
+
+    fn chunks() -> Vec<ResponseOk> {
+        CHUNKS
+            .iter()
+            .map(|chunk| serde_json::from_str(chunk).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_gemini_stream_chunk_events() -> anyhow::Result<()> {
+        let mut mapper = EventMapper::default();
+        let mut events = Vec::new();
+        for chunk in chunks() {
+            events.extend(mapper.map(chunk)?);
+        }
+
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::ContentDelta("Let me ".to_string()),
+                StreamEvent::ContentDelta("check.".to_string()),
+                StreamEvent::ToolCallDelta(ToolCallDelta {
+                    index: 0,
+                    id: Some("get_weather".to_string()),
+                    name: Some("get_weather".to_string()),
+                    arguments: r#"{"city":"Tokyo"}"#.to_string(),
+                }),
+                StreamEvent::FinishReason("STOP".to_string()),
+                StreamEvent::Usage(TokenUsage::new(30, 9)),
+            ]
+        );
+
+        let blocked: ResponseOk =
+            serde_json::from_str(r#"{"candidates":[{"finishReason":"RECITATION","index":0}]}"#)?;
+        assert!(matches!(
+            mapper.map(blocked),
+            Err(ModelError::GeminiResponseBlocked(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gemini_stream_accumulator() {
+        let mut accumulator = ResponseAccumulator::new();
+        for chunk in chunks() {
+            accumulator.push(chunk);
+        }
+
+        let response = accumulator.finish();
+        assert_eq!(response.text(), "Let me check.");
+        assert_eq!(response.tool_calls().len(), 1);
+        assert_eq!(
+            response.candidates[0].finish_reason.as_deref(),
+            Some("STOP")
+        );
+        assert_eq!(response.usage_metadata.unwrap().total_token_count, 39);
+        assert_eq!(response.model_version.as_deref(), Some("gemini-1.5-flash"));
+    }
+}
@@ -0,0 +1,172 @@
+//! HTTP client shared by the model backends.
+
+use std::{path::PathBuf, time::Duration};
+
+use lazy_static::lazy_static;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Certificate, Client, Proxy,
+};
+
+use super::{ModelError, ModelResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The user agent sent with model requests unless configured otherwise.
+pub const DEFAULT_USER_AGENT: &str = concat!("asterisk/", env!("CARGO_PKG_VERSION"));
+
+/// The default timeout for establishing a connection.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default time an idle pooled connection is kept open.
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+lazy_static! {
+    static ref DEFAULT_CLIENT: Client = HttpClientConfig::default()
+        .build()
+        .expect("default HTTP client config is valid");
+}
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `HttpClientConfig` describes the transport used to reach a model API.
+///
+/// Models share a single pooled client built from the default config, so connections and TLS
+/// sessions are reused across calls. Build a client from a custom config and hand it to a model
+/// builder's `client` method when requests need to go through a proxy, trust extra CA roots or
+/// carry extra headers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HttpClientConfig {
+    /// The user agent to send. Defaults to `DEFAULT_USER_AGENT`.
+    pub user_agent: Option<String>,
+
+    /// The URL of a proxy to send all requests through.
+    pub proxy: Option<String>,
+
+    /// Paths to PEM encoded certificates to trust in addition to the system roots.
+    pub root_certificates: Vec<PathBuf>,
+
+    /// Headers to send with every request.
+    pub default_headers: Vec<(String, String)>,
+
+    /// The timeout for establishing a connection. Defaults to `DEFAULT_CONNECT_TIMEOUT`.
+    pub connect_timeout: Option<Duration>,
+
+    /// How long an idle pooled connection is kept open. Defaults to `DEFAULT_POOL_IDLE_TIMEOUT`.
+    pub pool_idle_timeout: Option<Duration>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl HttpClientConfig {
+    /// Sets the user agent to send.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Sets the URL of a proxy to send all requests through.
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Adds a PEM encoded certificate to trust in addition to the system roots.
+    pub fn root_certificate(mut self, path: impl Into<PathBuf>) -> Self {
+        self.root_certificates.push(path.into());
+        self
+    }
+
+    /// Adds a header to send with every request.
+    pub fn default_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.default_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the timeout for establishing a connection.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Sets how long an idle pooled connection is kept open.
+    pub fn pool_idle_timeout(mut self, pool_idle_timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(pool_idle_timeout);
+        self
+    }
+
+    /// Builds a pooled client from the config.
+    pub fn build(&self) -> ModelResult<Client> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.default_headers {
+            let header_name = HeaderName::try_from(name.as_str())
+                .map_err(|_| ModelError::InvalidHttpConfig(format!("invalid header `{name}`")))?;
+            let header_value = HeaderValue::try_from(value.as_str()).map_err(|_| {
+                ModelError::InvalidHttpConfig(format!("invalid value for header `{name}`"))
+            })?;
+            headers.insert(header_name, header_value);
+        }
+
+        let mut builder = Client::builder()
+            .user_agent(self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT))
+            .default_headers(headers)
+            .connect_timeout(self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+            .pool_idle_timeout(self.pool_idle_timeout.unwrap_or(DEFAULT_POOL_IDLE_TIMEOUT));
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        for path in &self.root_certificates {
+            let pem = std::fs::read(path)?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the pooled client shared by models that weren't given one.
+///
+/// Cloning a client is cheap and the clones share the same connection pool.
+pub fn default_client() -> Client {
+    DEFAULT_CLIENT.clone()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_client_config_build() {
+        let config = HttpClientConfig::default()
+            .user_agent("asterisk-test")
+            .proxy("http://127.0.0.1:8080")
+            .default_header("x-team", "agents")
+            .connect_timeout(Duration::from_secs(1));
+        assert!(config.build().is_ok());
+
+        let config = HttpClientConfig::default().default_header("bad header", "value");
+        assert!(matches!(
+            config.build(),
+            Err(ModelError::InvalidHttpConfig(_))
+        ));
+
+        let config = HttpClientConfig::default().root_certificate("/does/not/exist.pem");
+        assert!(matches!(config.build(), Err(ModelError::IoError(_))));
+    }
+}
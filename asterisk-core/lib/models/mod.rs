@@ -1,16 +1,31 @@
 //! Models
 
+mod embedding;
 mod error;
 mod prompt;
+mod stream;
+mod structured;
+mod tokens;
 mod traits;
+mod usage;
 
 //--------------------------------------------------------------------------------------------------
 // Exports
 //--------------------------------------------------------------------------------------------------
 
+pub mod anthropic;
+pub mod gemini;
+pub mod http;
 pub mod ollama;
 pub mod openai;
+pub mod registry;
+pub mod retry;
 
+pub use embedding::*;
 pub use error::*;
 pub use prompt::*;
+pub use stream::*;
+pub use structured::*;
+pub use tokens::*;
 pub use traits::*;
+pub use usage::*;
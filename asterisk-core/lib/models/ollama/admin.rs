@@ -0,0 +1,508 @@
+use futures::stream::BoxStream;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::debug;
+
+use crate::models::{
+    http,
+    retry::{self, RetryPolicy},
+    ModelError, ModelResult,
+};
+
+use super::{stream, ResponseError};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The root URL of a local Ollama server, which the API paths are appended to.
+pub const OLLAMA_HOST_URL: &str = "http://localhost:11434";
+
+/// The tag Ollama gives a model pulled without one.
+pub const OLLAMA_DEFAULT_TAG: &str = "latest";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `OllamaAdminClient` manages the models of an Ollama server: listing, inspecting, pulling and
+/// deleting them.
+#[derive(Debug, Clone)]
+pub struct OllamaAdminClient {
+    pub(crate) base_url: String,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) client: reqwest::Client,
+}
+
+/// A builder for an Ollama admin client.
+#[derive(Debug, Clone, Default)]
+pub struct AdminClientBuilder {
+    base_url: Option<String>,
+    retry: RetryPolicy,
+    client: Option<reqwest::Client>,
+}
+
+/// A model stored on the Ollama server.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LocalModel {
+    /// The name of the model, including its tag.
+    pub name: String,
+
+    /// The model to refer to in requests, usually the same as `name`.
+    pub model: String,
+
+    /// When the model was last modified, as an RFC 3339 timestamp.
+    #[serde(default)]
+    pub modified_at: Option<String>,
+
+    /// The size of the model on disk in bytes.
+    pub size: u64,
+
+    /// The digest of the model's manifest.
+    pub digest: String,
+
+    /// The format, family and size of the model.
+    #[serde(default)]
+    pub details: ModelDetails,
+}
+
+/// A model loaded into memory on the Ollama server.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunningModel {
+    /// The name of the model, including its tag.
+    pub name: String,
+
+    /// The model to refer to in requests, usually the same as `name`.
+    pub model: String,
+
+    /// The memory the model takes up in bytes.
+    pub size: u64,
+
+    /// The part of `size` that is in video memory.
+    #[serde(default)]
+    pub size_vram: u64,
+
+    /// The digest of the model's manifest.
+    pub digest: String,
+
+    /// When the model will be unloaded, as an RFC 3339 timestamp.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+
+    /// The format, family and size of the model.
+    #[serde(default)]
+    pub details: ModelDetails,
+}
+
+/// The format, family and size of a model.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct ModelDetails {
+    /// The model this one was created from, if any.
+    pub parent_model: String,
+
+    /// The file format of the weights, like `gguf`.
+    pub format: String,
+
+    /// The model family, like `llama`.
+    pub family: String,
+
+    /// All the families the model belongs to.
+    pub families: Option<Vec<String>>,
+
+    /// The number of parameters, like `8.0B`.
+    pub parameter_size: String,
+
+    /// The quantization of the weights, like `Q4_0`.
+    pub quantization_level: String,
+}
+
+/// The information about a model returned by the show API.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct ModelShowResponse {
+    /// The Modelfile the model was created from.
+    pub modelfile: String,
+
+    /// The default parameters of the model, one per line.
+    pub parameters: Option<String>,
+
+    /// The prompt template of the model.
+    pub template: Option<String>,
+
+    /// The license of the model.
+    pub license: Option<String>,
+
+    /// The format, family and size of the model.
+    pub details: ModelDetails,
+
+    /// The architecture metadata from the weights, keyed like `llama.context_length`.
+    pub model_info: Option<serde_json::Map<String, serde_json::Value>>,
+
+    /// What the model can do, like `completion`, `tools` or `vision`.
+    pub capabilities: Vec<String>,
+
+    /// When the model was last modified, as an RFC 3339 timestamp.
+    pub modified_at: Option<String>,
+}
+
+/// A progress update streamed while pulling a model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PullProgress {
+    /// What the server is doing, like `pulling manifest` or `success` once done.
+    pub status: String,
+
+    /// The digest of the layer being downloaded.
+    #[serde(default)]
+    pub digest: Option<String>,
+
+    /// The size of the layer in bytes.
+    #[serde(default)]
+    pub total: Option<u64>,
+
+    /// The number of bytes of the layer downloaded so far.
+    #[serde(default)]
+    pub completed: Option<u64>,
+}
+
+/// The request body naming a model, for the show, pull and delete APIs.
+#[derive(Debug, Serialize)]
+struct ModelRequestBody<'a> {
+    model: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+}
+
+/// The response body listing models, for the tags and ps APIs.
+#[derive(Debug, Deserialize)]
+struct ModelsResponseOk<T> {
+    models: Vec<T>,
+}
+
+/// Admin response body.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AdminResponseBody<T> {
+    Ok(T),
+    Error(ResponseError),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl OllamaAdminClient {
+    /// Creates a builder for the client.
+    pub fn builder() -> AdminClientBuilder {
+        AdminClientBuilder::default()
+    }
+
+    /// Lists the models stored on the server.
+    pub async fn list_models(&self) -> ModelResult<Vec<LocalModel>> {
+        let request = self.client.get(self.url("tags"));
+        let body: ModelsResponseOk<LocalModel> = self.send(request).await?;
+        Ok(body.models)
+    }
+
+    /// Lists the models currently loaded into memory.
+    pub async fn running_models(&self) -> ModelResult<Vec<RunningModel>> {
+        let request = self.client.get(self.url("ps"));
+        let body: ModelsResponseOk<RunningModel> = self.send(request).await?;
+        Ok(body.models)
+    }
+
+    /// Gets the Modelfile, template, parameters and details of a model.
+    ///
+    /// Fails with `ModelError::OllamaResponseError` if the model is not on the server.
+    pub async fn show(&self, model: &str) -> ModelResult<ModelShowResponse> {
+        let request = self.client.post(self.url("show")).json(&ModelRequestBody {
+            model,
+            stream: None,
+        });
+
+        self.send(request).await
+    }
+
+    /// Whether the model is stored on the server.
+    ///
+    /// A name without a tag refers to its `latest` tag, as it does in chat requests.
+    pub async fn has_model(&self, model: &str) -> ModelResult<bool> {
+        let model = with_default_tag(model);
+        let models = self.list_models().await?;
+        Ok(models
+            .iter()
+            .any(|local| local.name == model || local.model == model))
+    }
+
+    /// Pulls a model from the Ollama library and gets back a stream of progress updates.
+    ///
+    /// The last update has the status `success`. A failure midway, like a missing model, is
+    /// yielded as `ModelError::OllamaResponseError` and ends the stream.
+    pub async fn pull(
+        &self,
+        model: &str,
+    ) -> ModelResult<BoxStream<'static, ModelResult<PullProgress>>> {
+        let request = self.client.post(self.url("pull")).json(&ModelRequestBody {
+            model,
+            stream: Some(true),
+        });
+
+        let response = self
+            .retry
+            .send_streaming(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
+        Ok(stream::decode_ndjson(
+            response.bytes_stream(),
+            self.retry.timeout,
+            PullProgress::is_success,
+        ))
+    }
+
+    /// Deletes a model and its data from the server.
+    ///
+    /// Fails with `ModelError::OllamaResponseError` if the model is not on the server.
+    pub async fn delete(&self, model: &str) -> ModelResult<()> {
+        let request = self
+            .client
+            .delete(self.url("delete"))
+            .json(&ModelRequestBody {
+                model,
+                stream: None,
+            });
+
+        self.retry
+            .send(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
+        Ok(())
+    }
+
+    /// Get the server's root URL
+    pub fn get_base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Returns the URL of the given API endpoint.
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}/api/{endpoint}", self.base_url)
+    }
+
+    /// Sends a request and decodes the response body.
+    async fn send<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> ModelResult<T> {
+        let response = self
+            .retry
+            .send(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
+        let body = response.text().await?;
+        debug!("body length = {}", body.len());
+
+        match serde_json::from_str(&body)? {
+            AdminResponseBody::Ok(body) => Ok(body),
+            AdminResponseBody::Error(error) => Err(ModelError::OllamaResponseError(error)),
+        }
+    }
+}
+
+impl ModelShowResponse {
+    /// The number of tokens the model was trained to attend to, read from its architecture
+    /// metadata.
+    pub fn context_length(&self) -> Option<u64> {
+        let info = self.model_info.as_ref()?;
+        let architecture = info.get("general.architecture")?.as_str()?;
+        info.get(&format!("{architecture}.context_length"))?
+            .as_u64()
+    }
+}
+
+impl PullProgress {
+    /// Whether the pull has finished.
+    pub fn is_success(&self) -> bool {
+        self.status == "success"
+    }
+
+    /// The fraction of the current layer downloaded so far, if it is being downloaded.
+    pub fn fraction(&self) -> Option<f64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some(completed as f64 / total as f64),
+            _ => None,
+        }
+    }
+}
+
+impl AdminClientBuilder {
+    /// The root URL of the Ollama server.
+    ///
+    /// The URL of any of its API endpoints also works, so the `base_url` of an `OllamaModel` can
+    /// be passed as is. Defaults to `OLLAMA_HOST_URL`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// The policy for retrying failed requests and timing them out.
+    ///
+    /// Defaults to `RetryPolicy::default()`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The HTTP client to send requests with.
+    ///
+    /// Defaults to a pooled client shared by all models.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Builds the admin client.
+    pub fn build(self) -> OllamaAdminClient {
+        let base_url = self.base_url.unwrap_or(OLLAMA_HOST_URL.to_string());
+        let base_url = match base_url.find("/api/") {
+            Some(index) => base_url[..index].to_string(),
+            None => base_url.trim_end_matches('/').to_string(),
+        };
+
+        OllamaAdminClient {
+            base_url,
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the model name with the `latest` tag if it has none.
+pub fn with_default_tag(model: &str) -> String {
+    match model.rsplit_once(':') {
+        Some((_, tag)) if !tag.contains('/') => model.to_string(),
+        _ => format!("{model}:{OLLAMA_DEFAULT_TAG}"),
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for OllamaAdminClient {
+    fn default() -> Self {
+        AdminClientBuilder::default().build()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use crate::models::{
+        ollama::OLLAMA_API_URL,
+        retry::tests::{http_response, mock_server},
+    };
+
+    use super::*;
+
+    const TAGS: &str = r#"{"models":[{"name":"llama3.1:latest","model":"llama3.1:latest","modified_at":"2024-09-01T10:00:00.000000+02:00","size":4661230766,"digest":"42182419e950","details":{"parent_model":"","format":"gguf","family":"llama","families":["llama"],"parameter_size":"8.0B","quantization_level":"Q4_0"}}]}"#;
+
+    fn client(url: String) -> OllamaAdminClient {
+        OllamaAdminClient::builder()
+            .base_url(url)
+            .retry_policy(RetryPolicy::default().backoff(Duration::ZERO, Duration::ZERO))
+            .build()
+    }
+
+    #[test]
+    fn test_ollama_admin_base_url() {
+        assert_eq!(OllamaAdminClient::default().base_url, OLLAMA_HOST_URL);
+
+        let admin = OllamaAdminClient::builder()
+            .base_url(OLLAMA_API_URL)
+            .build();
+        assert_eq!(admin.url("tags"), "http://localhost:11434/api/tags");
+
+        assert_eq!(with_default_tag("llama3.1"), "llama3.1:latest");
+        assert_eq!(with_default_tag("llama3.1:70b"), "llama3.1:70b");
+        assert_eq!(
+            with_default_tag("localhost:5000/llama3.1"),
+            "localhost:5000/llama3.1:latest"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ollama_admin_list_and_show() -> anyhow::Result<()> {
+        let show = r#"{"modelfile":"FROM llama3.1","parameters":"stop \"<|eot_id|>\"","template":"{{ .Prompt }}","details":{"format":"gguf","family":"llama","parameter_size":"8.0B","quantization_level":"Q4_0"},"model_info":{"general.architecture":"llama","llama.context_length":131072},"capabilities":["completion","tools"]}"#;
+        let (url, _) = mock_server(vec![
+            http_response("200 OK", &[("content-type", "application/json")], TAGS),
+            http_response("200 OK", &[("content-type", "application/json")], TAGS),
+            http_response("200 OK", &[("content-type", "application/json")], show),
+            http_response(
+                "404 Not Found",
+                &[("content-type", "application/json")],
+                r#"{"error":"model 'mistral' not found"}"#,
+            ),
+        ])
+        .await;
+        let admin = client(url);
+
+        let models = admin.list_models().await?;
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].details.parameter_size, "8.0B");
+
+        assert!(admin.has_model("llama3.1").await?);
+
+        let info = admin.show("llama3.1").await?;
+        assert_eq!(info.context_length(), Some(131072));
+        assert_eq!(info.capabilities, vec!["completion", "tools"]);
+
+        let Err(ModelError::OllamaResponseError(error)) = admin.delete("mistral").await else {
+            panic!("expected an Ollama error");
+        };
+        assert_eq!(error.error, "model 'mistral' not found");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ollama_admin_pull() {
+        let body = [
+            r#"{"status":"pulling manifest"}"#,
+            r#"{"status":"pulling 8eeb52dfb3bb","digest":"sha256:8eeb52dfb3bb","total":4000,"completed":1000}"#,
+            r#"{"status":"pulling 8eeb52dfb3bb","digest":"sha256:8eeb52dfb3bb","total":4000,"completed":4000}"#,
+            r#"{"status":"success"}"#,
+        ]
+        .join("\n");
+        let (url, _) = mock_server(vec![
+            http_response("200 OK", &[("content-type", "application/x-ndjson")], &body),
+            http_response(
+                "200 OK",
+                &[("content-type", "application/x-ndjson")],
+                "{\"status\":\"pulling manifest\"}\n{\"error\":\"pull model manifest: file does not exist\"}\n",
+            ),
+        ])
+        .await;
+        let admin = client(url);
+
+        let updates: Vec<_> = admin.pull("llama3.1").await.unwrap().collect().await;
+        assert_eq!(updates.len(), 4);
+        let updates: Vec<PullProgress> = updates.into_iter().map(Result::unwrap).collect();
+        assert_eq!(updates[1].fraction(), Some(0.25));
+        assert!(updates[3].is_success());
+
+        let updates: Vec<_> = admin.pull("nope").await.unwrap().collect().await;
+        assert_eq!(updates.len(), 2);
+        let Err(ModelError::OllamaResponseError(error)) = &updates[1] else {
+            panic!("expected an Ollama error, got {:?}", updates[1]);
+        };
+        assert_eq!(error.error, "pull model manifest: file does not exist");
+    }
+}
@@ -1,38 +1,25 @@
-use std::{borrow::Cow, collections::HashMap};
+use std::borrow::Cow;
 
-use super::{
-    Config, ModelType, OllamaModel, ResponseFormat, ServiceTier, StreamOptions, Tool, ToolChoice,
-    OLLAMA_API_URL,
-};
+use crate::models::{http, retry::RetryPolicy};
+
+use super::{Config, Format, ModelType, OllamaModel, Options, Tool, OLLAMA_API_URL};
 
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
-/// A builder for an OpenAI model.
+/// A builder for an Ollama model.
 #[derive(Debug, Clone, Default)]
 pub struct ModelBuilder {
     model: Option<String>,
     base_url: Option<String>,
-    frequency_penalty: Option<f32>,
-    logit_bias: Option<HashMap<u64, i8>>,
-    logprobs: Option<u8>,
-    top_logprobs: Option<u8>,
-    max_tokens: Option<u16>,
-    n: Option<u8>,
-    presence_penalty: Option<f32>,
-    response_format: Option<ResponseFormat>,
-    seed: Option<u64>,
-    service_tier: Option<ServiceTier>,
-    stop: Option<String>,
+    format: Option<Format>,
+    options: Options,
     stream: Option<bool>,
-    stream_options: Option<StreamOptions>,
-    temperature: Option<f32>,
-    top_p: Option<f32>,
+    keep_alive: Option<String>,
     tools: Option<Vec<Tool>>,
-    tool_choice: Option<ToolChoice>,
-    parallel_tool_calls: Option<bool>,
-    user: Option<String>,
+    retry: RetryPolicy,
+    client: Option<reqwest::Client>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -42,184 +29,191 @@ pub struct ModelBuilder {
 impl ModelBuilder {
     /// The ID of the model to use.
     ///
-    /// Defaults to `ModelType::Gpt4oMini`.
+    /// Defaults to `ModelType::Llama3_1_8B`.
     pub fn model(mut self, model: impl Into<String>) -> ModelBuilder {
         self.model = Some(model.into());
         self
     }
 
-    /// The base URL for making requests to the OpenAI-like API.
+    /// The URL of the Ollama chat endpoint.
+    ///
+    /// Defaults to `OLLAMA_API_URL`.
     pub fn base_url(mut self, base_url: impl Into<String>) -> ModelBuilder {
         self.base_url = Some(base_url.into());
         self
     }
 
-    /// A number between -2.0 and 2.0. Positive values penalize new tokens based on their existing
-    /// frequency in the text so far, decreasing the model's likelihood to repeat the same line
-    /// verbatim.
-    pub fn frequency_penalty(mut self, frequency_penalty: f32) -> Self {
-        self.frequency_penalty = Some(frequency_penalty);
+    /// The format the model must output, either any JSON or JSON matching a schema.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
         self
     }
 
-    /// A number between -100 and 100. Positive values increase the likelihood of the specified
-    /// tokens appearing in the completion.
-    pub fn logit_bias(mut self, logit_bias: impl IntoIterator<Item = (u64, i8)>) -> Self {
-        self.logit_bias = Some(logit_bias.into_iter().collect());
+    /// All the model parameters at once, replacing any set so far.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
         self
     }
 
-    /// A number between 0 and 20. Whether to return log probabilities of the output tokens.
-    pub fn logprobs(mut self, logprobs: u8) -> Self {
-        self.logprobs = Some(logprobs);
+    /// The size of the context window in tokens.
+    ///
+    /// Defaults to `2048` on the server, which cuts off longer prompts.
+    pub fn num_ctx(mut self, num_ctx: u32) -> Self {
+        self.options.num_ctx = Some(num_ctx);
         self
     }
 
-    /// A number between 0 and 20. The number of most likely tokens to return at each token
-    /// position.
-    pub fn top_logprobs(mut self, top_logprobs: u8) -> Self {
-        self.top_logprobs = Some(top_logprobs);
+    /// The maximum number of tokens to generate, or `-1` for no limit.
+    pub fn num_predict(mut self, num_predict: i32) -> Self {
+        self.options.num_predict = Some(num_predict);
         self
     }
 
-    /// The maximum number of tokens that can be generated in the chat completion.
-    pub fn max_tokens(mut self, max_tokens: u16) -> Self {
-        self.max_tokens = Some(max_tokens);
+    /// The random seed to use, which makes the output reproducible.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.options.seed = Some(seed);
         self
     }
 
-    /// The number of chat completion choices to generate for each input message.
+    /// The sampling temperature to use.
     ///
-    /// Defaults to `1`.
-    pub fn n(mut self, n: u8) -> Self {
-        self.n = Some(n);
+    /// Defaults to `0.8` on the server.
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.options.temperature = Some(temperature);
         self
     }
 
-    /// A number between -2.0 and 2.0. Positive values penalize new tokens based on whether they
-    /// appear in the text so far, increasing the model's likelihood to talk about new topics.
+    /// Only sample from the top K options for each subsequent token.
     ///
-    /// Defaults to `0`.
-    pub fn presence_penalty(mut self, presence_penalty: f32) -> Self {
-        self.presence_penalty = Some(presence_penalty);
+    /// Defaults to `40` on the server.
+    pub fn top_k(mut self, top_k: u32) -> Self {
+        self.options.top_k = Some(top_k);
         self
     }
 
-    /// An object specifying the format that the model must output.
-    pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
-        self.response_format = Some(response_format);
+    /// An alternative to sampling with temperature, called nucleus sampling, where the model
+    /// considers the results of the tokens with top_p probability mass.
+    ///
+    /// Defaults to `0.9` on the server.
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.options.top_p = Some(top_p);
         self
     }
 
-    /// A best effort to sample deterministically.
-    pub fn seed(mut self, seed: u64) -> Self {
-        self.seed = Some(seed);
+    /// The minimum probability of a token, relative to the most likely one, for it to be
+    /// considered.
+    pub fn min_p(mut self, min_p: f32) -> Self {
+        self.options.min_p = Some(min_p);
         self
     }
 
-    /// The latency tier to use for processing the request.
-    pub fn service_tier(mut self, service_tier: ServiceTier) -> Self {
-        self.service_tier = Some(service_tier);
+    /// How far back the model looks to prevent repetition, where `0` disables it and `-1` looks
+    /// at the whole context.
+    pub fn repeat_last_n(mut self, repeat_last_n: i32) -> Self {
+        self.options.repeat_last_n = Some(repeat_last_n);
         self
     }
 
-    /// Up to 4 sequences where the API will stop generating further tokens.
-    pub fn stop(mut self, stop: String) -> Self {
-        self.stop = Some(stop);
+    /// How strongly to penalize repetitions, where higher values penalize them more.
+    ///
+    /// Defaults to `1.1` on the server.
+    pub fn repeat_penalty(mut self, repeat_penalty: f32) -> Self {
+        self.options.repeat_penalty = Some(repeat_penalty);
         self
     }
 
-    /// Whether to stream the response.
-    ///
-    /// Defaults to `true` if not specified.
-    pub fn stream(mut self, stream: bool) -> Self {
-        self.stream = Some(stream);
+    /// Penalizes new tokens based on whether they appear in the text so far.
+    pub fn presence_penalty(mut self, presence_penalty: f32) -> Self {
+        self.options.presence_penalty = Some(presence_penalty);
         self
     }
 
-    /// Options for streaming response.
-    pub fn stream_options(mut self, stream_options: StreamOptions) -> Self {
-        self.stream_options = Some(stream_options);
+    /// Penalizes new tokens based on their frequency in the text so far.
+    pub fn frequency_penalty(mut self, frequency_penalty: f32) -> Self {
+        self.options.frequency_penalty = Some(frequency_penalty);
         self
     }
 
-    /// The sampling temperature to use.
-    ///
-    /// Defaults to `1`.
-    pub fn temperature(mut self, temperature: f32) -> Self {
-        self.temperature = Some(temperature);
+    /// Enables Mirostat sampling: `0` disables it, `1` is Mirostat and `2` is Mirostat 2.0.
+    pub fn mirostat(mut self, mirostat: u8) -> Self {
+        self.options.mirostat = Some(mirostat);
         self
     }
 
-    /// An alternative to sampling with temperature, called nucleus sampling, where the model
-    /// considers the results of the tokens with top_p probability mass.
+    /// The Mirostat target entropy, where lower values give more focused text.
+    pub fn mirostat_tau(mut self, mirostat_tau: f32) -> Self {
+        self.options.mirostat_tau = Some(mirostat_tau);
+        self
+    }
+
+    /// The Mirostat learning rate, or how quickly it responds to the generated text.
+    pub fn mirostat_eta(mut self, mirostat_eta: f32) -> Self {
+        self.options.mirostat_eta = Some(mirostat_eta);
+        self
+    }
+
+    /// Sequences where the model will stop generating further tokens.
+    pub fn stop(mut self, stop: Vec<String>) -> Self {
+        self.options.stop = Some(stop);
+        self
+    }
+
+    /// Whether to stream the response.
     ///
-    /// Defaults to `1`.
-    pub fn top_p(mut self, top_p: f32) -> Self {
-        self.top_p = Some(top_p);
+    /// Defaults to `true` on the server.
+    pub fn stream(mut self, stream: bool) -> Self {
+        self.stream = Some(stream);
         self
     }
 
-    /// A list of tools the model may call.
-    pub fn tools(mut self, tools: Vec<Tool>) -> Self {
-        self.tools = Some(tools);
+    /// How long the model stays loaded after a request, like `"5m"`, or `"-1m"` to keep it
+    /// loaded.
+    pub fn keep_alive(mut self, keep_alive: impl Into<String>) -> Self {
+        self.keep_alive = Some(keep_alive.into());
         self
     }
 
-    /// Controls which (if any) tool is called by the model.
-    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
-        self.tool_choice = Some(tool_choice);
+    /// A list of tools the model may call.
+    pub fn tools(mut self, tools: Vec<Tool>) -> Self {
+        self.tools = Some(tools);
         self
     }
 
-    /// Whether to enable parallel tool calls.
+    /// The policy for retrying failed requests and timing them out.
     ///
-    /// Defaults to `true`.
-    pub fn parallel_tool_calls(mut self, parallel_tool_calls: bool) -> Self {
-        self.parallel_tool_calls = Some(parallel_tool_calls);
+    /// Defaults to `RetryPolicy::default()`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
         self
     }
 
-    /// A unique identifier representing your end-user.
-    pub fn user(mut self, user: String) -> Self {
-        self.user = Some(user);
+    /// The HTTP client to send requests with, for example one built from an
+    /// `http::HttpClientConfig` with a proxy or extra CA roots.
+    ///
+    /// Defaults to a pooled client shared by all models.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
         self
     }
 }
 
 impl ModelBuilder {
-    /// Builds the OpenAI model.
+    /// Builds the Ollama model.
     pub fn build(self) -> OllamaModel {
         let config = Config {
             model: self.model.unwrap_or(ModelType::Llama3_1_8B.to_string()),
-            frequency_penalty: self.frequency_penalty,
-            logit_bias: self.logit_bias,
-            logprobs: self.logprobs,
-            top_logprobs: self.top_logprobs,
-            max_tokens: self.max_tokens,
-            n: self.n,
-            presence_penalty: self.presence_penalty,
-            response_format: self.response_format,
-            seed: self.seed,
-            service_tier: self.service_tier,
-            stop: self.stop,
+            format: self.format,
+            options: self.options,
             stream: self.stream,
-            stream_options: self.stream_options,
-            temperature: self.temperature,
-            top_p: self.top_p,
+            keep_alive: self.keep_alive,
             tools: self.tools,
-            tool_choice: self.tool_choice,
-            parallel_tool_calls: self.parallel_tool_calls,
-            user: self.user,
         };
 
         OllamaModel {
             config: Cow::Owned(config),
             base_url: self.base_url.unwrap_or(OLLAMA_API_URL.to_string()),
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
         }
     }
 }
-
-//--------------------------------------------------------------------------------------------------
-// Trait Implementations
-//--------------------------------------------------------------------------------------------------
@@ -1,108 +1,146 @@
-use std::collections::HashMap;
-
 use serde::{Deserialize, Serialize};
 use strum_macros::Display;
 
+use crate::models::OutputSchema;
+
 //--------------------------------------------------------------------------------------------------
 // Constants
 //--------------------------------------------------------------------------------------------------
 
-/// The URL for the OpenAI API.
+/// The URL for the Ollama chat API.
 pub const OLLAMA_API_URL: &str = "http://localhost:11434/api/chat";
 
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
-/// The configuration for the OpenAI model.
+/// The configuration for the Ollama model, in the shape of the chat API's request.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Config {
     /// The ID of the model to use.
     pub model: String,
 
-    /// A number between -2.0 and 2.0. Positive values penalize new tokens based on their existing
-    /// frequency in the text so far, decreasing the model's likelihood to repeat the same line
-    /// verbatim.
+    /// The format the model must output, either any JSON or JSON matching a schema.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub frequency_penalty: Option<f32>,
+    pub format: Option<Format>,
 
-    /// Modifies the likelihood of specified tokens appearing in the completion.
-    ///
-    /// The associated value is a number between -100 and 100. Positive values increase the
-    /// likelihood of the specified tokens appearing in the completion.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub logit_bias: Option<HashMap<u64, i8>>,
+    /// The model parameters, overriding the ones in the model's Modelfile.
+    #[serde(default, skip_serializing_if = "Options::is_empty")]
+    pub options: Options,
 
-    /// Whether to return log probabilities of the output tokens.
+    /// Whether to stream the response as newline-delimited JSON.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub logprobs: Option<u8>,
+    pub stream: Option<bool>,
 
-    /// A number between 0 and 20 specifying the number of most likely tokens to return at each
-    /// token position.
+    /// How long the model stays loaded after the request, like `"5m"`, or `"-1m"` to keep it
+    /// loaded.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub top_logprobs: Option<u8>,
+    pub keep_alive: Option<String>,
 
-    /// The maximum number of tokens that can be generated in the chat completion.
+    /// A list of tools the model may call.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub max_tokens: Option<u16>,
+    pub tools: Option<Vec<Tool>>,
+}
 
-    /// The number of chat completion choices to generate for each input message.``
+/// The model parameters sent in the `options` object.
+///
+/// See [here](https://github.com/ollama/ollama/blob/main/docs/modelfile.md#valid-parameters-and-values)
+/// for more.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct Options {
+    /// The size of the context window in tokens. Ollama defaults to 2048, cutting off longer
+    /// prompts.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub n: Option<u8>,
+    pub num_ctx: Option<u32>,
 
-    /// A number between -2.0 and 2.0. Positive values penalize new tokens based on whether they
-    /// appear in the text so far, increasing the model's likelihood to talk about new topics.
+    /// The maximum number of tokens to generate, or `-1` for no limit.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub presence_penalty: Option<f32>,
+    pub num_predict: Option<i32>,
 
-    /// An object specifying the format that the model must output.
+    /// The number of tokens from the start of the prompt kept when the context is cut off.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub response_format: Option<ResponseFormat>,
+    pub num_keep: Option<i32>,
 
-    /// A best effort to sample deterministically.
+    /// The random seed to use, which makes the output reproducible.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub seed: Option<u64>,
 
-    /// The latency tier to use for processing the request.
+    /// The sampling temperature to use.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub service_tier: Option<ServiceTier>,
+    pub temperature: Option<f32>,
 
-    /// Up to 4 sequences where the API will stop generating further tokens.
+    /// Only sample from the top K options for each subsequent token.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub stop: Option<String>,
+    pub top_k: Option<u32>,
 
-    /// Whether to response should be sent as data-only server-sent events as they become available.
+    /// An alternative to sampling with temperature, called nucleus sampling, where the model
+    /// considers the results of the tokens with top_p probability mass.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub stream: Option<bool>,
+    pub top_p: Option<f32>,
 
-    /// Options for streaming response.
+    /// The minimum probability of a token, relative to the most likely one, for it to be
+    /// considered.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub stream_options: Option<StreamOptions>,
+    pub min_p: Option<f32>,
 
-    /// The sampling temperature to use.
+    /// The locally typical sampling parameter, where `1.0` disables it.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub temperature: Option<f32>,
+    pub typical_p: Option<f32>,
 
-    /// An alternative to sampling with temperature, called nucleus sampling, where the model
-    /// considers the results of the tokens with top_p probability mass.
+    /// How far back the model looks to prevent repetition, where `0` disables it and `-1` looks
+    /// at the whole context.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub top_p: Option<f32>,
+    pub repeat_last_n: Option<i32>,
 
-    /// A list of tools the model may call.
+    /// How strongly to penalize repetitions, where higher values penalize them more.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tools: Option<Vec<Tool>>,
+    pub repeat_penalty: Option<f32>,
+
+    /// Penalizes new tokens based on whether they appear in the text so far.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+
+    /// Penalizes new tokens based on their frequency in the text so far.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+
+    /// Enables Mirostat sampling for controlling perplexity: `0` disables it, `1` is Mirostat and
+    /// `2` is Mirostat 2.0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat: Option<u8>,
 
-    /// Controls which (if any) tool is called by the model.
+    /// The Mirostat target entropy, where lower values give more focused text.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tool_choice: Option<ToolChoice>,
+    pub mirostat_tau: Option<f32>,
 
-    /// Whether to allow parallel tool calls.
+    /// The Mirostat learning rate, or how quickly it responds to the generated text.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub parallel_tool_calls: Option<bool>,
+    pub mirostat_eta: Option<f32>,
 
-    /// A unique identifier representing your end-user.
+    /// Sequences where the model will stop generating further tokens.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub user: Option<String>,
+    pub stop: Option<Vec<String>>,
+
+    /// The number of layers to offload to the GPU.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_gpu: Option<u32>,
+
+    /// The number of threads to use for generation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_thread: Option<u32>,
+}
+
+/// The format the model must output.
+///
+/// Serialized as `"json"` for any JSON or as the schema itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(from = "serde_json::Value", into = "serde_json::Value")]
+pub enum Format {
+    /// Any valid JSON.
+    Json,
+
+    /// JSON matching the given JSON schema.
+    JsonSchema(serde_json::Value),
 }
 
 /// The model type.
@@ -120,43 +158,6 @@ pub enum ModelType {
     Llama3_1_70B,
 }
 
-/// Specifies the latency tier to use for processing the request.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub enum ServiceTier {
-    /// The system will utilize scale tier credits until they are exhausted.
-    #[serde(rename = "auto")]
-    Auto,
-
-    /// The request will be processed using the default service tier with a lower uptime SLA and
-    /// no latency guarentee.
-    #[serde(rename = "default")]
-    Default,
-}
-
-/// An object specifying the format that the model must output.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(tag = "type")]
-pub enum ResponseFormat {
-    /// Enables Structured Outputs which ensures the model will match your supplied JSON schema
-    #[serde(rename = "json_schema")]
-    JsonSchema {
-        /// The JSON schema that the model must match.
-        json_schema: serde_json::Value,
-    },
-
-    /// Enables JSON mode, which ensures the message the model generates is valid JSON.
-    #[serde(rename = "json_object")]
-    JsonObject,
-}
-
-/// Options for streaming response.
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
-pub struct StreamOptions {
-    /// Whether to include the usage information in the response.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub include_usage: Option<bool>,
-}
-
 /// A tool that the model may call.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Tool {
@@ -167,17 +168,6 @@ pub struct Tool {
     pub function: Function,
 }
 
-/// Controls which (if any) tool is called by the model.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(untagged)]
-pub enum ToolChoice {
-    /// The level of requirement for the tool.
-    Requirement(Requirement),
-
-    /// The tool that the model must call.
-    RequiredTool(RequiredTool),
-}
-
 /// The type of tool.
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub enum ToolType {
@@ -201,34 +191,17 @@ pub struct Function {
     /// The parameters that the function accepts, described as a JSON schema.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parameters: Option<serde_json::Value>,
-
-    /// Whether to enable strict schema adherence when generating the function call.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub strict: Option<bool>,
-}
-
-/// The level of requirement for the tool.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum Requirement {
-    /// The model will not call any tools.
-    None,
-
-    /// The model can pick between generating a message or calling one or more tools
-    Auto,
-
-    /// The model must call one or more tools
-    Required,
 }
 
-/// Specifies a tool the model should use. Use to force the model to call a specific function.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub struct RequiredTool {
-    /// The type of tool.
-    pub r#type: ToolType,
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
 
-    /// The function that the tool represents.
-    pub function: Function,
+impl Options {
+    /// Whether no option is set, in which case the `options` object is left out.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -239,29 +212,39 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             model: ModelType::Llama3_1_8B.to_string(),
-            frequency_penalty: None,
-            logit_bias: None,
-            logprobs: None,
-            top_logprobs: None,
-            max_tokens: None,
-            n: None,
-            presence_penalty: None,
-            response_format: None,
-            seed: None,
-            service_tier: None,
-            stop: None,
+            format: None,
+            options: Options::default(),
             stream: None,
-            stream_options: None,
-            temperature: None,
-            top_p: None,
+            keep_alive: None,
             tools: None,
-            tool_choice: None,
-            parallel_tool_calls: None,
-            user: None,
         }
     }
 }
 
+impl From<serde_json::Value> for Format {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(format) if format == "json" => Format::Json,
+            schema => Format::JsonSchema(schema),
+        }
+    }
+}
+
+impl From<Format> for serde_json::Value {
+    fn from(value: Format) -> Self {
+        match value {
+            Format::Json => serde_json::Value::String("json".to_string()),
+            Format::JsonSchema(schema) => schema,
+        }
+    }
+}
+
+impl From<OutputSchema> for Format {
+    fn from(value: OutputSchema) -> Self {
+        Format::JsonSchema(value.schema)
+    }
+}
+
 impl From<ModelType> for String {
     fn from(value: ModelType) -> Self {
         value.to_string()
@@ -0,0 +1,343 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::models::{
+    http,
+    retry::{self, RetryPolicy},
+    EmbeddingModel, EmbeddingResponse, ModelError, ModelResult, TokenUsage,
+};
+
+use super::ResponseError;
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The URL for the Ollama embed API.
+pub const OLLAMA_EMBED_API_URL: &str = "http://localhost:11434/api/embed";
+
+/// The embedding model used when none is given.
+pub const DEFAULT_OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+/// The number of texts embedded in a single request by default.
+pub const OLLAMA_EMBEDDING_BATCH_SIZE: usize = 64;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `OllamaEmbeddingModel` embeds text with a model served by Ollama.
+#[derive(Debug, Clone)]
+pub struct OllamaEmbeddingModel {
+    pub(crate) model: String,
+    pub(crate) base_url: String,
+    pub(crate) dimensions: Option<usize>,
+    pub(crate) truncate: Option<bool>,
+    pub(crate) keep_alive: Option<String>,
+    pub(crate) batch_size: usize,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) client: reqwest::Client,
+}
+
+/// A builder for an Ollama embedding model.
+#[derive(Debug, Clone)]
+pub struct EmbeddingModelBuilder {
+    model: Option<String>,
+    base_url: Option<String>,
+    dimensions: Option<usize>,
+    truncate: Option<bool>,
+    keep_alive: Option<String>,
+    batch_size: usize,
+    retry: RetryPolicy,
+    client: Option<reqwest::Client>,
+}
+
+/// The request body for the embed API.
+#[derive(Debug, Serialize)]
+pub struct EmbeddingRequestBody {
+    /// The name of the model to use.
+    pub model: String,
+
+    /// The texts to embed.
+    pub input: Vec<String>,
+
+    /// Whether to truncate texts that don't fit in the model's context instead of failing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncate: Option<bool>,
+
+    /// How long the model stays loaded after the request, e.g. `"5m"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<String>,
+
+    /// The number of dimensions the embeddings should have, for models that support it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<usize>,
+}
+
+/// Embed response body.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingResponseBody {
+    /// A successful response.
+    Ok(EmbeddingResponseOk),
+
+    /// An error response.
+    Error(ResponseError),
+}
+
+/// A successful embed response.
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingResponseOk {
+    /// The model used to create the embeddings.
+    pub model: String,
+
+    /// The embeddings, one per input text.
+    pub embeddings: Vec<Vec<f32>>,
+
+    /// Number of tokens in the input.
+    pub prompt_eval_count: Option<u64>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl OllamaEmbeddingModel {
+    /// Creates a builder for the model.
+    pub fn builder() -> EmbeddingModelBuilder {
+        EmbeddingModelBuilder::default()
+    }
+
+    /// Calls the API with the given texts.
+    pub async fn call(&self, texts: Vec<String>) -> ModelResult<EmbeddingResponseOk> {
+        let request = self
+            .client
+            .post(&self.base_url)
+            .json(&EmbeddingRequestBody {
+                model: self.model.clone(),
+                input: texts,
+                truncate: self.truncate,
+                keep_alive: self.keep_alive.clone(),
+                dimensions: self.dimensions,
+            });
+
+        let response = self
+            .retry
+            .send(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
+        let body = response.text().await?;
+        debug!("body length = {}", body.len());
+
+        match serde_json::from_str(&body)? {
+            EmbeddingResponseBody::Ok(body) => Ok(body),
+            EmbeddingResponseBody::Error(error) => Err(ModelError::OllamaResponseError(error)),
+        }
+    }
+}
+
+impl EmbeddingResponseOk {
+    /// Converts the response, checking there is an embedding for each of `count` texts.
+    fn into_embedding_response(self, count: usize) -> ModelResult<EmbeddingResponse> {
+        if self.embeddings.len() != count {
+            return Err(ModelError::EmptyEmbeddingResponse);
+        }
+
+        Ok(EmbeddingResponse {
+            embeddings: self.embeddings,
+            model: self.model,
+            usage: self
+                .prompt_eval_count
+                .map(|prompt_tokens| TokenUsage::new(prompt_tokens, 0)),
+        })
+    }
+}
+
+impl EmbeddingModelBuilder {
+    /// The name of the model to use.
+    ///
+    /// Defaults to `DEFAULT_OLLAMA_EMBEDDING_MODEL`.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// The URL of the embed endpoint.
+    ///
+    /// Defaults to `OLLAMA_EMBED_API_URL`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// The number of dimensions the embeddings should have, for models that support it.
+    pub fn dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// Whether to truncate texts that don't fit in the model's context instead of failing.
+    ///
+    /// Defaults to `true` on the server.
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.truncate = Some(truncate);
+        self
+    }
+
+    /// How long the model stays loaded after a request, e.g. `"5m"`.
+    pub fn keep_alive(mut self, keep_alive: impl Into<String>) -> Self {
+        self.keep_alive = Some(keep_alive.into());
+        self
+    }
+
+    /// The maximum number of texts embedded in a single request.
+    ///
+    /// Defaults to `OLLAMA_EMBEDDING_BATCH_SIZE`.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// The policy for retrying failed requests and timing them out.
+    ///
+    /// Defaults to `RetryPolicy::default()`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The HTTP client to send requests with.
+    ///
+    /// Defaults to a pooled client shared by all models.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Builds the embedding model.
+    pub fn build(self) -> OllamaEmbeddingModel {
+        OllamaEmbeddingModel {
+            model: self
+                .model
+                .unwrap_or(DEFAULT_OLLAMA_EMBEDDING_MODEL.to_string()),
+            base_url: self.base_url.unwrap_or(OLLAMA_EMBED_API_URL.to_string()),
+            dimensions: self.dimensions,
+            truncate: self.truncate,
+            keep_alive: self.keep_alive,
+            batch_size: self.batch_size,
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the number of dimensions of the common Ollama embedding models.
+fn known_dimensions(model: &str) -> Option<usize> {
+    let name = model.split(':').next().unwrap_or(model);
+    match name {
+        "nomic-embed-text" => Some(768),
+        "mxbai-embed-large" | "snowflake-arctic-embed" => Some(1024),
+        "all-minilm" => Some(384),
+        _ => None,
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl EmbeddingModel for OllamaEmbeddingModel {
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.dimensions.or_else(|| known_dimensions(&self.model))
+    }
+
+    fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    fn embed_batch(&self, texts: Vec<String>) -> BoxFuture<'_, ModelResult<EmbeddingResponse>> {
+        Box::pin(async move {
+            let count = texts.len();
+            let response = self.call(texts).await?;
+            response.into_embedding_response(count)
+        })
+    }
+}
+
+impl Default for EmbeddingModelBuilder {
+    fn default() -> Self {
+        Self {
+            model: None,
+            base_url: None,
+            dimensions: None,
+            truncate: None,
+            keep_alive: None,
+            batch_size: OLLAMA_EMBEDDING_BATCH_SIZE,
+            retry: RetryPolicy::default(),
+            client: None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_model_ollama_embedding() -> anyhow::Result<()> {
+        let model = OllamaEmbeddingModel::builder()
+            .model("nomic-embed-text:latest")
+            .build();
+        assert_eq!(model.base_url, OLLAMA_EMBED_API_URL);
+        assert_eq!(model.dimensions(), Some(768));
+        assert_eq!(model.batch_size(), OLLAMA_EMBEDDING_BATCH_SIZE);
+
+        let body = serde_json::to_value(EmbeddingRequestBody {
+            model: "all-minilm".to_string(),
+            input: vec!["Hello".to_string()],
+            truncate: None,
+            keep_alive: Some("5m".to_string()),
+            dimensions: None,
+        })?;
+        assert_eq!(
+            body,
+            json!({ "model": "all-minilm", "input": ["Hello"], "keep_alive": "5m" })
+        );
+
+        let body: EmbeddingResponseBody = serde_json::from_value(json!({
+            "model": "all-minilm",
+            "embeddings": [[0.1, 0.2], [0.3, 0.4]],
+            "total_duration": 14143917,
+            "load_duration": 1019500,
+            "prompt_eval_count": 8
+        }))?;
+        let EmbeddingResponseBody::Ok(body) = body else {
+            panic!("expected a successful response");
+        };
+        assert!(matches!(
+            body.into_embedding_response(3),
+            Err(ModelError::EmptyEmbeddingResponse)
+        ));
+
+        let body: EmbeddingResponseBody =
+            serde_json::from_value(json!({ "error": "model \"foo\" not found" }))?;
+        assert!(matches!(body, EmbeddingResponseBody::Error(_)));
+
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::models::{AssistantMessage, Prompt, PromptMessage, SystemMessage, UserMessage};
+use crate::models::{
+    self, AssistantMessage, ContentPart, MessageContent, ModelError, ModelResult, Prompt,
+    PromptMessage, SystemMessage, ToolMessage, UserMessage,
+};
 
 use super::Config;
 
@@ -20,12 +23,12 @@ pub struct RequestBody {
     pub config: Config,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 /// A collection of messages in a chat conversation with the model.
 pub struct RequestMessages(pub Vec<RequestMessage>);
 
 /// A message in a chat conversation with the model.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(tag = "role")]
 pub enum RequestMessage {
     /// A message that sets the context for the conversation.
@@ -48,6 +51,10 @@ pub enum RequestMessage {
 
         /// The content of the message.
         content: String,
+
+        /// The base64 encoded images attached to the message.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        images: Option<Vec<String>>,
     },
 
     /// A message that assumes the role of the assistant.
@@ -63,7 +70,39 @@ pub enum RequestMessage {
         /// Refusal message
         #[serde(skip_serializing_if = "Option::is_none")]
         refusal: Option<String>,
+
+        /// The tool calls made by the assistant.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tool_calls: Option<Vec<ToolCall>>,
     },
+
+    /// A message for a tool call.
+    #[serde(rename = "tool")]
+    Tool {
+        /// The name of the tool that was called.
+        #[serde(rename = "tool_name", skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+
+        /// The content of the message.
+        content: String,
+    },
+}
+
+/// A tool call made by the assistant.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolCall {
+    /// The function call made by the tool.
+    pub function: FunctionCall,
+}
+
+/// A function call made by the tool.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionCall {
+    /// The name of the function.
+    pub name: String,
+
+    /// The arguments to call the function with.
+    pub arguments: serde_json::Value,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -75,7 +114,7 @@ pub enum RequestMessage {
 #[serde(untagged)]
 pub enum ResponseBody {
     /// A successful response.
-    Ok(ResponseOk),
+    Ok(Box<ResponseOk>),
 
     /// An error response.
     Error(ResponseError),
@@ -92,6 +131,9 @@ pub struct ResponseError {
 }
 
 /// Represents a successful chat completion response returned by model, based on the provided input.
+///
+/// When streaming, each line of the response is one of these carrying a piece of the message, and
+/// the last one has `done` set along with the stats for the whole response.
 #[derive(Debug, Deserialize)]
 pub struct ResponseOk {
     /// The timestamp of when the chat completion was created.
@@ -107,21 +149,39 @@ pub struct ResponseOk {
     pub done: bool,
 
     /// The reason the model stopped generating tokens.
-    pub done_reason: String,
+    ///
+    /// Only present once the model is done.
+    pub done_reason: Option<String>,
 
     /// The context of the message.
     pub context: Option<Vec<u64>>,
 
+    /// The timing and token counts of the response.
+    ///
+    /// Only present once the model is done.
+    #[serde(flatten)]
+    pub stats: Option<ResponseStats>,
+}
+
+/// The timing and token counts Ollama reports at the end of a response. Durations are in
+/// nanoseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ResponseStats {
     /// The total duration of the message.
     pub total_duration: u64,
 
     /// The load duration of the message.
+    #[serde(default)]
     pub load_duration: u64,
 
     /// The prompt evaluation count of the message.
+    ///
+    /// Ollama leaves this out when the whole prompt was already cached.
+    #[serde(default)]
     pub prompt_eval_count: u64,
 
     /// The prompt evaluation duration of the message.
+    #[serde(default)]
     pub prompt_eval_duration: u64,
 
     /// The evaluation count of the message.
@@ -132,13 +192,16 @@ pub struct ResponseOk {
 }
 
 /// The message generated by the model.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ResponseMessage {
     /// The content of the message.
     pub content: Option<String>,
 
     /// The role of the message.
     pub role: Option<String>,
+
+    /// The tool calls made by the assistant.
+    pub tool_calls: Option<Vec<ToolCall>>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -159,29 +222,192 @@ impl ResponseBody {
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
 
-impl From<Prompt> for RequestMessages {
-    fn from(prompt: Prompt) -> Self {
-        let request_messages = prompt
+impl TryFrom<Prompt> for RequestMessages {
+    type Error = ModelError;
+
+    fn try_from(prompt: Prompt) -> ModelResult<Self> {
+        prompt
             .into_iter()
-            .map(|m| match m {
-                PromptMessage::System(SystemMessage { content }) => RequestMessage::System {
-                    content,
-                    name: None,
-                },
-                PromptMessage::User(UserMessage { content }) => RequestMessage::User {
-                    content,
-                    name: None,
-                },
-                PromptMessage::Assistant(AssistantMessage { content }) => {
-                    RequestMessage::Assistant {
-                        content,
-                        name: None,
-                        refusal: None,
-                    }
+            .map(RequestMessage::try_from)
+            .collect::<ModelResult<_>>()
+            .map(Self)
+    }
+}
+
+impl TryFrom<PromptMessage> for RequestMessage {
+    type Error = ModelError;
+
+    fn try_from(message: PromptMessage) -> ModelResult<Self> {
+        let message = match message {
+            PromptMessage::System(SystemMessage { content, name }) => {
+                RequestMessage::System { content, name }
+            }
+            PromptMessage::User(UserMessage { content, name }) => {
+                let images = match &content {
+                    MessageContent::Text(_) => vec![],
+                    MessageContent::Parts(parts) => parts
+                        .iter()
+                        .filter_map(|part| match part {
+                            ContentPart::Image(image) => Some(image_data(&image.url)),
+                            ContentPart::Text(_) => None,
+                        })
+                        .collect::<ModelResult<_>>()?,
+                };
+
+                RequestMessage::User {
+                    content: content.text(),
+                    name,
+                    images: (!images.is_empty()).then_some(images),
                 }
+            }
+            PromptMessage::Assistant(AssistantMessage {
+                content,
+                name,
+                refusal,
+                tool_calls,
+            }) => RequestMessage::Assistant {
+                content: content.unwrap_or_default(),
+                name,
+                refusal,
+                tool_calls: (!tool_calls.is_empty())
+                    .then(|| tool_calls.into_iter().map(Into::into).collect()),
+            },
+            PromptMessage::Tool(ToolMessage { content, name, .. }) => {
+                RequestMessage::Tool { content, name }
+            }
+        };
+
+        Ok(message)
+    }
+}
+
+impl From<models::ToolCall> for ToolCall {
+    fn from(tool_call: models::ToolCall) -> Self {
+        // Ollama expects the arguments as an object rather than a JSON string.
+        let arguments = serde_json::from_str(&tool_call.arguments)
+            .unwrap_or(serde_json::Value::String(tool_call.arguments));
+
+        ToolCall {
+            function: FunctionCall {
+                name: tool_call.name,
+                arguments,
+            },
+        }
+    }
+}
+
+impl From<ResponseMessage> for PromptMessage {
+    fn from(message: ResponseMessage) -> Self {
+        // Ollama does not assign ids to tool calls so we derive them from their position.
+        let tool_calls = message
+            .tool_calls
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(index, tool_call)| {
+                models::ToolCall::new(
+                    format!("call_{index}"),
+                    tool_call.function.name,
+                    tool_call.function.arguments.to_string(),
+                )
             })
             .collect();
 
-        Self(request_messages)
+        PromptMessage::Assistant(AssistantMessage {
+            content: message.content,
+            name: None,
+            refusal: None,
+            tool_calls,
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the raw base64 data of an image, the only form Ollama accepts, by removing the
+/// `data:<mime>;base64,` prefix of its data URL.
+///
+/// Images given by an `http(s)` URL are not downloaded, since the prompt is converted without
+/// access to the network. They fail with `ModelError::UnsupportedImageUrl` instead of being sent
+/// as data Ollama can't decode.
+fn image_data(url: &str) -> ModelResult<String> {
+    match url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+    {
+        Some((_, data)) => Ok(data.to_string()),
+        None => Err(ModelError::UnsupportedImageUrl(url.to_string())),
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::models::ImageContent;
+
+    use super::*;
+
+    #[test]
+    fn test_model_ollama_request_messages_from_prompt() -> anyhow::Result<()> {
+        let mut prompt = Prompt::new();
+        prompt.push(PromptMessage::system("You are a helpful assistant."));
+        prompt.push(PromptMessage::user(vec![
+            ContentPart::text("What is in this image?"),
+            ContentPart::Image(ImageContent {
+                url: "data:image/png;base64,iVBORw0KGgo=".to_string(),
+                detail: None,
+            }),
+        ]));
+        prompt.push(PromptMessage::Assistant(AssistantMessage::with_tool_calls(
+            [models::ToolCall::new(
+                "call_0",
+                "describe",
+                r#"{"detail":"low"}"#,
+            )],
+        )));
+        prompt.push(PromptMessage::Tool(
+            ToolMessage::new("call_0", "a cat").with_name("describe"),
+        ));
+
+        assert_eq!(
+            serde_json::to_value(RequestMessages::try_from(prompt)?)?,
+            json!([
+                { "role": "system", "content": "You are a helpful assistant." },
+                {
+                    "role": "user",
+                    "content": "What is in this image?",
+                    "images": ["iVBORw0KGgo="]
+                },
+                {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{
+                        "function": { "name": "describe", "arguments": { "detail": "low" } }
+                    }]
+                },
+                { "role": "tool", "tool_name": "describe", "content": "a cat" }
+            ])
+        );
+
+        let mut prompt = Prompt::new();
+        prompt.push(PromptMessage::user(vec![ContentPart::Image(
+            ImageContent {
+                url: "https://example.com/cat.png".to_string(),
+                detail: None,
+            },
+        )]));
+        assert!(matches!(
+            RequestMessages::try_from(prompt),
+            Err(ModelError::UnsupportedImageUrl(url)) if url == "https://example.com/cat.png"
+        ));
+
+        Ok(())
     }
 }
@@ -1,7 +1,9 @@
 //! Module for working with OpenAI models.
 
+mod admin;
 mod builder;
 mod config;
+mod embedding;
 mod message;
 mod model;
 mod stream;
@@ -10,8 +12,10 @@ mod stream;
 // Exports
 //--------------------------------------------------------------------------------------------------
 
+pub use admin::*;
 pub use builder::*;
 pub use config::*;
+pub use embedding::*;
 pub use message::*;
 pub use model::*;
 pub use stream::*;
@@ -1,15 +1,19 @@
 use std::borrow::Cow;
 
-use futures::stream::BoxStream;
+use futures::{future::BoxFuture, stream::BoxStream};
 use tracing::debug;
 
 use crate::models::{
-    ollama::{StreamOptions, OLLAMA_API_URL},
-    ModelError, ModelResult, Prompt, TextModel, TextStreamModel,
+    http,
+    ollama::OLLAMA_API_URL,
+    retry::{self, RetryPolicy},
+    ChatModel, ModelError, ModelResponse, ModelResult, OutputSchema, Prompt, StreamEvent,
+    TextModel, TextStreamModel, TokenUsage,
 };
 
 use super::{
-    Config, ModelBuilder, RequestBody, RequestMessages, ResponseBody, ResponseOk, ResponseStream,
+    Config, ModelBuilder, RequestBody, RequestMessages, ResponseBody, ResponseError, ResponseOk,
+    ResponseStream,
 };
 
 //--------------------------------------------------------------------------------------------------
@@ -21,6 +25,8 @@ use super::{
 pub struct OllamaModel {
     pub(crate) config: Cow<'static, Config>,
     pub(crate) base_url: String,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) client: reqwest::Client,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -36,14 +42,16 @@ impl OllamaModel {
     /// Calls the API with the given request messages.
     pub async fn call(&self, messages: impl Into<RequestMessages>) -> ModelResult<ResponseOk> {
         let config = self.get_config_without_streaming();
-        let request = reqwest::Client::new()
-            .post(&self.base_url)
-            .json(&RequestBody {
-                messages: messages.into(),
-                config: config.into_owned(),
-            });
-
-        let response = request.send().await?;
+        let request = self.client.post(&self.base_url).json(&RequestBody {
+            messages: messages.into(),
+            config: config.into_owned(),
+        });
+
+        let response = self
+            .retry
+            .send(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
         let body = response.text().await?;
         debug!("body = {body:#?}");
         let body: ResponseBody = serde_json::from_str(&body)?;
@@ -51,46 +59,49 @@ impl OllamaModel {
             return Err(ModelError::OllamaResponseError(body.unwrap_err()));
         };
 
-        Ok(body)
+        Ok(*body)
     }
 
     /// Calls the API with the given request messages and gets back a stream of response chunks.
-    pub fn call_streaming(
+    ///
+    /// The last chunk has `done` set and carries the stats for the whole response.
+    pub async fn call_streaming(
         &self,
         messages: impl Into<RequestMessages>,
     ) -> ModelResult<ResponseStream> {
-        let config = self.get_config_with_streaming(None);
+        let config = self.get_config_with_streaming();
         debug!("config = {config:#?}");
-        let request = reqwest::Client::new()
-            .post(&self.base_url)
-            .json(&RequestBody {
-                messages: messages.into(),
-                config: config.into_owned(),
-            });
-
-        Ok(ResponseStream::new(request))
+        let request = self.client.post(&self.base_url).json(&RequestBody {
+            messages: messages.into(),
+            config: config.into_owned(),
+        });
+
+        let response = self
+            .retry
+            .send_streaming(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
+        Ok(ResponseStream::new(response, self.retry.timeout))
     }
 
     /// Gets the model's configuration with streaming enabled.
-    fn get_config_with_streaming(&self, options: Option<StreamOptions>) -> Cow<Config> {
+    fn get_config_with_streaming(&self) -> Cow<'_, Config> {
         let mut config = Cow::Borrowed(self.config.as_ref());
 
-        if self.config.stream.is_none() {
+        if self.config.stream != Some(true) {
             config.to_mut().stream = Some(true);
         }
 
-        if let Some(options) = options {
-            config.to_mut().stream_options = Some(options);
-        }
-
         config
     }
 
     /// Gets the model's configuration without streaming enabled.
-    fn get_config_without_streaming(&self) -> Cow<Config> {
+    ///
+    /// Ollama streams unless told not to, so `stream` is always sent.
+    fn get_config_without_streaming(&self) -> Cow<'_, Config> {
         let mut config = Cow::Borrowed(self.config.as_ref());
 
-        if self.config.stream.is_some() {
+        if self.config.stream != Some(false) {
             config.to_mut().stream = Some(false);
         }
 
@@ -103,15 +114,20 @@ impl OllamaModel {
         response.message.content.clone().unwrap_or_default()
     }
 
-    /// Extract main content from response chunk
-    pub(crate) fn extract_content_from_response_chunk(response: &ResponseOk) -> String {
-        response.message.content.clone().unwrap_or_default()
-    }
-
     /// Get the model's configuration
     pub fn get_config(&self) -> &Config {
         &self.config
     }
+
+    /// Get the HTTP client the model sends requests with
+    pub fn get_client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Get the model's retry policy
+    pub fn get_retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -120,19 +136,67 @@ impl OllamaModel {
 
 impl TextModel for OllamaModel {
     async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
-        let response = self.call(prompt.into()).await?;
+        let response = self.call(RequestMessages::try_from(prompt.into())?).await?;
         let content = Self::extract_content_from_response(&response);
         Ok(content)
     }
+
+    async fn prompt_with_usage(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<ModelResponse> {
+        let response = self.call(RequestMessages::try_from(prompt.into())?).await?;
+        Ok(ModelResponse {
+            content: Self::extract_content_from_response(&response),
+            usage: response
+                .stats
+                .as_ref()
+                .map(|stats| TokenUsage::new(stats.prompt_eval_count, stats.eval_count)),
+            model: response.model,
+        })
+    }
 }
 
 impl TextStreamModel for OllamaModel {
     async fn prompt_stream(
         &self,
         prompt: impl Into<Prompt> + Send,
-    ) -> ModelResult<BoxStream<'static, ModelResult<String>>> {
-        let stream = self.call_streaming(prompt.into())?;
-        Ok(Box::pin(stream))
+    ) -> ModelResult<BoxStream<'static, ModelResult<StreamEvent>>> {
+        let stream = self
+            .call_streaming(RequestMessages::try_from(prompt.into())?)
+            .await?;
+        Ok(stream.into_events())
+    }
+}
+
+impl ChatModel for OllamaModel {
+    fn model_id(&self) -> &str {
+        &self.config.model
+    }
+
+    fn chat(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<String>> {
+        Box::pin(self.prompt(prompt))
+    }
+
+    fn chat_with_usage(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        Box::pin(self.prompt_with_usage(prompt))
+    }
+
+    fn chat_stream(
+        &self,
+        prompt: Prompt,
+    ) -> BoxFuture<'_, ModelResult<BoxStream<'static, ModelResult<StreamEvent>>>> {
+        Box::pin(self.prompt_stream(prompt))
+    }
+
+    fn chat_with_schema(
+        &self,
+        prompt: Prompt,
+        schema: OutputSchema,
+    ) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        let mut model = self.clone();
+        model.config.to_mut().format = Some(schema.into());
+        Box::pin(async move { model.prompt_with_usage(prompt).await })
     }
 }
 
@@ -141,6 +205,8 @@ impl Default for OllamaModel {
         Self {
             config: Cow::Owned(Config::default()),
             base_url: OLLAMA_API_URL.to_string(),
+            retry: RetryPolicy::default(),
+            client: http::default_client(),
         }
     }
 }
@@ -151,8 +217,15 @@ impl Default for OllamaModel {
 
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
+
     use crate::{
-        models::ollama::ModelType,
+        models::{
+            ollama::{Format, ModelType},
+            retry::tests::{http_response, mock_server},
+            StructuredModel,
+        },
+        prompt,
         utils::{self, Env},
     };
 
@@ -165,52 +238,92 @@ mod tests {
 
         assert_eq!(model.base_url, OLLAMA_API_URL.to_string());
         assert_eq!(model.config.model, ModelType::Llama3_1_8B.to_string());
-        assert_eq!(model.config.frequency_penalty, None);
-        assert_eq!(model.config.logit_bias, None);
-        assert_eq!(model.config.logprobs, None);
-        assert_eq!(model.config.top_logprobs, None);
-        assert_eq!(model.config.max_tokens, None);
-        assert_eq!(model.config.n, None);
-        assert_eq!(model.config.presence_penalty, None);
-        assert_eq!(model.config.response_format, None);
-        assert_eq!(model.config.seed, None);
-        assert_eq!(model.config.service_tier, None);
-        assert_eq!(model.config.stop, None);
+        assert_eq!(model.config.format, None);
+        assert!(model.config.options.is_empty());
         assert_eq!(model.config.stream, None);
-        assert_eq!(model.config.stream_options, None);
-        assert_eq!(model.config.temperature, None);
-        assert_eq!(model.config.top_p, None);
+        assert_eq!(model.config.keep_alive, None);
         assert_eq!(model.config.tools, None);
-        assert_eq!(model.config.tool_choice, None);
-        assert_eq!(model.config.parallel_tool_calls, None);
-        assert_eq!(model.config.user, None);
     }
 
     #[test]
-    fn test_model_ollama_builders() {
+    fn test_model_ollama_builders() -> anyhow::Result<()> {
         utils::load_env(Env::Dev);
-        let model = OllamaModel::builder().build();
+        let model = OllamaModel::builder()
+            .model("llama3.2")
+            .format(Format::JsonSchema(json!({ "type": "object" })))
+            .num_ctx(8192)
+            .num_predict(256)
+            .temperature(0.2)
+            .repeat_penalty(1.2)
+            .mirostat(2)
+            .stop(vec!["<contd>".to_string()])
+            .keep_alive("10m")
+            .build();
 
         assert_eq!(model.base_url, OLLAMA_API_URL.to_string());
-        assert_eq!(model.config.model, ModelType::Llama3_1_8B.to_string());
-        assert_eq!(model.config.frequency_penalty, None);
-        assert_eq!(model.config.logit_bias, None);
-        assert_eq!(model.config.logprobs, None);
-        assert_eq!(model.config.top_logprobs, None);
-        assert_eq!(model.config.max_tokens, None);
-        assert_eq!(model.config.n, None);
-        assert_eq!(model.config.presence_penalty, None);
-        assert_eq!(model.config.response_format, None);
-        assert_eq!(model.config.seed, None);
-        assert_eq!(model.config.service_tier, None);
-        assert_eq!(model.config.stop, None);
-        assert_eq!(model.config.stream, None);
-        assert_eq!(model.config.stream_options, None);
-        assert_eq!(model.config.temperature, None);
-        assert_eq!(model.config.top_p, None);
-        assert_eq!(model.config.tools, None);
-        assert_eq!(model.config.tool_choice, None);
-        assert_eq!(model.config.parallel_tool_calls, None);
-        assert_eq!(model.config.user, None);
+        assert_eq!(model.config.options.num_ctx, Some(8192));
+
+        let body = serde_json::to_value(RequestBody {
+            messages: RequestMessages(Vec::new()),
+            config: model.get_config_without_streaming().into_owned(),
+        })?;
+        assert_eq!(
+            body,
+            json!({
+                "model": "llama3.2",
+                "messages": [],
+                "format": { "type": "object" },
+                "options": {
+                    "num_ctx": 8192,
+                    "num_predict": 256,
+                    "temperature": 0.2f32,
+                    "repeat_penalty": 1.2f32,
+                    "mirostat": 2,
+                    "stop": ["<contd>"]
+                },
+                "stream": false,
+                "keep_alive": "10m"
+            })
+        );
+
+        let config = model.get_config_with_streaming();
+        assert_eq!(config.stream, Some(true));
+
+        let json: Config =
+            serde_json::from_value(json!({ "model": "llama3.1", "format": "json" }))?;
+        assert_eq!(json.format, Some(Format::Json));
+        assert_eq!(
+            serde_json::to_value(&json)?,
+            json!({ "model": "llama3.1", "format": "json" })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_model_ollama_prompt_structured() -> anyhow::Result<()> {
+        #[derive(Debug, serde::Deserialize, schemars::JsonSchema, PartialEq)]
+        struct Answer {
+            answer: u32,
+        }
+
+        let body = r#"{"model":"llama3.1","created_at":"2024-09-01T00:00:00Z","message":{"role":"assistant","content":"{\"answer\": 42}"},"done":true,"done_reason":"stop","total_duration":5000,"load_duration":100,"prompt_eval_count":30,"prompt_eval_duration":900,"eval_count":6,"eval_duration":4000}"#;
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "application/json")],
+            body,
+        )])
+        .await;
+
+        let model = OllamaModel::builder().base_url(url).build();
+        let answer: Answer = model.prompt_structured(prompt! { user: "6 x 7?" }).await?;
+        assert_eq!(answer, Answer { answer: 42 });
+
+        let Format::JsonSchema(schema) = Format::from(OutputSchema::of::<Answer>()) else {
+            panic!("expected a JSON schema format");
+        };
+        assert_eq!(schema["required"], json!(["answer"]));
+
+        Ok(())
     }
 }
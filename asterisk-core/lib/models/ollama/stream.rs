@@ -1,26 +1,45 @@
 use std::{
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
-use futures::{ready, Stream};
-use pin_project::pin_project;
-use reqwest::RequestBuilder;
-use reqwest_eventsource::{Error, Event, EventSource, RequestBuilderExt};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use reqwest::Response;
+use serde::{de::DeserializeOwned, Deserialize};
 
-use crate::models::{ModelResult, ResponseStreamError};
+use crate::models::{ModelError, ModelResult, StreamEvent, TokenUsage, ToolCallDelta};
 
-use super::{OllamaModel, ResponseOk};
+use super::{ResponseError, ResponseOk};
 
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
-/// A stream of chunked responses from the OpenAI API using server-sent events.
-#[pin_project]
+/// A stream of chunked responses from the Ollama API, which streams newline-delimited JSON.
+///
+/// Each chunk carries a piece of the message. The last one has `done` set and carries the stats
+/// for the whole response, after which the stream ends. An `error` object sent midway is yielded
+/// as `ModelError::OllamaResponseError` and also ends the stream.
 pub struct ResponseStream {
-    #[pin]
-    stream: EventSource,
+    stream: BoxStream<'static, ModelResult<ResponseOk>>,
+}
+
+/// The state threaded through the decoder.
+struct DecoderState<S, T> {
+    bytes: S,
+    buffer: Vec<u8>,
+    idle_timeout: Option<Duration>,
+    is_last: fn(&T) -> bool,
+    finished: bool,
+}
+
+/// A line of a newline-delimited JSON body, which is either a chunk or an error.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Line<T> {
+    Ok(T),
+    Error(ResponseError),
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -28,53 +47,319 @@ pub struct ResponseStream {
 //--------------------------------------------------------------------------------------------------
 
 impl ResponseStream {
-    /// Creates a new `ResponseStream` from the given request.
-    pub fn new(request: RequestBuilder) -> Self {
-        let stream = request.eventsource().unwrap();
-        Self { stream }
+    /// Creates a new `ResponseStream` from a response, failing with `ModelError::Timeout` if no
+    /// data arrives for `idle_timeout`.
+    pub fn new(response: Response, idle_timeout: Option<Duration>) -> Self {
+        Self::from_bytes(response.bytes_stream(), idle_timeout)
     }
 
-    fn map_error(err: Error) -> ModelResult<String> {
-        let error = match err {
-            Error::InvalidStatusCode(_, response) => ResponseStreamError::APIResponse(response),
-            err => ResponseStreamError::EventSourceError(err),
-        };
+    /// Consumes the stream and folds the chunks into a whole response, as `OllamaModel::call` would
+    /// have returned it.
+    pub async fn collect_response(mut self) -> ModelResult<ResponseOk> {
+        let mut response: Option<ResponseOk> = None;
+        while let Some(chunk) = self.next().await {
+            let mut chunk = chunk?;
+            let Some(response) = response.as_mut() else {
+                response = Some(chunk);
+                continue;
+            };
+
+            if let Some(content) = chunk.message.content.take() {
+                response
+                    .message
+                    .content
+                    .get_or_insert_with(String::new)
+                    .push_str(&content);
+            }
+
+            if let Some(tool_calls) = chunk.message.tool_calls.take() {
+                response
+                    .message
+                    .tool_calls
+                    .get_or_insert_with(Vec::new)
+                    .extend(tool_calls);
+            }
+
+            response.created_at = chunk.created_at;
+            response.done = chunk.done;
+            response.done_reason = chunk.done_reason.or(response.done_reason.take());
+            response.context = chunk.context.or(response.context.take());
+            response.stats = chunk.stats.or(response.stats.take());
+        }
+
+        response.ok_or_else(|| ModelError::custom(anyhow::anyhow!("empty response stream")))
+    }
+
+    /// Converts the stream into provider-agnostic stream events.
+    ///
+    /// Ollama sends each tool call whole, so every tool call becomes a single delta. The final
+    /// chunk becomes the finish reason and the token usage.
+    pub fn into_events(self) -> BoxStream<'static, ModelResult<StreamEvent>> {
+        let stream = self.scan(0, |tool_calls, chunk| {
+            let events = chunk.map(|chunk| {
+                let mut events = Vec::new();
+                if let Some(content) = chunk.message.content.filter(|content| !content.is_empty()) {
+                    events.push(StreamEvent::ContentDelta(content));
+                }
+
+                for tool_call in chunk.message.tool_calls.unwrap_or_default() {
+                    events.push(StreamEvent::ToolCallDelta(ToolCallDelta {
+                        index: *tool_calls,
+                        id: Some(format!("call_{tool_calls}")),
+                        name: Some(tool_call.function.name),
+                        arguments: tool_call.function.arguments.to_string(),
+                    }));
+                    *tool_calls += 1;
+                }
+
+                if let Some(reason) = chunk.done_reason {
+                    events.push(StreamEvent::FinishReason(reason));
+                }
+
+                if let Some(stats) = chunk.stats {
+                    events.push(StreamEvent::Usage(TokenUsage::new(
+                        stats.prompt_eval_count,
+                        stats.eval_count,
+                    )));
+                }
 
-        Err(error.into())
+                events
+            });
+
+            futures::future::ready(Some(events))
+        });
+
+        crate::models::stream::flatten_events(stream)
+    }
+
+    /// Creates a new `ResponseStream` from a stream of raw body chunks.
+    pub(crate) fn from_bytes<S, B>(bytes: S, idle_timeout: Option<Duration>) -> Self
+    where
+        S: Stream<Item = reqwest::Result<B>> + Send + Unpin + 'static,
+        B: AsRef<[u8]>,
+    {
+        Self {
+            stream: decode_ndjson(bytes, idle_timeout, |chunk: &ResponseOk| chunk.done),
+        }
     }
 }
 
+impl<S, B, T> DecoderState<S, T>
+where
+    S: Stream<Item = reqwest::Result<B>> + Unpin,
+    B: AsRef<[u8]>,
+    T: DeserializeOwned,
+{
+    /// Decodes the next chunk, reading more of the body as needed.
+    async fn next_chunk(&mut self) -> Option<ModelResult<T>> {
+        loop {
+            if self.finished {
+                return None;
+            }
+
+            if let Some(line) = self.take_line() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                return Some(self.decode(&line));
+            }
+
+            let next = match self.idle_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, self.bytes.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        self.finished = true;
+                        return Some(Err(ModelError::Timeout(timeout)));
+                    }
+                },
+                None => self.bytes.next().await,
+            };
+
+            match next {
+                Some(Ok(bytes)) => self.buffer.extend_from_slice(bytes.as_ref()),
+                Some(Err(error)) => {
+                    self.finished = true;
+                    return Some(Err(error.into()));
+                }
+                None => {
+                    // The body ended, so whatever is left is the last line even without a newline.
+                    self.finished = true;
+                    let rest =
+                        String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+                    if rest.trim().is_empty() {
+                        return None;
+                    }
+
+                    return Some(self.decode(&rest));
+                }
+            }
+        }
+    }
+
+    /// Takes the next complete line out of the buffer.
+    fn take_line(&mut self) -> Option<String> {
+        let end = self.buffer.iter().position(|&byte| byte == b'\n')?;
+        let line: Vec<u8> = self.buffer.drain(..=end).collect();
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    /// Decodes a line into a chunk, ending the stream on the final chunk or an error.
+    fn decode(&mut self, line: &str) -> ModelResult<T> {
+        let line = serde_json::from_str::<Line<T>>(line).inspect_err(|_| {
+            self.finished = true;
+        })?;
+
+        match line {
+            Line::Ok(chunk) => {
+                self.finished = (self.is_last)(&chunk);
+                Ok(chunk)
+            }
+            Line::Error(error) => {
+                self.finished = true;
+                Err(ModelError::OllamaResponseError(error))
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Decodes a newline-delimited JSON body into a stream of chunks.
+///
+/// The stream ends after the chunk for which `is_last` returns true, or after an `error` object,
+/// which is yielded as `ModelError::OllamaResponseError`. It fails with `ModelError::Timeout` if no
+/// data arrives for `idle_timeout`.
+pub(crate) fn decode_ndjson<S, B, T>(
+    bytes: S,
+    idle_timeout: Option<Duration>,
+    is_last: fn(&T) -> bool,
+) -> BoxStream<'static, ModelResult<T>>
+where
+    S: Stream<Item = reqwest::Result<B>> + Send + Unpin + 'static,
+    B: AsRef<[u8]>,
+    T: DeserializeOwned + Send + 'static,
+{
+    let state = DecoderState {
+        bytes,
+        buffer: Vec::new(),
+        idle_timeout,
+        is_last,
+        finished: false,
+    };
+
+    Box::pin(futures::stream::unfold(state, |mut state| async move {
+        let item = state.next_chunk().await?;
+        Some((item, state))
+    }))
+}
+
 //--------------------------------------------------------------------------------------------------
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
 
 impl Stream for ResponseStream {
-    type Item = ModelResult<String>;
-
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.project();
-        let event = ready!(this.stream.poll_next(cx));
-
-        match event {
-            Some(Ok(event)) => match event {
-                Event::Message(message) => {
-                    let data = message.data;
-                    if data == "[DONE]" {
-                        return Poll::Ready(None);
-                    }
+    type Item = ModelResult<ResponseOk>;
 
-                    let body: ResponseOk = serde_json::from_str(&data)?;
-                    let content = OllamaModel::extract_content_from_response_chunk(&body);
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.stream.poll_next_unpin(cx)
+    }
+}
 
-                    Poll::Ready(Some(Ok(content)))
-                }
-                Event::Open => {
-                    cx.waker().wake_by_ref();
-                    Poll::Pending
-                }
-            },
-            Some(Err(err)) => Poll::Ready(Some(Self::map_error(err))),
-            None => Poll::Ready(None),
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(content: &str) -> String {
+        format!(
+            r#"{{"model":"llama3.1","created_at":"2024-09-01T00:00:00Z","message":{{"role":"assistant","content":"{content}"}},"done":false}}"#
+        )
+    }
+
+    const DONE: &str = r#"{"model":"llama3.1","created_at":"2024-09-01T00:00:00Z","message":{"role":"assistant","content":""},"done":true,"done_reason":"stop","total_duration":5000,"load_duration":100,"prompt_eval_count":12,"prompt_eval_duration":900,"eval_count":3,"eval_duration":4000}"#;
+
+    fn stream_of(body: &str, split_at: &[usize]) -> ResponseStream {
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        for &end in split_at {
+            pieces.push(Ok(body.as_bytes()[start..end].to_vec()));
+            start = end;
         }
+        pieces.push(Ok(body.as_bytes()[start..].to_vec()));
+
+        ResponseStream::from_bytes(futures::stream::iter(pieces), None)
+    }
+
+    #[tokio::test]
+    async fn test_ollama_stream_decodes_ndjson() {
+        let body = format!("{}\n{}\n{DONE}\n", chunk("Hel"), chunk("lo"));
+        // Split lines across body chunks to exercise the buffering.
+        let chunks: Vec<ResponseOk> = stream_of(&body, &[10, 150, 160])
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        let content: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk.message.content.clone())
+            .collect();
+        assert_eq!(content, "Hello");
+
+        let last = chunks.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.done_reason.as_deref(), Some("stop"));
+        let stats = last.stats.as_ref().unwrap();
+        assert_eq!(stats.prompt_eval_count, 12);
+        assert_eq!(stats.eval_count, 3);
+        assert_eq!(stats.eval_duration, 4000);
+        assert!(chunks[0].stats.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ollama_stream_events_and_collect() {
+        let body = format!("{}\n{}\n{DONE}\n", chunk("Hel"), chunk("lo"));
+
+        let events: Vec<StreamEvent> = stream_of(&body, &[])
+            .into_events()
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::ContentDelta("Hel".to_string()),
+                StreamEvent::ContentDelta("lo".to_string()),
+                StreamEvent::FinishReason("stop".to_string()),
+                StreamEvent::Usage(TokenUsage::new(12, 3)),
+            ]
+        );
+
+        let response = stream_of(&body, &[]).collect_response().await.unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("Hello"));
+        assert!(response.done);
+        assert_eq!(response.stats.unwrap().eval_count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_ollama_stream_surfaces_errors() {
+        let body = format!(
+            "{}\n{{\"error\":\"model ran out of memory\"}}\n{}\n",
+            chunk("Hel"),
+            chunk("lo")
+        );
+        let items: Vec<ModelResult<ResponseOk>> = stream_of(&body, &[]).collect().await;
+
+        assert_eq!(items.len(), 2);
+        assert!(items[0].is_ok());
+        let Err(ModelError::OllamaResponseError(error)) = &items[1] else {
+            panic!("expected an Ollama error, got {:?}", items[1]);
+        };
+        assert_eq!(error.error, "model ran out of memory");
     }
 }
@@ -1,5 +1,7 @@
 use std::{borrow::Cow, collections::HashMap, env};
 
+use crate::models::{http, retry::RetryPolicy};
+
 use super::{
     Config, ModelType, OpenAILikeModel, OpenAIModel, ResponseFormat, ServiceTier, StreamOptions,
     Tool, ToolChoice, OPENAI_API_KEY, OPENAI_API_URL,
@@ -18,7 +20,7 @@ pub struct ModelBuilder<U, M> {
     logit_bias: Option<HashMap<u64, i8>>,
     logprobs: Option<u8>,
     top_logprobs: Option<u8>,
-    max_tokens: Option<u16>,
+    max_tokens: Option<u32>,
     n: Option<u8>,
     presence_penalty: Option<f32>,
     response_format: Option<ResponseFormat>,
@@ -33,6 +35,8 @@ pub struct ModelBuilder<U, M> {
     tool_choice: Option<ToolChoice>,
     parallel_tool_calls: Option<bool>,
     user: Option<String>,
+    retry: RetryPolicy,
+    client: Option<reqwest::Client>,
 }
 
 /// A builder for an OpenAI model.
@@ -73,6 +77,8 @@ impl<U, M> ModelBuilder<U, M> {
             tool_choice: self.tool_choice,
             parallel_tool_calls: self.parallel_tool_calls,
             user: self.user,
+            retry: self.retry,
+            client: self.client,
         }
     }
 
@@ -101,6 +107,8 @@ impl<U, M> ModelBuilder<U, M> {
             tool_choice: self.tool_choice,
             parallel_tool_calls: self.parallel_tool_calls,
             user: self.user,
+            retry: self.retry,
+            client: self.client,
         }
     }
 
@@ -141,7 +149,7 @@ impl<U, M> ModelBuilder<U, M> {
     }
 
     /// The maximum number of tokens that can be generated in the chat completion.
-    pub fn max_tokens(mut self, max_tokens: u16) -> Self {
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
         self.max_tokens = Some(max_tokens);
         self
     }
@@ -241,6 +249,23 @@ impl<U, M> ModelBuilder<U, M> {
         self.user = Some(user);
         self
     }
+
+    /// The policy for retrying failed requests and timing them out.
+    ///
+    /// Defaults to `RetryPolicy::default()`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The HTTP client to send requests with, for example one built from an
+    /// `http::HttpClientConfig` with a proxy or extra CA roots.
+    ///
+    /// Defaults to a pooled client shared by all models.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
 }
 
 impl OpenAIModelBuilder<()> {
@@ -273,6 +298,8 @@ impl OpenAIModelBuilder<()> {
         OpenAIModel {
             config: Cow::Owned(config),
             base_url: OPENAI_API_URL.to_string(),
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
         }
     }
 }
@@ -307,6 +334,8 @@ impl OpenAIModelBuilder<String> {
         OpenAIModel {
             config: Cow::Owned(config),
             base_url: OPENAI_API_URL.to_string(),
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
         }
     }
 }
@@ -341,6 +370,8 @@ impl OpenAILikeModelBuilder<String> {
         OpenAILikeModel(OpenAIModel {
             config: Cow::Owned(config),
             base_url: self.base_url,
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
         })
     }
 }
@@ -374,6 +405,8 @@ impl Default for ModelBuilder<(), ()> {
             tool_choice: None,
             parallel_tool_calls: None,
             user: None,
+            retry: RetryPolicy::default(),
+            client: None,
         }
     }
 }
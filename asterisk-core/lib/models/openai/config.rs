@@ -1,8 +1,11 @@
 use std::{collections::HashMap, env};
 
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use strum_macros::Display;
 
+use crate::models::OutputSchema;
+
 //--------------------------------------------------------------------------------------------------
 // Constants
 //--------------------------------------------------------------------------------------------------
@@ -51,7 +54,7 @@ pub struct Config {
 
     /// The maximum number of tokens that can be generated in the chat completion.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub max_tokens: Option<u16>,
+    pub max_tokens: Option<u32>,
 
     /// The number of chat completion choices to generate for each input message.``
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -298,6 +301,20 @@ impl Default for Config {
     }
 }
 
+impl From<OutputSchema> for ResponseFormat {
+    fn from(value: OutputSchema) -> Self {
+        // Strict mode needs every property required and no additional ones, which derived
+        // schemas don't guarantee, so the reply is validated afterwards instead.
+        ResponseFormat::JsonSchema {
+            json_schema: json!({
+                "name": value.name,
+                "schema": value.schema,
+                "strict": false,
+            }),
+        }
+    }
+}
+
 impl From<ModelType> for String {
     fn from(value: ModelType) -> Self {
         value.to_string()
@@ -0,0 +1,365 @@
+use std::env;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::models::{
+    http,
+    retry::{self, RetryPolicy},
+    EmbeddingModel, EmbeddingResponse, ModelError, ModelResult, TokenUsage,
+};
+
+use super::{ResponseError, OPENAI_API_KEY};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The URL for the OpenAI embeddings API.
+pub const OPENAI_EMBEDDINGS_API_URL: &str = "https://api.openai.com/v1/embeddings";
+
+/// The embedding model used when none is given.
+pub const DEFAULT_OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// The maximum number of texts the OpenAI embeddings API accepts in a single request.
+pub const OPENAI_EMBEDDING_BATCH_SIZE: usize = 2048;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `OpenAIEmbeddingModel` embeds text with the OpenAI embeddings API or any API compatible with it.
+#[derive(Debug, Clone)]
+pub struct OpenAIEmbeddingModel {
+    pub(crate) model: String,
+    pub(crate) base_url: String,
+    pub(crate) api_key: Option<String>,
+    pub(crate) dimensions: Option<usize>,
+    pub(crate) user: Option<String>,
+    pub(crate) batch_size: usize,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) client: reqwest::Client,
+}
+
+/// A builder for an OpenAI embedding model.
+#[derive(Debug, Clone)]
+pub struct EmbeddingModelBuilder {
+    model: Option<String>,
+    base_url: Option<String>,
+    api_key: Option<String>,
+    dimensions: Option<usize>,
+    user: Option<String>,
+    batch_size: usize,
+    retry: RetryPolicy,
+    client: Option<reqwest::Client>,
+}
+
+/// The request body for the embeddings API.
+#[derive(Debug, Serialize)]
+pub struct EmbeddingRequestBody {
+    /// The ID of the model to use.
+    pub model: String,
+
+    /// The texts to embed.
+    pub input: Vec<String>,
+
+    /// The format to return the embeddings in.
+    pub encoding_format: &'static str,
+
+    /// The number of dimensions the embeddings should have.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<usize>,
+
+    /// A unique identifier representing your end-user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+/// Embeddings response body.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingResponseBody {
+    /// A successful response.
+    Ok(EmbeddingResponseOk),
+
+    /// An error response.
+    Error(Box<ResponseError>),
+}
+
+/// A successful embeddings response.
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingResponseOk {
+    /// The embeddings, one per input text.
+    pub data: Vec<EmbeddingData>,
+
+    /// The model used to create the embeddings.
+    pub model: String,
+
+    /// Usage statistics for the request.
+    pub usage: Option<EmbeddingUsage>,
+}
+
+/// The embedding of one input text.
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingData {
+    /// The position of the text in the input.
+    pub index: usize,
+
+    /// The embedding vector.
+    pub embedding: Vec<f32>,
+}
+
+/// Usage statistics for an embeddings request.
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingUsage {
+    /// Number of tokens in the input.
+    pub prompt_tokens: u64,
+
+    /// Total number of tokens used in the request.
+    pub total_tokens: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl OpenAIEmbeddingModel {
+    /// Creates a builder for the model.
+    pub fn builder() -> EmbeddingModelBuilder {
+        EmbeddingModelBuilder::default()
+    }
+
+    /// Calls the API with the given texts.
+    pub async fn call(&self, texts: Vec<String>) -> ModelResult<EmbeddingResponseOk> {
+        let request = self
+            .client
+            .post(&self.base_url)
+            .bearer_auth(self.api_key.as_ref().ok_or(ModelError::NoAPIKeyFound)?)
+            .json(&EmbeddingRequestBody {
+                model: self.model.clone(),
+                input: texts,
+                encoding_format: "float",
+                dimensions: self.dimensions,
+                user: self.user.clone(),
+            });
+
+        let response = self
+            .retry
+            .send(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
+        let body = response.text().await?;
+        debug!("body length = {}", body.len());
+
+        match serde_json::from_str(&body)? {
+            EmbeddingResponseBody::Ok(body) => Ok(body),
+            EmbeddingResponseBody::Error(error) => Err(ModelError::OpenAIResponseError(*error)),
+        }
+    }
+}
+
+impl EmbeddingResponseOk {
+    /// Orders the embeddings by input position, checking there is one for each of `count` texts.
+    fn into_embedding_response(mut self, count: usize) -> ModelResult<EmbeddingResponse> {
+        if self.data.len() != count {
+            return Err(ModelError::EmptyEmbeddingResponse);
+        }
+
+        self.data.sort_by_key(|data| data.index);
+        Ok(EmbeddingResponse {
+            embeddings: self.data.into_iter().map(|data| data.embedding).collect(),
+            model: self.model,
+            usage: self
+                .usage
+                .map(|usage| TokenUsage::new(usage.prompt_tokens, 0)),
+        })
+    }
+}
+
+impl EmbeddingModelBuilder {
+    /// The ID of the model to use.
+    ///
+    /// Defaults to `DEFAULT_OPENAI_EMBEDDING_MODEL`.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// The URL of the embeddings endpoint, for APIs compatible with OpenAI's.
+    ///
+    /// Defaults to `OPENAI_EMBEDDINGS_API_URL`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// The API key to use.
+    ///
+    /// Defaults to the value of the `OPENAI_API_KEY` environment variable if set.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// The number of dimensions the embeddings should have. Only supported by
+    /// `text-embedding-3` and later models.
+    pub fn dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// A unique identifier representing your end-user.
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// The maximum number of texts embedded in a single request.
+    ///
+    /// Defaults to `OPENAI_EMBEDDING_BATCH_SIZE`.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// The policy for retrying failed requests and timing them out.
+    ///
+    /// Defaults to `RetryPolicy::default()`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The HTTP client to send requests with.
+    ///
+    /// Defaults to a pooled client shared by all models.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Builds the embedding model.
+    pub fn build(self) -> OpenAIEmbeddingModel {
+        OpenAIEmbeddingModel {
+            model: self
+                .model
+                .unwrap_or(DEFAULT_OPENAI_EMBEDDING_MODEL.to_string()),
+            base_url: self
+                .base_url
+                .unwrap_or(OPENAI_EMBEDDINGS_API_URL.to_string()),
+            api_key: self.api_key,
+            dimensions: self.dimensions,
+            user: self.user,
+            batch_size: self.batch_size,
+            retry: self.retry,
+            client: self.client.unwrap_or_else(http::default_client),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the default number of dimensions of the known OpenAI embedding models.
+fn known_dimensions(model: &str) -> Option<usize> {
+    match model {
+        "text-embedding-3-small" | "text-embedding-ada-002" => Some(1536),
+        "text-embedding-3-large" => Some(3072),
+        _ => None,
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl EmbeddingModel for OpenAIEmbeddingModel {
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.dimensions.or_else(|| known_dimensions(&self.model))
+    }
+
+    fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    fn embed_batch(&self, texts: Vec<String>) -> BoxFuture<'_, ModelResult<EmbeddingResponse>> {
+        Box::pin(async move {
+            let count = texts.len();
+            let response = self.call(texts).await?;
+            response.into_embedding_response(count)
+        })
+    }
+}
+
+impl Default for EmbeddingModelBuilder {
+    fn default() -> Self {
+        Self {
+            model: None,
+            base_url: None,
+            api_key: env::var(OPENAI_API_KEY).ok(),
+            dimensions: None,
+            user: None,
+            batch_size: OPENAI_EMBEDDING_BATCH_SIZE,
+            retry: RetryPolicy::default(),
+            client: None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_model_openai_embedding() -> anyhow::Result<()> {
+        let model = OpenAIEmbeddingModel::builder()
+            .api_key("test")
+            .batch_size(16)
+            .build();
+        assert_eq!(model.model_id(), DEFAULT_OPENAI_EMBEDDING_MODEL);
+        assert_eq!(model.dimensions(), Some(1536));
+        assert_eq!(model.batch_size(), 16);
+
+        let model = OpenAIEmbeddingModel::builder()
+            .model("text-embedding-3-large")
+            .dimensions(256)
+            .build();
+        assert_eq!(model.dimensions(), Some(256));
+
+        let body: EmbeddingResponseBody = serde_json::from_value(json!({
+            "object": "list",
+            "data": [
+                { "object": "embedding", "index": 1, "embedding": [0.3, 0.4] },
+                { "object": "embedding", "index": 0, "embedding": [0.1, 0.2] }
+            ],
+            "model": "text-embedding-3-small",
+            "usage": { "prompt_tokens": 8, "total_tokens": 8 }
+        }))?;
+        let EmbeddingResponseBody::Ok(body) = body else {
+            panic!("expected a successful response");
+        };
+
+        let response = body.into_embedding_response(2)?;
+        assert_eq!(response.embeddings, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+        assert_eq!(response.usage.unwrap().total_tokens, 8);
+
+        let body: EmbeddingResponseBody = serde_json::from_value(json!({
+            "error": { "message": "Invalid model", "type": "invalid_request_error" }
+        }))?;
+        assert!(matches!(body, EmbeddingResponseBody::Error(_)));
+
+        Ok(())
+    }
+}
@@ -1,9 +1,13 @@
 use std::fmt::{self, Display};
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::models::{AssistantMessage, Prompt, PromptMessage, SystemMessage, UserMessage};
+use crate::models::{
+    self, AssistantMessage, ContentPart, ImageContent, ImageDetail, MessageContent, ModelResult,
+    Prompt, PromptMessage, StreamEvent, SystemMessage, TokenUsage, ToolCallDelta, ToolMessage,
+    UserMessage,
+};
 
 use super::{Config, ToolType};
 
@@ -22,12 +26,12 @@ pub struct RequestBody {
     pub config: Config,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 /// A collection of messages in a chat conversation with the model.
 pub struct RequestMessages(pub Vec<RequestMessage>);
 
 /// A message in a chat conversation with the model.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(tag = "role")]
 pub enum RequestMessage {
     /// A message that sets the context for the conversation.
@@ -49,7 +53,7 @@ pub enum RequestMessage {
         name: Option<String>,
 
         /// The content of the message.
-        content: String,
+        content: RequestContent,
     },
 
     /// A message that assumes the role of the assistant.
@@ -60,7 +64,9 @@ pub enum RequestMessage {
         name: Option<String>,
 
         /// The content of the message.
-        content: String,
+        ///
+        /// Can be `None` when the assistant only makes tool calls.
+        content: Option<String>,
 
         /// Refusal message
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -83,28 +89,71 @@ pub enum RequestMessage {
     },
 }
 
+/// The content of a user message.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum RequestContent {
+    /// Plain text content.
+    Text(String),
+
+    /// Content made up of multiple parts.
+    Parts(Vec<RequestContentPart>),
+}
+
+/// A part of a multi-part user message.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum RequestContentPart {
+    /// A text part.
+    #[serde(rename = "text")]
+    Text {
+        /// The text content.
+        text: String,
+    },
+
+    /// An image part.
+    #[serde(rename = "image_url")]
+    ImageUrl {
+        /// The image to send to the model.
+        image_url: ImageUrl,
+    },
+}
+
+/// An image sent to the model.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageUrl {
+    /// Either a URL of the image or the base64 encoded image data.
+    pub url: String,
+
+    /// Specifies the detail level of the image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
 /// A tool call made by the assistant.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ToolCall {
     /// The id of the tool call.
-    #[serde(rename = "tool_call_id")]
-    id: String,
+    pub id: String,
 
     /// The type of the tool.
-    r#type: ToolType,
+    pub r#type: ToolType,
 
     /// The function call made by the tool.
-    function: FunctionCall,
+    pub function: FunctionCall,
 }
 
 /// A function call made by the tool.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FunctionCall {
     /// The name of the function.
-    name: String,
+    pub name: String,
 
-    /// The arguments of the function.
-    arguments: Vec<serde_json::Value>,
+    /// The arguments to call the function with, as a JSON string generated by the model.
+    ///
+    /// The model does not always generate valid JSON and may hallucinate parameters not defined
+    /// by the function schema, so the arguments should be validated before use.
+    pub arguments: String,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -335,8 +384,8 @@ pub struct ChoiceDelta {
     /// Refusal message
     pub refusal: Option<String>,
 
-    /// The tool calls made by the assistant.
-    pub tool_calls: Option<Vec<ToolCall>>,
+    /// The pieces of the tool calls made by the assistant.
+    pub tool_calls: Option<Vec<ToolCallChunk>>,
 
     /// The role of the message.
     ///
@@ -345,8 +394,37 @@ pub struct ChoiceDelta {
     pub role: Option<String>,
 }
 
+/// A piece of a tool call made by the assistant, generated by streamed model responses.
+///
+/// Only the first piece of a tool call carries its id, type and function name. The arguments are
+/// spread across the pieces with the same `index`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolCallChunk {
+    /// The position of the tool call among the tool calls of the message.
+    pub index: usize,
+
+    /// The ID of the tool call.
+    pub id: Option<String>,
+
+    /// The type of the tool.
+    pub r#type: Option<ToolType>,
+
+    /// The piece of the function call.
+    pub function: Option<FunctionCallChunk>,
+}
+
+/// A piece of a function call made by the assistant.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FunctionCallChunk {
+    /// The name of the function to call.
+    pub name: Option<String>,
+
+    /// A piece of the JSON arguments to call the function with.
+    pub arguments: Option<String>,
+}
+
 /// A chat completion message generated by the model.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ChoiceMessage {
     /// The content of the message.
     pub content: Option<String>,
@@ -358,8 +436,6 @@ pub struct ChoiceMessage {
     pub tool_calls: Option<Vec<ToolCall>>,
 
     /// The role of the message.
-    ///
-    ///
     pub role: Option<String>,
 }
 
@@ -424,6 +500,92 @@ pub struct TopLogprobs {
 // Methods
 //--------------------------------------------------------------------------------------------------
 
+impl RequestMessages {
+    /// Appends a message to the conversation.
+    pub fn push(&mut self, message: RequestMessage) {
+        self.0.push(message);
+    }
+}
+
+impl RequestMessage {
+    /// Creates a message carrying the result of the tool call with the given id.
+    pub fn tool(id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self::Tool {
+            id: id.into(),
+            content: content.into(),
+        }
+    }
+}
+
+impl FunctionCall {
+    /// Deserializes the arguments of the function call into the given type.
+    pub fn parse_arguments<T: DeserializeOwned>(&self) -> ModelResult<T> {
+        Ok(serde_json::from_str(&self.arguments)?)
+    }
+}
+
+impl ResponseOk {
+    /// Returns the message of the first choice.
+    pub fn message(&self) -> Option<&ChoiceMessage> {
+        self.choices.first().map(|choice| &choice.message)
+    }
+
+    /// Returns the content of the first choice.
+    pub fn content(&self) -> Option<&str> {
+        self.message()?.content.as_deref()
+    }
+
+    /// Returns the tool calls made in the first choice.
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        self.message()
+            .and_then(|message| message.tool_calls.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+impl ResponseChunkOk {
+    /// Converts the chunk into stream events for the first choice, followed by the usage if the
+    /// chunk carries it.
+    ///
+    /// The usage is sent in a final chunk with no choices when `StreamOptions::include_usage` is
+    /// set.
+    pub fn into_events(self) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        for choice in self.choices.into_iter().filter(|choice| choice.index == 0) {
+            let delta = choice.delta;
+            if let Some(content) = delta.content.filter(|content| !content.is_empty()) {
+                events.push(StreamEvent::ContentDelta(content));
+            }
+
+            if let Some(refusal) = delta.refusal.filter(|refusal| !refusal.is_empty()) {
+                events.push(StreamEvent::RefusalDelta(refusal));
+            }
+
+            for tool_call in delta.tool_calls.unwrap_or_default() {
+                let function = tool_call.function;
+                events.push(StreamEvent::ToolCallDelta(ToolCallDelta {
+                    index: tool_call.index,
+                    id: tool_call.id,
+                    name: function.as_ref().and_then(|function| function.name.clone()),
+                    arguments: function
+                        .and_then(|function| function.arguments)
+                        .unwrap_or_default(),
+                }));
+            }
+
+            if let Some(reason) = choice.finish_reason {
+                events.push(StreamEvent::FinishReason(reason));
+            }
+        }
+
+        if let Some(usage) = &self.usage {
+            events.push(StreamEvent::Usage(usage.into()));
+        }
+
+        events
+    }
+}
+
 impl ResponseBody {
     /// Gets the error variant or panics.
     pub fn unwrap_err(self) -> ResponseError {
@@ -446,28 +608,284 @@ impl Display for ErrorInfo {
 
 impl From<Prompt> for RequestMessages {
     fn from(prompt: Prompt) -> Self {
-        let request_messages = prompt
-            .into_iter()
-            .map(|m| match m {
-                PromptMessage::System(SystemMessage { content }) => RequestMessage::System {
-                    content,
-                    name: None,
-                },
-                PromptMessage::User(UserMessage { content }) => RequestMessage::User {
-                    content,
-                    name: None,
+        Self(prompt.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<PromptMessage> for RequestMessage {
+    fn from(message: PromptMessage) -> Self {
+        match message {
+            PromptMessage::System(SystemMessage { content, name }) => {
+                RequestMessage::System { content, name }
+            }
+            PromptMessage::User(UserMessage { content, name }) => RequestMessage::User {
+                content: content.into(),
+                name,
+            },
+            PromptMessage::Assistant(AssistantMessage {
+                content,
+                name,
+                refusal,
+                tool_calls,
+            }) => RequestMessage::Assistant {
+                content,
+                name,
+                refusal,
+                tool_calls: (!tool_calls.is_empty())
+                    .then(|| tool_calls.into_iter().map(Into::into).collect()),
+            },
+            PromptMessage::Tool(ToolMessage {
+                tool_call_id,
+                content,
+                ..
+            }) => RequestMessage::Tool {
+                id: tool_call_id,
+                content,
+            },
+        }
+    }
+}
+
+impl From<MessageContent> for RequestContent {
+    fn from(content: MessageContent) -> Self {
+        match content {
+            MessageContent::Text(text) => RequestContent::Text(text),
+            MessageContent::Parts(parts) => {
+                RequestContent::Parts(parts.into_iter().map(Into::into).collect())
+            }
+        }
+    }
+}
+
+impl From<ContentPart> for RequestContentPart {
+    fn from(part: ContentPart) -> Self {
+        match part {
+            ContentPart::Text(text) => RequestContentPart::Text { text },
+            ContentPart::Image(ImageContent { url, detail }) => RequestContentPart::ImageUrl {
+                image_url: ImageUrl {
+                    url,
+                    detail: detail.map(|detail| {
+                        match detail {
+                            ImageDetail::Auto => "auto",
+                            ImageDetail::Low => "low",
+                            ImageDetail::High => "high",
+                        }
+                        .to_string()
+                    }),
                 },
-                PromptMessage::Assistant(AssistantMessage { content }) => {
-                    RequestMessage::Assistant {
-                        content,
-                        name: None,
-                        refusal: None,
-                        tool_calls: None,
-                    }
+            },
+        }
+    }
+}
+
+impl From<models::ToolCall> for ToolCall {
+    fn from(tool_call: models::ToolCall) -> Self {
+        ToolCall {
+            id: tool_call.id,
+            r#type: ToolType::Function,
+            function: FunctionCall {
+                name: tool_call.name,
+                arguments: tool_call.arguments,
+            },
+        }
+    }
+}
+
+impl From<ToolCall> for models::ToolCall {
+    fn from(tool_call: ToolCall) -> Self {
+        models::ToolCall {
+            id: tool_call.id,
+            name: tool_call.function.name,
+            arguments: tool_call.function.arguments,
+        }
+    }
+}
+
+impl From<&Usage> for TokenUsage {
+    fn from(usage: &Usage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+impl From<ChoiceMessage> for RequestMessage {
+    fn from(message: ChoiceMessage) -> Self {
+        RequestMessage::Assistant {
+            content: message.content,
+            name: None,
+            refusal: message.refusal,
+            tool_calls: message.tool_calls,
+        }
+    }
+}
+
+impl From<ChoiceMessage> for PromptMessage {
+    fn from(message: ChoiceMessage) -> Self {
+        PromptMessage::Assistant(AssistantMessage {
+            content: message.content,
+            name: None,
+            refusal: message.refusal,
+            tool_calls: message
+                .tool_calls
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_model_openai_response_tool_calls() -> anyhow::Result<()> {
+        let body = json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "created": 1726000000,
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "finish_reason": "tool_calls",
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_abc",
+                        "type": "function",
+                        "function": {
+                            "name": "get_weather",
+                            "arguments": "{\"city\":\"Tokyo\"}"
+                        }
+                    }]
                 }
-            })
-            .collect();
+            }]
+        });
+
+        let response: ResponseOk = serde_json::from_value(body)?;
+        let tool_calls = response.tool_calls();
+
+        assert_eq!(response.content(), None);
+        assert_eq!(tool_calls.len(), 1);
+        assert_eq!(tool_calls[0].id, "call_abc");
+        assert_eq!(tool_calls[0].function.name, "get_weather");
+
+        let args: serde_json::Value = tool_calls[0].function.parse_arguments()?;
+        assert_eq!(args, json!({ "city": "Tokyo" }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_openai_request_tool_messages() -> anyhow::Result<()> {
+        let tool_call = ToolCall {
+            id: "call_abc".to_string(),
+            r#type: ToolType::Function,
+            function: FunctionCall {
+                name: "get_weather".to_string(),
+                arguments: r#"{"city":"Tokyo"}"#.to_string(),
+            },
+        };
+
+        let messages = RequestMessages(vec![
+            RequestMessage::Assistant {
+                content: None,
+                name: None,
+                refusal: None,
+                tool_calls: Some(vec![tool_call]),
+            },
+            RequestMessage::tool("call_abc", "sunny"),
+        ]);
+
+        assert_eq!(
+            serde_json::to_value(&messages)?,
+            json!([
+                {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_abc",
+                        "type": "function",
+                        "function": {
+                            "name": "get_weather",
+                            "arguments": "{\"city\":\"Tokyo\"}"
+                        }
+                    }]
+                },
+                { "role": "tool", "tool_call_id": "call_abc", "content": "sunny" }
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_openai_request_messages_from_prompt() -> anyhow::Result<()> {
+        let mut prompt = Prompt::new();
+        prompt.push(PromptMessage::System(
+            SystemMessage::new("You are a helpful assistant.").with_name("rules"),
+        ));
+        prompt.push(PromptMessage::User(
+            UserMessage::new(vec![
+                ContentPart::text("What is in this image?"),
+                ContentPart::Image(ImageContent {
+                    url: "https://example.com/cat.png".to_string(),
+                    detail: Some(ImageDetail::Low),
+                }),
+            ])
+            .with_name("alice"),
+        ));
+        prompt.push(PromptMessage::Assistant(AssistantMessage::with_tool_calls(
+            [models::ToolCall::new("call_abc", "describe", "{}")],
+        )));
+        prompt.push(PromptMessage::tool("call_abc", "a cat"));
+        prompt.push(PromptMessage::Assistant(AssistantMessage {
+            content: None,
+            name: None,
+            refusal: Some("I can't help with that.".to_string()),
+            tool_calls: vec![],
+        }));
+
+        assert_eq!(
+            serde_json::to_value(RequestMessages::from(prompt))?,
+            json!([
+                { "role": "system", "name": "rules", "content": "You are a helpful assistant." },
+                {
+                    "role": "user",
+                    "name": "alice",
+                    "content": [
+                        { "type": "text", "text": "What is in this image?" },
+                        {
+                            "type": "image_url",
+                            "image_url": { "url": "https://example.com/cat.png", "detail": "low" }
+                        }
+                    ]
+                },
+                {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_abc",
+                        "type": "function",
+                        "function": { "name": "describe", "arguments": "{}" }
+                    }]
+                },
+                { "role": "tool", "tool_call_id": "call_abc", "content": "a cat" },
+                { "role": "assistant", "content": null, "refusal": "I can't help with that." }
+            ])
+        );
 
-        Self(request_messages)
+        Ok(())
     }
 }
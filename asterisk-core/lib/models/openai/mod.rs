@@ -2,6 +2,7 @@
 
 mod builder;
 mod config;
+mod embedding;
 mod message;
 mod model;
 mod stream;
@@ -12,6 +13,7 @@ mod stream;
 
 pub use builder::*;
 pub use config::*;
+pub use embedding::*;
 pub use message::*;
 pub use model::*;
 pub use stream::*;
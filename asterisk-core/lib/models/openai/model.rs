@@ -1,18 +1,29 @@
-use std::{borrow::Cow, ops::Deref};
+use std::{borrow::Cow, future::Future, ops::Deref};
 
-use futures::stream::BoxStream;
+use futures::{future::BoxFuture, stream::BoxStream, StreamExt};
 use tracing::debug;
 
 use crate::models::{
+    http,
     openai::{StreamOptions, OPENAI_API_URL},
-    ModelError, ModelResult, Prompt, TextModel, TextStreamModel,
+    retry::{self, RetryPolicy},
+    stream, ChatModel, ModelError, ModelResponse, ModelResult, OutputSchema, Prompt, StreamEvent,
+    TextModel, TextStreamModel, TokenUsage,
 };
 
 use super::{
-    Config, ModelBuilder, RequestBody, RequestMessages, ResponseBody, ResponseChunkOk, ResponseOk,
-    ResponseStream,
+    Config, ModelBuilder, RequestBody, RequestMessage, RequestMessages, ResponseBody,
+    ResponseChunkOk, ResponseError, ResponseOk, ResponseStream, ToolCall,
 };
 
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of requests `OpenAIModel::call_with_tools` makes before giving up on the
+/// model producing a final response.
+pub const MAX_TOOL_CALL_ROUNDS: usize = 10;
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -22,6 +33,8 @@ use super::{
 pub struct OpenAIModel {
     pub(crate) config: Cow<'static, Config>,
     pub(crate) base_url: String,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) client: reqwest::Client,
 }
 
 /// `OpenAILikeModel` is a type that can prompt and stream responses from models that are compatible
@@ -47,16 +60,20 @@ impl OpenAIModel {
         let messages = messages.into();
         debug!("messages = {}", serde_json::to_string(&messages).unwrap());
 
-        let request = reqwest::Client::new()
+        let request = self
+            .client
             .post(&self.base_url)
-            .timeout(std::time::Duration::from_secs(60))
             .bearer_auth(config.api_key.as_ref().ok_or(ModelError::NoAPIKeyFound)?)
             .json(&RequestBody {
                 messages,
                 config: config.into_owned(),
             });
 
-        let response = request.send().await?;
+        let response = self
+            .retry
+            .send(request)
+            .await
+            .map_err(retry::parse_error_body::<ResponseError>)?;
 
         let body = response.text().await?;
         debug!("body = {body:#?}");
@@ -69,18 +86,78 @@ impl OpenAIModel {
         Ok(*body)
     }
 
+    /// Calls the API with the given request messages, runs the tool calls the model makes through
+    /// `handler` and feeds the results back until the model produces a final response.
+    ///
+    /// The assistant's messages, including the final one, and the tool results are appended to
+    /// `messages`, so the caller is left with the full conversation once the response is returned.
+    pub async fn call_with_tools<F, Fut>(
+        &self,
+        messages: &mut RequestMessages,
+        mut handler: F,
+    ) -> ModelResult<ResponseOk>
+    where
+        F: FnMut(ToolCall) -> Fut,
+        Fut: Future<Output = ModelResult<String>>,
+    {
+        for _ in 0..MAX_TOOL_CALL_ROUNDS {
+            let response = self.call(messages.clone()).await?;
+            let Some(message) = response.message().cloned() else {
+                return Ok(response);
+            };
+
+            let tool_calls = message.tool_calls.clone().unwrap_or_default();
+            messages.push(RequestMessage::from(message));
+            if tool_calls.is_empty() {
+                return Ok(response);
+            }
+
+            for tool_call in tool_calls {
+                let id = tool_call.id.clone();
+                let content = handler(tool_call).await?;
+                messages.push(RequestMessage::tool(id, content));
+            }
+        }
+
+        Err(ModelError::ToolCallRoundsExceeded(MAX_TOOL_CALL_ROUNDS))
+    }
+
     /// Calls the API with the given request messages and gets back a stream of response chunks.
+    ///
+    /// The last chunk carries the token usage of the call unless the stream options say otherwise.
     pub fn call_streaming(
         &self,
         messages: impl Into<RequestMessages>,
     ) -> ModelResult<ResponseStream> {
-        let config = self.get_config_with_streaming(None);
+        self.call_streaming_with(messages.into(), true)
+    }
+
+    /// Calls the API with the given request messages and gets back a stream of events.
+    fn stream_events(
+        &self,
+        messages: RequestMessages,
+        include_usage: bool,
+    ) -> ModelResult<BoxStream<'static, ModelResult<StreamEvent>>> {
+        let stream = self.call_streaming_with(messages, include_usage)?;
+        Ok(stream::flatten_events(
+            stream.map(|chunk| chunk.map(ResponseChunkOk::into_events)),
+        ))
+    }
+
+    /// Calls the API with the given request messages and gets back a stream of response chunks,
+    /// asking for the token usage when `include_usage` is set and the stream options aren't.
+    fn call_streaming_with(
+        &self,
+        messages: RequestMessages,
+        include_usage: bool,
+    ) -> ModelResult<ResponseStream> {
+        let config = self.get_config_with_streaming(include_usage);
         debug!("config = {config:#?}");
 
-        let messages = messages.into();
         debug!("messages = {}", serde_json::to_string(&messages).unwrap());
 
-        let request = reqwest::Client::new()
+        let request = self
+            .client
             .post(&self.base_url)
             .bearer_auth(config.api_key.as_ref().ok_or(ModelError::NoAPIKeyFound)?)
             .json(&RequestBody {
@@ -88,26 +165,31 @@ impl OpenAIModel {
                 config: config.into_owned(),
             });
 
-        Ok(ResponseStream::new(request))
+        Ok(ResponseStream::new(request, self.retry.clone()))
     }
 
     /// Gets the model's configuration with streaming enabled.
-    fn get_config_with_streaming(&self, options: Option<StreamOptions>) -> Cow<Config> {
+    ///
+    /// Asks for the token usage in the last chunk if `include_usage` is set, unless the stream
+    /// options are.
+    fn get_config_with_streaming(&self, include_usage: bool) -> Cow<'_, Config> {
         let mut config = Cow::Borrowed(self.config.as_ref());
 
         if self.config.stream.is_none() {
             config.to_mut().stream = Some(true);
         }
 
-        if let Some(options) = options {
-            config.to_mut().stream_options = Some(options);
+        if include_usage && self.config.stream_options.is_none() {
+            config.to_mut().stream_options = Some(StreamOptions {
+                include_usage: Some(true),
+            });
         }
 
         config
     }
 
     /// Gets the model's configuration without streaming enabled.
-    fn get_config_without_streaming(&self) -> Cow<Config> {
+    fn get_config_without_streaming(&self) -> Cow<'_, Config> {
         let mut config = Cow::Borrowed(self.config.as_ref());
 
         if self.config.stream.is_some() {
@@ -120,26 +202,23 @@ impl OpenAIModel {
     /// Extract main content from response
     pub(crate) fn extract_content_from_response(response: &ResponseOk) -> String {
         debug!("response = {response:#?}");
-        response.choices[0]
-            .message
-            .content
-            .clone()
-            .unwrap_or_default()
-    }
-
-    /// Extract main content from response chunk
-    pub(crate) fn extract_content_from_response_chunk(response: &ResponseChunkOk) -> String {
-        response.choices[0]
-            .delta
-            .content
-            .clone()
-            .unwrap_or_default()
+        response.content().unwrap_or_default().to_string()
     }
 
     /// Get the model's configuration
     pub fn get_config(&self) -> &Config {
         &self.config
     }
+
+    /// Get the HTTP client the model sends requests with
+    pub fn get_client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Get the model's retry policy
+    pub fn get_retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
 }
 
 impl OpenAILikeModel {
@@ -153,9 +232,22 @@ impl OpenAILikeModel {
         Self(OpenAIModel {
             config: Cow::Owned(Config::default()),
             base_url,
+            retry: RetryPolicy::default(),
+            client: http::default_client(),
         })
     }
 
+    /// Calls the API with the given request messages and gets back a stream of response chunks.
+    ///
+    /// Not every OpenAI-compatible API accepts stream options, so the token usage is only asked
+    /// for if they are set.
+    pub fn call_streaming(
+        &self,
+        messages: impl Into<RequestMessages>,
+    ) -> ModelResult<ResponseStream> {
+        self.0.call_streaming_with(messages.into(), false)
+    }
+
     /// Get the model's configuration
     pub fn get_config(&self) -> &Config {
         &self.0.config
@@ -172,15 +264,26 @@ impl TextModel for OpenAIModel {
         let content = Self::extract_content_from_response(&response);
         Ok(content)
     }
+
+    async fn prompt_with_usage(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<ModelResponse> {
+        let response = self.call(prompt.into()).await?;
+        Ok(ModelResponse {
+            content: Self::extract_content_from_response(&response),
+            usage: response.usage.as_ref().map(TokenUsage::from),
+            model: response.model,
+        })
+    }
 }
 
 impl TextStreamModel for OpenAIModel {
     async fn prompt_stream(
         &self,
         prompt: impl Into<Prompt> + Send,
-    ) -> ModelResult<BoxStream<'static, ModelResult<String>>> {
-        let stream = self.call_streaming(prompt.into())?;
-        Ok(Box::pin(stream))
+    ) -> ModelResult<BoxStream<'static, ModelResult<StreamEvent>>> {
+        self.stream_events(prompt.into().into(), true)
     }
 }
 
@@ -188,14 +291,83 @@ impl TextModel for OpenAILikeModel {
     async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
         self.0.prompt(prompt).await
     }
+
+    async fn prompt_with_usage(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<ModelResponse> {
+        self.0.prompt_with_usage(prompt).await
+    }
 }
 
 impl TextStreamModel for OpenAILikeModel {
     async fn prompt_stream(
         &self,
         prompt: impl Into<Prompt> + Send,
-    ) -> ModelResult<BoxStream<'static, ModelResult<String>>> {
-        self.0.prompt_stream(prompt).await
+    ) -> ModelResult<BoxStream<'static, ModelResult<StreamEvent>>> {
+        self.0.stream_events(prompt.into().into(), false)
+    }
+}
+
+impl ChatModel for OpenAIModel {
+    fn model_id(&self) -> &str {
+        &self.config.model
+    }
+
+    fn chat(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<String>> {
+        Box::pin(self.prompt(prompt))
+    }
+
+    fn chat_with_usage(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        Box::pin(self.prompt_with_usage(prompt))
+    }
+
+    fn chat_stream(
+        &self,
+        prompt: Prompt,
+    ) -> BoxFuture<'_, ModelResult<BoxStream<'static, ModelResult<StreamEvent>>>> {
+        Box::pin(self.prompt_stream(prompt))
+    }
+
+    fn chat_with_schema(
+        &self,
+        prompt: Prompt,
+        schema: OutputSchema,
+    ) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        let mut model = self.clone();
+        model.config.to_mut().response_format = Some(schema.into());
+        Box::pin(async move { model.prompt_with_usage(prompt).await })
+    }
+}
+
+impl ChatModel for OpenAILikeModel {
+    fn model_id(&self) -> &str {
+        self.0.model_id()
+    }
+
+    fn chat(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<String>> {
+        self.0.chat(prompt)
+    }
+
+    fn chat_with_usage(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        self.0.chat_with_usage(prompt)
+    }
+
+    fn chat_stream(
+        &self,
+        prompt: Prompt,
+    ) -> BoxFuture<'_, ModelResult<BoxStream<'static, ModelResult<StreamEvent>>>> {
+        Box::pin(self.prompt_stream(prompt))
+    }
+
+    /// Not every OpenAI-compatible API supports `json_schema` response formats, so the model is
+    /// asked for the schema in the prompt instead.
+    fn chat_with_schema(
+        &self,
+        prompt: Prompt,
+        schema: OutputSchema,
+    ) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        Box::pin(self.0.prompt_with_usage(schema.instruct(prompt)))
     }
 }
 
@@ -204,6 +376,8 @@ impl Default for OpenAIModel {
         Self {
             config: Cow::Owned(Config::default()),
             base_url: OPENAI_API_URL.to_string(),
+            retry: RetryPolicy::default(),
+            client: http::default_client(),
         }
     }
 }
@@ -222,8 +396,15 @@ impl Deref for OpenAILikeModel {
 
 #[cfg(test)]
 mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
     use crate::{
-        models::openai::ModelType,
+        models::{
+            openai::ModelType,
+            retry::tests::{http_response, mock_server},
+            StreamAccumulator,
+        },
+        prompt,
         utils::{self, Env},
     };
 
@@ -315,4 +496,283 @@ mod tests {
         assert_eq!(model.config.parallel_tool_calls, None);
         assert_eq!(model.config.user, None);
     }
+
+    #[test]
+    fn test_model_openai_dyn_chat_model() {
+        utils::load_env(Env::Test);
+        let models: Vec<Box<dyn ChatModel>> = vec![
+            Box::new(OpenAIModel::default()),
+            Box::new(
+                OpenAILikeModel::builder()
+                    .base_url("https://api.closedai.com/v1/chat/completions")
+                    .model("llama-3.1-405b")
+                    .build(),
+            ),
+        ];
+
+        assert_eq!(models[0].model_id(), ModelType::Gpt4oMini.to_string());
+        assert_eq!(models[1].model_id(), "llama-3.1-405b");
+    }
+
+    #[tokio::test]
+    async fn test_model_openai_retries_rate_limited_calls() {
+        let body = r#"{"id":"chatcmpl-1","object":"chat.completion","created":0,"model":"gpt-4o-mini","choices":[{"index":0,"message":{"role":"assistant","content":"Hello!"},"finish_reason":"stop"}]}"#;
+        let (url, hits) = mock_server(vec![
+            http_response(
+                "429 Too Many Requests",
+                &[
+                    ("x-ratelimit-remaining-requests", "0"),
+                    ("x-ratelimit-reset-requests", "10ms"),
+                ],
+                r#"{"error":{"message":"Rate limit reached","type":"requests"}}"#,
+            ),
+            http_response("200 OK", &[("content-type", "application/json")], body),
+        ])
+        .await;
+
+        let model = OpenAILikeModel::builder()
+            .base_url(url)
+            .api_key("sk-test")
+            .model("gpt-4o-mini")
+            .retry_policy(RetryPolicy::default().backoff(Duration::ZERO, Duration::from_secs(1)))
+            .build();
+
+        let output = model.prompt(prompt! { user: "Hi" }).await.unwrap();
+        assert_eq!(output, "Hello!");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_model_openai_prompt_with_usage() {
+        let body = r#"{"id":"chatcmpl-1","object":"chat.completion","created":0,"model":"Meta-Llama-3.1-8B-Instruct","choices":[{"index":0,"message":{"role":"assistant","content":"Hello!"},"finish_reason":"stop"}],"usage":{"prompt_tokens":9,"completion_tokens":3,"total_tokens":12,"completion_tokens_per_sec":410.5}}"#;
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "application/json")],
+            body,
+        )])
+        .await;
+
+        let model = OpenAILikeModel::builder()
+            .base_url(url)
+            .api_key("sk-test")
+            .model("Meta-Llama-3.1-8B-Instruct")
+            .build();
+
+        let response = model
+            .prompt_with_usage(prompt! { user: "Hi" })
+            .await
+            .unwrap();
+        assert_eq!(response.content, "Hello!");
+        assert_eq!(response.model, "Meta-Llama-3.1-8B-Instruct");
+        assert_eq!(response.usage, Some(TokenUsage::new(9, 3)));
+    }
+
+    #[tokio::test]
+    async fn test_model_openai_retries_stream_before_first_event() {
+        let chunk = |content: &str| {
+            format!(
+                r#"data: {{"id":"chatcmpl-1","object":"chat.completion.chunk","created":0,"model":"gpt-4o-mini","choices":[{{"index":0,"delta":{{"content":"{content}"}},"finish_reason":null}}]}}"#
+            )
+        };
+        let body = format!("{}\n\n{}\n\ndata: [DONE]\n\n", chunk("Hel"), chunk("lo"));
+        let (url, hits) = mock_server(vec![
+            http_response("503 Service Unavailable", &[("retry-after", "0")], ""),
+            http_response("200 OK", &[("content-type", "text/event-stream")], &body),
+        ])
+        .await;
+
+        let model = OpenAILikeModel::builder()
+            .base_url(url)
+            .api_key("sk-test")
+            .model("gpt-4o-mini")
+            .build();
+
+        let chunks: Vec<String> = model
+            .prompt_stream(prompt! { user: "Hi" })
+            .await
+            .unwrap()
+            .map(|event| event.unwrap().content().unwrap_or_default().to_string())
+            .collect()
+            .await;
+
+        assert_eq!(chunks.concat(), "Hello");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_model_openai_stream_reports_usage() {
+        let model = OpenAIModel::builder().api_key("sk-test").build();
+        let config = model.get_config_with_streaming(true);
+        assert_eq!(config.stream, Some(true));
+        assert_eq!(
+            config.stream_options,
+            Some(StreamOptions {
+                include_usage: Some(true)
+            })
+        );
+
+        // Compatible APIs are only sent stream options when they are set.
+        let model = OpenAILikeModel::builder()
+            .base_url("https://api.closedai.com/v1/chat/completions")
+            .api_key("sk-test")
+            .model("llama-3.1-405b")
+            .build();
+        let config = model.get_config_with_streaming(false);
+        assert_eq!(config.stream, Some(true));
+        assert_eq!(config.stream_options, None);
+
+        let content = r#"data: {"id":"chatcmpl-1","object":"chat.completion.chunk","created":0,"model":"gpt-4o-mini","choices":[{"index":0,"delta":{"content":"Hello"},"finish_reason":"stop"}]}"#;
+        let usage = r#"data: {"id":"chatcmpl-1","object":"chat.completion.chunk","created":0,"model":"gpt-4o-mini","choices":[],"usage":{"prompt_tokens":12,"completion_tokens":1,"total_tokens":13}}"#;
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "text/event-stream")],
+            &format!("{content}\n\n{usage}\n\ndata: [DONE]\n\n"),
+        )])
+        .await;
+
+        let model = OpenAILikeModel::builder()
+            .base_url(url)
+            .api_key("sk-test")
+            .model("gpt-4o-mini")
+            .stream_options(StreamOptions {
+                include_usage: Some(true),
+            })
+            .build();
+
+        let stream = model.prompt_stream(prompt! { user: "Hi" }).await.unwrap();
+        let response = StreamAccumulator::collect(stream).await.unwrap();
+        assert_eq!(response.message.content.as_deref(), Some("Hello"));
+        assert_eq!(response.usage, Some(TokenUsage::new(12, 1)));
+    }
+
+    #[tokio::test]
+    async fn test_model_openai_call_with_tools() -> anyhow::Result<()> {
+        let tool_call = r#"{"id":"chatcmpl-1","object":"chat.completion","created":0,"model":"gpt-4o-mini","choices":[{"index":0,"message":{"role":"assistant","content":null,"tool_calls":[{"id":"call_abc","type":"function","function":{"name":"get_weather","arguments":"{\"city\":\"Tokyo\"}"}}]},"finish_reason":"tool_calls"}]}"#;
+        let answer = r#"{"id":"chatcmpl-2","object":"chat.completion","created":0,"model":"gpt-4o-mini","choices":[{"index":0,"message":{"role":"assistant","content":"It's sunny in Tokyo."},"finish_reason":"stop"}]}"#;
+        let (url, hits) = mock_server(vec![
+            http_response("200 OK", &[("content-type", "application/json")], tool_call),
+            http_response("200 OK", &[("content-type", "application/json")], answer),
+        ])
+        .await;
+
+        let model = OpenAILikeModel::builder()
+            .base_url(url)
+            .api_key("sk-test")
+            .model("gpt-4o-mini")
+            .build();
+
+        let mut messages = RequestMessages::from(prompt! { user: "Weather in Tokyo?" });
+        let response = model
+            .call_with_tools(&mut messages, |tool_call| async move {
+                assert_eq!(tool_call.function.name, "get_weather");
+                Ok("sunny".to_string())
+            })
+            .await?;
+
+        assert_eq!(response.content(), Some("It's sunny in Tokyo."));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert_eq!(messages.0.len(), 4);
+        assert_eq!(
+            serde_json::to_value(&messages.0[2..])?,
+            serde_json::json!([
+                { "role": "tool", "tool_call_id": "call_abc", "content": "sunny" },
+                { "role": "assistant", "content": "It's sunny in Tokyo." }
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_model_openai_call_with_tools_gives_up() {
+        let tool_call = r#"{"id":"chatcmpl-1","object":"chat.completion","created":0,"model":"gpt-4o-mini","choices":[{"index":0,"message":{"role":"assistant","content":null,"tool_calls":[{"id":"call_abc","type":"function","function":{"name":"get_weather","arguments":"{}"}}]},"finish_reason":"tool_calls"}]}"#;
+        let (url, hits) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "application/json")],
+            tool_call,
+        )])
+        .await;
+
+        let model = OpenAILikeModel::builder()
+            .base_url(url)
+            .api_key("sk-test")
+            .model("gpt-4o-mini")
+            .build();
+
+        let mut messages = RequestMessages::from(prompt! { user: "Weather?" });
+        let result = model
+            .call_with_tools(&mut messages, |_| async { Ok("sunny".to_string()) })
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ModelError::ToolCallRoundsExceeded(MAX_TOOL_CALL_ROUNDS))
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), MAX_TOOL_CALL_ROUNDS);
+    }
+
+    #[tokio::test]
+    async fn test_model_openai_does_not_retry_fatal_errors() {
+        let (url, hits) = mock_server(vec![http_response(
+            "401 Unauthorized",
+            &[],
+            r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error"}}"#,
+        )])
+        .await;
+
+        let model = OpenAILikeModel::builder()
+            .base_url(url)
+            .api_key("sk-test")
+            .model("gpt-4o-mini")
+            .build();
+
+        let error = model.prompt(prompt! { user: "Hi" }).await.unwrap_err();
+        assert!(!error.is_retryable());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // The body of the response is parsed into OpenAI's error, whether streaming or not.
+        let ModelError::OpenAIResponseError(error) = error else {
+            panic!("expected an OpenAI error, got {error:?}");
+        };
+        assert_eq!(error.error.message, "Incorrect API key provided");
+
+        let mut stream = model.prompt_stream(prompt! { user: "Hi" }).await.unwrap();
+        let Some(Err(error)) = stream.next().await else {
+            panic!("expected the stream to fail");
+        };
+        let ModelError::OpenAIResponseError(error) = error else {
+            panic!("expected an OpenAI error, got {error:?}");
+        };
+        assert_eq!(error.error.r#type, "invalid_request_error");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_model_openai_stream_cut_midway_fails() {
+        let chunk = r#"data: {"id":"chatcmpl-1","object":"chat.completion.chunk","created":0,"model":"gpt-4o-mini","choices":[{"index":0,"delta":{"content":"Hel"},"finish_reason":null}]}"#;
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("content-type", "text/event-stream")],
+            &format!("{chunk}\n\n"),
+        )])
+        .await;
+
+        let model = OpenAILikeModel::builder()
+            .base_url(url)
+            .api_key("sk-test")
+            .model("gpt-4o-mini")
+            .build();
+
+        let events: Vec<_> = model
+            .prompt_stream(prompt! { user: "Hi" })
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(events.first().is_some_and(Result::is_ok));
+        assert!(events.last().is_some_and(Result::is_err));
+        assert!(StreamAccumulator::collect(futures::stream::iter(events))
+            .await
+            .is_err());
+    }
 }
@@ -1,16 +1,22 @@
 use std::{
+    collections::BTreeMap,
     pin::Pin,
     task::{Context, Poll},
 };
 
-use futures::{ready, Stream};
+use futures::{ready, Stream, StreamExt};
 use pin_project::pin_project;
 use reqwest::RequestBuilder;
-use reqwest_eventsource::{Error, Event, EventSource, RequestBuilderExt};
 
-use crate::models::{ModelResult, ResponseStreamError};
+use crate::models::{
+    retry::{RetryEventSource, RetryPolicy},
+    ModelResult,
+};
 
-use super::{OpenAIModel, ResponseChunkOk};
+use super::{
+    Choice, ChoiceMessage, FunctionCall, ResponseChunkOk, ResponseError, ResponseOk, ToolCall,
+    ToolType, Usage,
+};
 
 //--------------------------------------------------------------------------------------------------
 // Types
@@ -20,7 +26,30 @@ use super::{OpenAIModel, ResponseChunkOk};
 #[pin_project]
 pub struct ResponseStream {
     #[pin]
-    stream: EventSource,
+    stream: RetryEventSource,
+}
+
+/// Folds the chunks of a streamed response back into the response `OpenAIModel::call` would have
+/// returned.
+#[derive(Debug, Default)]
+pub struct ResponseAccumulator {
+    id: String,
+    created: u64,
+    model: String,
+    service_tier: Option<String>,
+    system_fingerprint: Option<String>,
+    usage: Option<Usage>,
+    choices: BTreeMap<u64, ChoiceState>,
+}
+
+/// The state of a single choice being accumulated.
+#[derive(Debug, Default)]
+struct ChoiceState {
+    content: Option<String>,
+    refusal: Option<String>,
+    role: Option<String>,
+    tool_calls: BTreeMap<usize, ToolCall>,
+    finish_reason: Option<String>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -28,19 +57,126 @@ pub struct ResponseStream {
 //--------------------------------------------------------------------------------------------------
 
 impl ResponseStream {
-    /// Creates a new `ResponseStream` from the given request.
-    pub fn new(request: RequestBuilder) -> Self {
-        let stream = request.eventsource().unwrap();
+    /// Creates a new `ResponseStream` from the given request, reconnecting according to the
+    /// retry policy if the connection fails before the response starts.
+    pub fn new(request: RequestBuilder, retry: RetryPolicy) -> Self {
+        let stream = RetryEventSource::new(request, retry).error_body::<ResponseError>();
         Self { stream }
     }
 
-    fn map_error(err: Error) -> ModelResult<String> {
-        let error = match err {
-            Error::InvalidStatusCode(_, response) => ResponseStreamError::APIResponse(response),
-            err => ResponseStreamError::EventSourceError(err),
-        };
+    /// Consumes the stream and folds the chunks into a whole response.
+    pub async fn collect_response(mut self) -> ModelResult<ResponseOk> {
+        let mut accumulator = ResponseAccumulator::new();
+        while let Some(chunk) = self.next().await {
+            accumulator.push(chunk?);
+        }
+
+        Ok(accumulator.finish())
+    }
+}
+
+impl ResponseAccumulator {
+    /// Creates a new, empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk to the response.
+    pub fn push(&mut self, chunk: ResponseChunkOk) {
+        self.id = chunk.id;
+        self.created = chunk.created;
+        self.model = chunk.model;
+        self.service_tier = chunk.service_tier.or(self.service_tier.take());
+        self.system_fingerprint = chunk.system_fingerprint.or(self.system_fingerprint.take());
+        self.usage = chunk.usage.or(self.usage.take());
+
+        for choice in chunk.choices {
+            let state = self.choices.entry(choice.index).or_default();
+            let delta = choice.delta;
+
+            if let Some(content) = delta.content {
+                state
+                    .content
+                    .get_or_insert_with(String::new)
+                    .push_str(&content);
+            }
+
+            if let Some(refusal) = delta.refusal {
+                state
+                    .refusal
+                    .get_or_insert_with(String::new)
+                    .push_str(&refusal);
+            }
+
+            state.role = delta.role.or(state.role.take());
+            state.finish_reason = choice.finish_reason.or(state.finish_reason.take());
+
+            for chunk in delta.tool_calls.unwrap_or_default() {
+                let tool_call = state
+                    .tool_calls
+                    .entry(chunk.index)
+                    .or_insert_with(|| ToolCall {
+                        id: String::new(),
+                        r#type: ToolType::Function,
+                        function: FunctionCall {
+                            name: String::new(),
+                            arguments: String::new(),
+                        },
+                    });
+
+                if let Some(id) = chunk.id {
+                    tool_call.id = id;
+                }
+
+                if let Some(r#type) = chunk.r#type {
+                    tool_call.r#type = r#type;
+                }
+
+                if let Some(function) = chunk.function {
+                    tool_call
+                        .function
+                        .name
+                        .push_str(&function.name.unwrap_or_default());
+                    tool_call
+                        .function
+                        .arguments
+                        .push_str(&function.arguments.unwrap_or_default());
+                }
+            }
+        }
+    }
+
+    /// Returns the response accumulated so far.
+    pub fn finish(self) -> ResponseOk {
+        let choices = self
+            .choices
+            .into_iter()
+            .map(|(index, state)| {
+                let tool_calls: Vec<ToolCall> = state.tool_calls.into_values().collect();
+                Choice {
+                    finish_reason: state.finish_reason,
+                    index,
+                    message: ChoiceMessage {
+                        content: state.content,
+                        refusal: state.refusal,
+                        tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                        role: state.role,
+                    },
+                    logprobs: None,
+                }
+            })
+            .collect();
 
-        Err(error.into())
+        ResponseOk {
+            id: self.id,
+            choices,
+            created: self.created,
+            model: self.model,
+            service_tier: self.service_tier,
+            system_fingerprint: self.system_fingerprint,
+            object: "chat.completion".to_string(),
+            usage: self.usage,
+        }
     }
 }
 
@@ -49,32 +185,88 @@ impl ResponseStream {
 //--------------------------------------------------------------------------------------------------
 
 impl Stream for ResponseStream {
-    type Item = ModelResult<String>;
+    type Item = ModelResult<ResponseChunkOk>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.project();
-        let event = ready!(this.stream.poll_next(cx));
+        let Some(data) = ready!(this.stream.poll_next(cx)) else {
+            return Poll::Ready(None);
+        };
 
-        match event {
-            Some(Ok(event)) => match event {
-                Event::Message(message) => {
-                    let data = message.data;
-                    if data == "[DONE]" {
-                        return Poll::Ready(None);
-                    }
+        let data = data?;
+        if data == "[DONE]" {
+            return Poll::Ready(None);
+        }
 
-                    let body: ResponseChunkOk = serde_json::from_str(&data)?;
-                    let content = OpenAIModel::extract_content_from_response_chunk(&body);
+        let body: ResponseChunkOk = serde_json::from_str(&data)?;
+        Poll::Ready(Some(Ok(body)))
+    }
+}
 
-                    Poll::Ready(Some(Ok(content)))
-                }
-                Event::Open => {
-                    cx.waker().wake_by_ref();
-                    Poll::Pending
-                }
-            },
-            Some(Err(err)) => Poll::Ready(Some(Self::map_error(err))),
-            None => Poll::Ready(None),
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{StreamEvent, TokenUsage};
+
+    use super::*;
+
+    const CHUNKS: [&str; 5] = [
+        r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1,"model":"gpt-4o-mini","choices":[{"index":0,"delta":{"role":"assistant","content":null,"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"get_weather","arguments":""}}]},"finish_reason":null}]}"#,
+        r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1,"model":"gpt-4o-mini","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\":"}}]},"finish_reason":null}]}"#,
+        r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1,"model":"gpt-4o-mini","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"Tokyo\"}"}}]},"finish_reason":null}]}"#,
+        r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1,"model":"gpt-4o-mini","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#,
+        r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1,"model":"gpt-4o-mini","choices":[],"usage":{"prompt_tokens":30,"completion_tokens":9,"total_tokens":39}}"#,
+    ];
+
+    fn chunks() -> Vec<ResponseChunkOk> {
+        CHUNKS
+            .iter()
+            .map(|chunk| serde_json::from_str(chunk).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_openai_stream_chunk_events() {
+        let events: Vec<StreamEvent> = chunks()
+            .into_iter()
+            .flat_map(ResponseChunkOk::into_events)
+            .collect();
+
+        assert_eq!(events.len(), 5);
+        assert!(matches!(
+            &events[0],
+            StreamEvent::ToolCallDelta(delta) if delta.id.as_deref() == Some("call_a")
+        ));
+        assert_eq!(
+            events[3],
+            StreamEvent::FinishReason("tool_calls".to_string())
+        );
+        assert_eq!(events[4], StreamEvent::Usage(TokenUsage::new(30, 9)));
+    }
+
+    #[test]
+    fn test_openai_stream_accumulator() {
+        let mut accumulator = ResponseAccumulator::new();
+        for chunk in chunks() {
+            accumulator.push(chunk);
         }
+
+        let response = accumulator.finish();
+        assert_eq!(response.id, "chatcmpl-1");
+        assert_eq!(response.usage.as_ref().unwrap().total_tokens, 39);
+
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(choice.message.role.as_deref(), Some("assistant"));
+        assert_eq!(choice.message.content, None);
+
+        let tool_calls = response.tool_calls();
+        assert_eq!(tool_calls.len(), 1);
+        assert_eq!(tool_calls[0].id, "call_a");
+        assert_eq!(tool_calls[0].function.name, "get_weather");
+        assert_eq!(tool_calls[0].function.arguments, r#"{"city":"Tokyo"}"#);
     }
 }
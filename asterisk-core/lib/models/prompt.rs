@@ -1,5 +1,7 @@
 use std::vec::IntoIter;
 
+use serde::{Deserialize, Serialize};
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
@@ -21,27 +23,116 @@ pub enum PromptMessage {
 
     /// A message that assumes the role of the assistant.
     Assistant(AssistantMessage),
+
+    /// A message that carries the result of a tool call made by the assistant.
+    Tool(ToolMessage),
 }
 
 /// A system message is a message that sets the context for the conversation.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SystemMessage {
     /// The content of the message.
     pub content: String,
+
+    /// An optional name for the participant.
+    pub name: Option<String>,
 }
 
 /// A user message is a message that the user sends to the assistant.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UserMessage {
     /// The content of the message.
-    pub content: String,
+    pub content: MessageContent,
+
+    /// An optional name for the participant.
+    pub name: Option<String>,
 }
 
 /// A assistant message is a message that the assistant sends to the user.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AssistantMessage {
+    /// The content of the message.
+    ///
+    /// Can be `None` when the assistant only makes tool calls.
+    pub content: Option<String>,
+
+    /// An optional name for the participant.
+    pub name: Option<String>,
+
+    /// The refusal message generated by the assistant.
+    pub refusal: Option<String>,
+
+    /// The tool calls made by the assistant.
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// A tool message carries the result of a tool call back to the assistant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolMessage {
+    /// The id of the tool call this message is a result of.
+    pub tool_call_id: String,
+
     /// The content of the message.
     pub content: String,
+
+    /// The name of the tool that was called.
+    pub name: Option<String>,
+}
+
+/// The content of a user message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageContent {
+    /// Plain text content.
+    Text(String),
+
+    /// Content made up of multiple parts, like text and images.
+    Parts(Vec<ContentPart>),
+}
+
+/// A part of a multi-part message content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentPart {
+    /// A text part.
+    Text(String),
+
+    /// An image part.
+    Image(ImageContent),
+}
+
+/// An image in a message content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageContent {
+    /// The URL of the image. This can also be a base64 encoded `data:` URL.
+    pub url: String,
+
+    /// The level of detail the model should process the image with.
+    pub detail: Option<ImageDetail>,
+}
+
+/// The level of detail the model should process an image with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageDetail {
+    /// Let the model decide.
+    Auto,
+
+    /// Low resolution.
+    Low,
+
+    /// High resolution.
+    High,
+}
+
+/// A tool call made by the assistant.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolCall {
+    /// The id of the tool call.
+    pub id: String,
+
+    /// The name of the tool to call.
+    pub name: String,
+
+    /// The arguments to call the tool with, as a JSON string.
+    pub arguments: String,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -85,24 +176,122 @@ impl SystemMessage {
     pub fn new(content: impl Into<String>) -> Self {
         Self {
             content: content.into(),
+            name: None,
         }
     }
+
+    /// Sets the name of the participant.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
 }
 
 impl UserMessage {
     /// Create a new user message.
-    pub fn new(content: impl Into<String>) -> Self {
+    pub fn new(content: impl Into<MessageContent>) -> Self {
         Self {
             content: content.into(),
+            name: None,
         }
     }
+
+    /// Sets the name of the participant.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
 }
 
 impl AssistantMessage {
     /// Create a new assistant message.
     pub fn new(content: impl Into<String>) -> Self {
         Self {
+            content: Some(content.into()),
+            name: None,
+            refusal: None,
+            tool_calls: vec![],
+        }
+    }
+
+    /// Create a new assistant message that only makes tool calls.
+    pub fn with_tool_calls(tool_calls: impl IntoIterator<Item = ToolCall>) -> Self {
+        Self {
+            content: None,
+            name: None,
+            refusal: None,
+            tool_calls: tool_calls.into_iter().collect(),
+        }
+    }
+
+    /// Sets the name of the participant.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+impl ToolMessage {
+    /// Create a new tool message.
+    pub fn new(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: tool_call_id.into(),
             content: content.into(),
+            name: None,
+        }
+    }
+
+    /// Sets the name of the tool that was called.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+impl MessageContent {
+    /// Returns the text of the content, joining text parts with newlines and skipping other
+    /// parts.
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text(text) => Some(text.as_str()),
+                    ContentPart::Image(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+impl ContentPart {
+    /// Create a new text part.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// Create a new image part.
+    pub fn image(url: impl Into<String>) -> Self {
+        Self::Image(ImageContent {
+            url: url.into(),
+            detail: None,
+        })
+    }
+}
+
+impl ToolCall {
+    /// Create a new tool call.
+    pub fn new(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        arguments: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            arguments: arguments.into(),
         }
     }
 }
@@ -114,14 +303,19 @@ impl PromptMessage {
     }
 
     /// Create a new user message.
-    pub fn user(content: impl Into<String>) -> Self {
-        Self::User(UserMessage::new(content.into()))
+    pub fn user(content: impl Into<MessageContent>) -> Self {
+        Self::User(UserMessage::new(content))
     }
 
     /// Create a new assistant message.
     pub fn assistant(content: impl Into<String>) -> Self {
         Self::Assistant(AssistantMessage::new(content.into()))
     }
+
+    /// Create a new tool message.
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self::Tool(ToolMessage::new(tool_call_id, content))
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -143,6 +337,30 @@ impl Default for Prompt {
     }
 }
 
+impl From<Vec<PromptMessage>> for Prompt {
+    fn from(messages: Vec<PromptMessage>) -> Self {
+        Self { messages }
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ContentPart>> for MessageContent {
+    fn from(parts: Vec<ContentPart>) -> Self {
+        Self::Parts(parts)
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // Macros
 //--------------------------------------------------------------------------------------------------
@@ -208,4 +426,15 @@ mod tests {
             PromptMessage::assistant("The weather in Tokyo is sunny.")
         );
     }
+
+    #[test]
+    fn test_model_prompt_message_content_text() {
+        let content = MessageContent::from(vec![
+            ContentPart::text("What is in this image?"),
+            ContentPart::image("https://example.com/cat.png"),
+            ContentPart::text("Be brief."),
+        ]);
+
+        assert_eq!(content.text(), "What is in this image?\nBe brief.");
+    }
 }
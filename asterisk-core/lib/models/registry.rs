@@ -0,0 +1,586 @@
+//! A registry of named provider profiles that models can be constructed from at runtime.
+
+use std::{env, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    anthropic::{self, AnthropicModel},
+    gemini::{self, GeminiModel},
+    ollama::{self, OllamaAdminClient, OllamaModel},
+    openai::{self, OpenAILikeModel, OpenAIModel},
+    ChatModel, ModelError, ModelPrice, ModelResult, PriceTable,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The environment variable pointing to a custom model registry file.
+pub const ASTERISK_MODELS_CONFIG: &str = "ASTERISK_MODELS_CONFIG";
+
+/// The provider profiles available by default.
+pub const DEFAULT_PROFILES: &str = include_str!("profiles/default.toml");
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A collection of named provider profiles.
+///
+/// A registry is usually loaded from a TOML or JSON file that looks like this:
+///
+/// ```toml
+/// default = "fireworks-llama-3.1-8b"
+///
+/// [[profiles]]
+/// name = "fireworks-llama-3.1-8b"
+/// kind = "openai-like"
+/// base_url = "https://api.fireworks.ai/inference/v1/chat/completions"
+/// model = "accounts/fireworks/models/llama-v3p1-8b-instruct"
+/// api_key_env = "FIREWORKS_API_KEY"
+///
+/// [profiles.params]
+/// temperature = 0.0
+///
+/// [profiles.price]
+/// input_per_million = 0.2
+/// output_per_million = 0.2
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelRegistry {
+    /// The name of the profile to use when none is specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+
+    /// The profiles in the registry.
+    #[serde(default)]
+    pub profiles: Vec<ModelProfile>,
+}
+
+/// A named provider profile describing how to construct a model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelProfile {
+    /// The name of the profile.
+    pub name: String,
+
+    /// A human-readable description of the profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The kind of API the provider serves.
+    pub kind: ProviderKind,
+
+    /// The URL of the provider's chat endpoint.
+    ///
+    /// Defaults to the official endpoint for `openai`, `anthropic`, `gemini` and `ollama` profiles
+    /// and is required for `openai-like` profiles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+
+    /// The environment variable holding the API key for the provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+
+    /// The ID of the model to use.
+    pub model: String,
+
+    /// The default sampling parameters for the model.
+    #[serde(default)]
+    pub params: SamplingParams,
+
+    /// The price of the model, used to estimate spend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<ModelPrice>,
+}
+
+/// The kind of API a provider serves.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ProviderKind {
+    /// The OpenAI API.
+    #[serde(rename = "openai")]
+    OpenAI,
+
+    /// An API compatible with the OpenAI API.
+    #[serde(rename = "openai-like")]
+    OpenAILike,
+
+    /// The Ollama API.
+    #[serde(rename = "ollama")]
+    Ollama,
+
+    /// The Anthropic Messages API.
+    #[serde(rename = "anthropic")]
+    Anthropic,
+
+    /// The Gemini API.
+    #[serde(rename = "gemini")]
+    Gemini,
+}
+
+/// Sampling parameters applied to a model built from a profile.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SamplingParams {
+    /// The sampling temperature to use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// The nucleus sampling probability mass.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// The maximum number of tokens to generate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    /// A best effort to sample deterministically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+
+    /// Sequences where the model will stop generating further tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+
+    /// Penalizes new tokens based on their frequency in the text so far.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+
+    /// Penalizes new tokens based on whether they appear in the text so far.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ModelRegistry {
+    /// Loads the registry from a file.
+    ///
+    /// Files with a `.json` extension are parsed as JSON, everything else as TOML.
+    pub fn from_path(path: impl AsRef<Path>) -> ModelResult<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json_str(&content),
+            _ => Self::from_toml_str(&content),
+        }
+    }
+
+    /// Loads the registry from the file at `ASTERISK_MODELS_CONFIG` if set, otherwise falls back to
+    /// the default profiles.
+    pub fn from_env() -> ModelResult<Self> {
+        match env::var(ASTERISK_MODELS_CONFIG) {
+            Ok(path) => Self::from_path(path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Parses the registry from a TOML string.
+    pub fn from_toml_str(content: &str) -> ModelResult<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Parses the registry from a JSON string.
+    pub fn from_json_str(content: &str) -> ModelResult<Self> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    /// Gets the profile with the given name.
+    pub fn profile(&self, name: &str) -> ModelResult<&ModelProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| ModelError::ProfileNotFound(name.to_string()))
+    }
+
+    /// Gets the default profile, which is the first profile if no default is set.
+    pub fn default_profile(&self) -> ModelResult<&ModelProfile> {
+        match &self.default {
+            Some(name) => self.profile(name),
+            None => self
+                .profiles
+                .first()
+                .ok_or_else(|| ModelError::ProfileNotFound("default".to_string())),
+        }
+    }
+
+    /// Adds a profile to the registry, replacing any existing profile with the same name.
+    pub fn insert(&mut self, profile: ModelProfile) {
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+
+    /// Returns the prices of the models of all profiles that have one.
+    pub fn price_table(&self) -> PriceTable {
+        self.profiles
+            .iter()
+            .filter_map(|profile| Some((profile.model.clone(), profile.price?)))
+            .collect()
+    }
+
+    /// Builds the model described by the profile with the given name.
+    pub fn build(&self, name: &str) -> ModelResult<Box<dyn ChatModel>> {
+        self.profile(name)?.build()
+    }
+
+    /// Builds the model described by the default profile.
+    pub fn build_default(&self) -> ModelResult<Box<dyn ChatModel>> {
+        self.default_profile()?.build()
+    }
+}
+
+impl ModelProfile {
+    /// Overrides the profile's sampling parameters with the ones set in `params`.
+    pub fn with_params(mut self, params: SamplingParams) -> Self {
+        self.params = self.params.merge(params);
+        self
+    }
+
+    /// Creates a client for managing the models of the profile's Ollama server.
+    ///
+    /// Returns `None` for profiles of other providers.
+    pub fn ollama_admin(&self) -> Option<OllamaAdminClient> {
+        if self.kind != ProviderKind::Ollama {
+            return None;
+        }
+
+        let mut builder = OllamaAdminClient::builder();
+        if let Some(base_url) = &self.base_url {
+            builder = builder.base_url(base_url);
+        }
+
+        Some(builder.build())
+    }
+
+    /// Builds the model described by the profile.
+    pub fn build(&self) -> ModelResult<Box<dyn ChatModel>> {
+        let api_key = match &self.api_key_env {
+            Some(var) => Some(env::var(var).map_err(|_| ModelError::EnvVarNotFound(var.clone()))?),
+            None => None,
+        };
+
+        let model: Box<dyn ChatModel> = match self.kind {
+            ProviderKind::OpenAI => {
+                let mut builder = OpenAIModel::builder().model(&self.model);
+                if let Some(api_key) = api_key {
+                    builder = builder.api_key(api_key);
+                }
+
+                match &self.base_url {
+                    Some(base_url) => {
+                        Box::new(self.params.apply_openai(builder.base_url(base_url)).build())
+                    }
+                    None => Box::new(self.params.apply_openai(builder).build()),
+                }
+            }
+            ProviderKind::OpenAILike => {
+                let base_url = self.base_url.as_ref().ok_or_else(|| {
+                    ModelError::InvalidProfile(self.name.clone(), "missing `base_url`".to_string())
+                })?;
+
+                let mut builder = OpenAILikeModel::builder()
+                    .base_url(base_url)
+                    .model(&self.model);
+                if let Some(api_key) = api_key {
+                    builder = builder.api_key(api_key);
+                }
+
+                Box::new(self.params.apply_openai(builder).build())
+            }
+            ProviderKind::Ollama => {
+                let mut builder = OllamaModel::builder().model(&self.model);
+                if let Some(base_url) = &self.base_url {
+                    builder = builder.base_url(base_url);
+                }
+
+                Box::new(self.params.apply_ollama(builder).build())
+            }
+            ProviderKind::Anthropic => {
+                let mut builder = AnthropicModel::builder().model(&self.model);
+                if let Some(api_key) = api_key {
+                    builder = builder.api_key(api_key);
+                }
+
+                if let Some(base_url) = &self.base_url {
+                    builder = builder.base_url(base_url);
+                }
+
+                Box::new(self.params.apply_anthropic(builder).build())
+            }
+            ProviderKind::Gemini => {
+                let mut builder = GeminiModel::builder().model(&self.model);
+                if let Some(api_key) = api_key {
+                    builder = builder.api_key(api_key);
+                }
+
+                if let Some(base_url) = &self.base_url {
+                    builder = builder.base_url(base_url);
+                }
+
+                Box::new(self.params.apply_gemini(builder).build())
+            }
+        };
+
+        Ok(model)
+    }
+}
+
+impl SamplingParams {
+    /// Returns the parameters with the ones set in `other` taking precedence.
+    pub fn merge(self, other: SamplingParams) -> Self {
+        Self {
+            temperature: other.temperature.or(self.temperature),
+            top_p: other.top_p.or(self.top_p),
+            max_tokens: other.max_tokens.or(self.max_tokens),
+            seed: other.seed.or(self.seed),
+            stop: other.stop.or(self.stop),
+            frequency_penalty: other.frequency_penalty.or(self.frequency_penalty),
+            presence_penalty: other.presence_penalty.or(self.presence_penalty),
+        }
+    }
+
+    /// Applies the parameters to an OpenAI model builder.
+    fn apply_openai<U, M>(
+        &self,
+        mut builder: openai::ModelBuilder<U, M>,
+    ) -> openai::ModelBuilder<U, M> {
+        if let Some(temperature) = self.temperature {
+            builder = builder.temperature(temperature);
+        }
+
+        if let Some(top_p) = self.top_p {
+            builder = builder.top_p(top_p);
+        }
+
+        if let Some(max_tokens) = self.max_tokens {
+            builder = builder.max_tokens(max_tokens);
+        }
+
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed);
+        }
+
+        if let Some(stop) = &self.stop {
+            builder = builder.stop(stop.clone());
+        }
+
+        if let Some(frequency_penalty) = self.frequency_penalty {
+            builder = builder.frequency_penalty(frequency_penalty);
+        }
+
+        if let Some(presence_penalty) = self.presence_penalty {
+            builder = builder.presence_penalty(presence_penalty);
+        }
+
+        builder
+    }
+
+    /// Applies the parameters to an Ollama model builder.
+    fn apply_ollama(&self, mut builder: ollama::ModelBuilder) -> ollama::ModelBuilder {
+        if let Some(temperature) = self.temperature {
+            builder = builder.temperature(temperature);
+        }
+
+        if let Some(top_p) = self.top_p {
+            builder = builder.top_p(top_p);
+        }
+
+        if let Some(max_tokens) = self.max_tokens {
+            builder = builder.num_predict(i32::try_from(max_tokens).unwrap_or(i32::MAX));
+        }
+
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed);
+        }
+
+        if let Some(stop) = &self.stop {
+            builder = builder.stop(stop.clone());
+        }
+
+        if let Some(frequency_penalty) = self.frequency_penalty {
+            builder = builder.frequency_penalty(frequency_penalty);
+        }
+
+        if let Some(presence_penalty) = self.presence_penalty {
+            builder = builder.presence_penalty(presence_penalty);
+        }
+
+        builder
+    }
+
+    /// Applies the parameters to an Anthropic model builder.
+    ///
+    /// The Messages API has no seed or penalties, so those are ignored.
+    fn apply_anthropic(&self, mut builder: anthropic::ModelBuilder) -> anthropic::ModelBuilder {
+        if let Some(temperature) = self.temperature {
+            builder = builder.temperature(temperature);
+        }
+
+        if let Some(top_p) = self.top_p {
+            builder = builder.top_p(top_p);
+        }
+
+        if let Some(max_tokens) = self.max_tokens {
+            builder = builder.max_tokens(max_tokens);
+        }
+
+        if let Some(stop) = &self.stop {
+            builder = builder.stop_sequences(stop.clone());
+        }
+
+        builder
+    }
+
+    /// Applies the parameters to a Gemini model builder.
+    fn apply_gemini(&self, mut builder: gemini::ModelBuilder) -> gemini::ModelBuilder {
+        if let Some(temperature) = self.temperature {
+            builder = builder.temperature(temperature);
+        }
+
+        if let Some(top_p) = self.top_p {
+            builder = builder.top_p(top_p);
+        }
+
+        if let Some(max_tokens) = self.max_tokens {
+            builder = builder.max_tokens(max_tokens);
+        }
+
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed);
+        }
+
+        if let Some(stop) = &self.stop {
+            builder = builder.stop(stop.clone());
+        }
+
+        if let Some(frequency_penalty) = self.frequency_penalty {
+            builder = builder.frequency_penalty(frequency_penalty);
+        }
+
+        if let Some(presence_penalty) = self.presence_penalty {
+            builder = builder.presence_penalty(presence_penalty);
+        }
+
+        builder
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        Self::from_toml_str(DEFAULT_PROFILES).expect("default profiles should be valid")
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{self, Env};
+
+    use super::*;
+
+    #[test]
+    fn test_model_registry_from_str() -> anyhow::Result<()> {
+        utils::load_env(Env::Test);
+        env::set_var("ASTERISK_TEST_REGISTRY_API_KEY", "sk-test");
+
+        let registry = ModelRegistry::from_toml_str(
+            r#"
+            default = "together"
+
+            [[profiles]]
+            name = "together"
+            kind = "openai-like"
+            base_url = "https://api.together.xyz/v1/chat/completions"
+            model = "meta-llama/Meta-Llama-3.1-8B-Instruct-Turbo"
+            api_key_env = "ASTERISK_TEST_REGISTRY_API_KEY"
+
+            [profiles.params]
+            temperature = 0.5
+            stop = ["<contd>"]
+
+            [profiles.price]
+            input_per_million = 0.18
+            output_per_million = 0.18
+
+            [[profiles]]
+            name = "local"
+            kind = "ollama"
+            model = "llama3.1"
+            "#,
+        )?;
+
+        let profile = registry.default_profile()?;
+        assert_eq!(profile.name, "together");
+        assert_eq!(profile.kind, ProviderKind::OpenAILike);
+        assert_eq!(profile.params.temperature, Some(0.5));
+        assert_eq!(profile.params.stop, Some(vec!["<contd>".to_string()]));
+
+        let prices = registry.price_table();
+        assert_eq!(
+            prices.get("meta-llama/Meta-Llama-3.1-8B-Instruct-Turbo"),
+            Some(&ModelPrice::new(0.18, 0.18))
+        );
+        assert_eq!(prices.get("llama3.1"), None);
+
+        let json = serde_json::to_string(&registry)?;
+        assert_eq!(ModelRegistry::from_json_str(&json)?, registry);
+
+        assert_eq!(
+            registry.build_default()?.model_id(),
+            "meta-llama/Meta-Llama-3.1-8B-Instruct-Turbo"
+        );
+        assert_eq!(registry.build("local")?.model_id(), "llama3.1");
+        assert!(profile.ollama_admin().is_none());
+        assert_eq!(
+            registry
+                .profile("local")?
+                .ollama_admin()
+                .unwrap()
+                .get_base_url(),
+            ollama::OLLAMA_HOST_URL
+        );
+        assert!(matches!(
+            registry.build("missing"),
+            Err(ModelError::ProfileNotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_registry_default_profiles() -> anyhow::Result<()> {
+        let registry = ModelRegistry::default();
+        assert!(registry.default_profile().is_ok());
+
+        let profile = registry.profile("gpt-4o")?.clone();
+        let profile = profile.with_params(SamplingParams {
+            stop: Some(vec!["<contd>".to_string()]),
+            ..Default::default()
+        });
+
+        assert_eq!(profile.params.temperature, Some(0.0));
+        assert_eq!(profile.params.stop, Some(vec!["<contd>".to_string()]));
+
+        let profile = ModelProfile {
+            api_key_env: Some("ASTERISK_UNSET_API_KEY".to_string()),
+            ..profile
+        };
+        assert!(matches!(
+            profile.build(),
+            Err(ModelError::EnvVarNotFound(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,794 @@
+//! Retry and backoff handling for model API calls.
+
+use std::{
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{ready, Stream, StreamExt};
+use reqwest::{header::HeaderMap, RequestBuilder, Response, StatusCode};
+use reqwest_eventsource::{retry::Never, Error, Event, EventSource, RequestBuilderExt};
+use serde::de::DeserializeOwned;
+use tokio::time::{Instant, Sleep};
+use tracing::warn;
+
+use super::{ModelError, ModelResult, ResponseStreamError, StatusError};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The default number of times a failed request is retried.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// The default delay before the first retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The default upper bound on the delay between retries.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The default per-request timeout.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// `RetryPolicy` decides whether and when a failed model API call is sent again.
+///
+/// Delays grow exponentially from `initial_backoff` up to `max_backoff`, with jitter applied so
+/// concurrent callers don't retry in lockstep. A delay requested by the server through the
+/// `Retry-After` or `x-ratelimit-reset-*` headers takes precedence over the computed backoff, as
+/// long as it is no longer than `max_backoff`; otherwise the request fails right away with the
+/// requested delay in the error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt.
+    pub max_retries: u32,
+
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+
+    /// The upper bound on the delay between retries, whether computed or requested by the server.
+    pub max_backoff: Duration,
+
+    /// The factor the delay is multiplied by after each retry.
+    pub multiplier: f64,
+
+    /// Whether to randomize the delay between half and all of the computed backoff.
+    pub jitter: bool,
+
+    /// The timeout for a single attempt.
+    ///
+    /// For streaming calls this is the longest the stream may go without receiving an event.
+    pub timeout: Option<Duration>,
+}
+
+/// A server-sent event source that reconnects according to a `RetryPolicy` when the connection
+/// fails before any message has arrived, and yields the data of each message.
+///
+/// Once a message has been received the stream is never reconnected, since the server would start
+/// the completion over and the caller would see duplicated output.
+pub(crate) struct RetryEventSource {
+    stream: EventSource,
+    request: RequestBuilder,
+    policy: RetryPolicy,
+    attempt: u32,
+    started: bool,
+    ends_on_close: bool,
+    parse_error: fn(ModelError) -> ModelError,
+    delay: Option<Pin<Box<Sleep>>>,
+    idle: Option<Pin<Box<Sleep>>>,
+    failed: Option<Pin<Box<dyn Future<Output = StatusError> + Send>>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl RetryPolicy {
+    /// Creates a policy that never retries but still applies the default timeout.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the maximum number of retries.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before the first retry and the upper bound on the delay between retries.
+    pub fn backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets whether the delay between retries is randomized.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the timeout for a single attempt.
+    pub fn timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Returns the delay to wait before retrying after `error` on the given zero-based `attempt`,
+    /// or `None` if the error should not be retried.
+    ///
+    /// An error whose server asked for a longer wait than `max_backoff` is not retried, so the
+    /// caller isn't kept waiting; the requested delay is left in the error.
+    pub fn delay_for(&self, error: &ModelError, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_retries || !error.is_retryable() {
+            return None;
+        }
+
+        match error.retry_after() {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(self.backoff_for(attempt)),
+        }
+    }
+
+    /// Returns the computed backoff for the given zero-based `attempt`.
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .mul_f64(self.multiplier.max(1.0).powi(attempt as i32))
+            .min(self.max_backoff);
+
+        if self.jitter {
+            backoff.mul_f64(0.5 + random_unit() / 2.0)
+        } else {
+            backoff
+        }
+    }
+
+    /// Sends the request, retrying it according to the policy.
+    ///
+    /// Responses with an unsuccessful status are turned into `ModelError::StatusError`, so the
+    /// returned response always has a successful status. Backends turn it into their own error
+    /// with `parse_error_body`.
+    pub async fn send(&self, request: RequestBuilder) -> ModelResult<Response> {
+        self.send_with_retries(request, false).await
+    }
+
+    /// Sends a request whose response body is streamed, retrying it according to the policy.
+    ///
+    /// Unlike `send`, the timeout only applies to receiving the response headers, so it doesn't
+    /// cut off a long response midway.
+    pub async fn send_streaming(&self, request: RequestBuilder) -> ModelResult<Response> {
+        self.send_with_retries(request, true).await
+    }
+
+    async fn send_with_retries(
+        &self,
+        request: RequestBuilder,
+        streaming: bool,
+    ) -> ModelResult<Response> {
+        let mut attempt = 0;
+        loop {
+            let mut current = request
+                .try_clone()
+                .expect("model requests have a buffered body");
+
+            let response = match self.timeout {
+                Some(timeout) if streaming => tokio::time::timeout(timeout, current.send())
+                    .await
+                    .map_err(|_| ModelError::Timeout(timeout)),
+                Some(timeout) => {
+                    current = current.timeout(timeout);
+                    Ok(current.send().await)
+                }
+                None => Ok(current.send().await),
+            };
+
+            let error = match response {
+                Ok(Ok(response)) if response.status().is_success() => return Ok(response),
+                Ok(Ok(response)) => StatusError::from_response(response).await.into(),
+                Ok(Err(error)) => ModelError::from(error),
+                Err(error) => error,
+            };
+
+            let Some(delay) = self.delay_for(&error, attempt) else {
+                return Err(error);
+            };
+
+            warn!("retrying model request in {delay:?} after error: {error}");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl RetryEventSource {
+    /// Creates a new event source for the given request.
+    pub(crate) fn new(request: RequestBuilder, policy: RetryPolicy) -> Self {
+        Self {
+            stream: Self::connect(&request),
+            idle: policy
+                .timeout
+                .map(|timeout| Box::pin(tokio::time::sleep(timeout))),
+            request,
+            policy,
+            attempt: 0,
+            started: false,
+            ends_on_close: false,
+            parse_error: |error| error,
+            delay: None,
+            failed: None,
+        }
+    }
+
+    /// Treats the server closing the connection after the first event as the end of the stream,
+    /// for APIs that send no final event.
+    ///
+    /// Otherwise a stream cut off midway fails with `ResponseStreamError`, so a truncated
+    /// response isn't mistaken for a whole one.
+    pub(crate) fn ends_on_close(mut self) -> Self {
+        self.ends_on_close = true;
+        self
+    }
+
+    /// Turns the body of an unsuccessful response into the backend's error `E` where it can, as
+    /// `parse_error_body` does.
+    pub(crate) fn error_body<E>(mut self) -> Self
+    where
+        E: DeserializeOwned + Into<ModelError>,
+    {
+        self.parse_error = parse_error_body::<E>;
+        self
+    }
+
+    fn connect(request: &RequestBuilder) -> EventSource {
+        let mut stream = request
+            .try_clone()
+            .expect("model requests have a buffered body")
+            .eventsource()
+            .expect("model requests have a buffered body");
+
+        // Reconnecting is handled here so it can be stopped once the completion has started.
+        stream.set_retry_policy(Box::new(Never));
+        stream
+    }
+
+    fn reset_idle(&mut self) {
+        if let (Some(idle), Some(timeout)) = (self.idle.as_mut(), self.policy.timeout) {
+            idle.as_mut().reset(Instant::now() + timeout);
+        }
+    }
+
+    /// Reads the body of an unsuccessful response into a `StatusError`, giving up on the body if
+    /// it doesn't arrive within the timeout.
+    fn read_status_error(&self, response: Response) -> impl Future<Output = StatusError> + Send {
+        let timeout = self.policy.timeout;
+        async move {
+            let headers_only = StatusError::from_parts(response.status(), response.headers());
+            let error = StatusError::from_response(response);
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, error)
+                    .await
+                    .unwrap_or(headers_only),
+                None => error.await,
+            }
+        }
+    }
+}
+
+impl StatusError {
+    /// Creates a `StatusError` from an unsuccessful response, consuming its body.
+    pub async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let retry_after = parse_retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+
+        Self {
+            status,
+            retry_after,
+            message: extract_error_message(&body)
+                .unwrap_or_else(|| status.canonical_reason().unwrap_or_default().to_string()),
+            body,
+        }
+    }
+
+    /// Creates a `StatusError` from the status and headers of a response whose body is not
+    /// available.
+    pub fn from_parts(status: StatusCode, headers: &HeaderMap) -> Self {
+        Self {
+            status,
+            retry_after: parse_retry_after(headers),
+            message: status.canonical_reason().unwrap_or_default().to_string(),
+            body: String::new(),
+        }
+    }
+
+    /// Whether the status indicates a transient failure worth retrying.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.status,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::CONFLICT | StatusCode::TOO_MANY_REQUESTS
+        ) || self.status.is_server_error()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Parses how long the server asks the client to wait before retrying.
+///
+/// Checks `retry-after-ms`, then `Retry-After` (in seconds or as an HTTP date), then the
+/// `x-ratelimit-reset-*` headers of whichever limit has been exhausted.
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(millis) = header("retry-after-ms").and_then(|value| value.trim().parse().ok()) {
+        return Some(Duration::from_millis(millis));
+    }
+
+    if let Some(value) = header("retry-after") {
+        if let Ok(seconds) = value.trim().parse::<f64>() {
+            return Some(Duration::from_secs_f64(seconds.max(0.0)));
+        }
+
+        if let Some(date) = parse_http_date(value) {
+            return Some(date.duration_since(SystemTime::now()).unwrap_or_default());
+        }
+    }
+
+    ["requests", "tokens"]
+        .into_iter()
+        .filter(|limit| header(&format!("x-ratelimit-remaining-{limit}")) == Some("0"))
+        .filter_map(|limit| header(&format!("x-ratelimit-reset-{limit}")))
+        .filter_map(parse_reset_duration)
+        .max()
+}
+
+/// Parses a rate limit reset duration such as `1s`, `6m0s`, `20ms` or `2m59.56s`. A bare number is
+/// read as seconds.
+fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(seconds.max(0.0)));
+    }
+
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(split);
+        let number: f64 = number.parse().ok()?;
+
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        total += match unit {
+            "h" => number * 3600.0,
+            "m" => number * 60.0,
+            "s" => number,
+            "ms" => number / 1000.0,
+            _ => return None,
+        };
+
+        rest = tail;
+    }
+
+    Some(Duration::from_secs_f64(total))
+}
+
+/// Parses an HTTP date such as `Wed, 21 Oct 2015 07:28:00 GMT`. Only the IMF-fixdate format is
+/// understood, since it is the one servers are required to send.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u64 + 1;
+    let year: u64 = year.parse().ok()?;
+    let time = time
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let [hours, minutes, seconds] = time[..] else {
+        return None;
+    };
+
+    // Count the days since the epoch with years starting in March, so leap days come last.
+    let year = if month <= 2 { year - 1 } else { year };
+    let (era, year_of_era) = (year / 400, year % 400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Turns a `StatusError` whose body is the backend's error `E` into that error, so the caller
+/// gets the details the API sent. Any other error is returned as it is.
+///
+/// This is done once retrying is over, since only a `StatusError` tells whether it is worth
+/// retrying.
+pub(crate) fn parse_error_body<E>(error: ModelError) -> ModelError
+where
+    E: DeserializeOwned + Into<ModelError>,
+{
+    match error {
+        ModelError::StatusError(status) => match serde_json::from_str::<E>(&status.body) {
+            Ok(error) => error.into(),
+            Err(_) => ModelError::StatusError(status),
+        },
+        error => error,
+    }
+}
+
+/// Pulls a human readable message out of an error body, which is `{"error": {"message": ..}}` for
+/// OpenAI-compatible APIs and `{"error": ".."}` for Ollama.
+fn extract_error_message(body: &str) -> Option<String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return None;
+    }
+
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(body) else {
+        return Some(body.to_string());
+    };
+
+    let message = value
+        .pointer("/error/message")
+        .or_else(|| value.get("error"))
+        .or_else(|| value.get("message"))
+        .and_then(|message| message.as_str());
+
+    Some(message.unwrap_or(body).to_string())
+}
+
+/// Returns a random number in `[0, 1)`.
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64,
+    );
+
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl Stream for RetryEventSource {
+    type Item = ModelResult<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(failed) = this.failed.as_mut() {
+                let error = ready!(failed.as_mut().poll(cx));
+                this.failed = None;
+                return Poll::Ready(Some(Err((this.parse_error)(error.into()))));
+            }
+
+            if let Some(delay) = this.delay.as_mut() {
+                ready!(delay.as_mut().poll(cx));
+                this.delay = None;
+                this.stream = Self::connect(&this.request);
+                this.reset_idle();
+            }
+
+            let mut failed_response = None;
+            let event = match this.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Err(Error::StreamEnded)))
+                    if this.started && this.ends_on_close =>
+                {
+                    this.stream.close();
+                    this.idle = None;
+                    return Poll::Ready(None);
+                }
+                Poll::Ready(Some(Err(Error::InvalidStatusCode(status, response)))) => {
+                    let error = StatusError::from_parts(status, response.headers());
+                    failed_response = Some(response);
+                    Some(Err(error.into()))
+                }
+                Poll::Ready(event) => event
+                    .map(|event| event.map_err(|error| ResponseStreamError::from(error).into())),
+                Poll::Pending => {
+                    let timed_out = match this.idle.as_mut() {
+                        Some(idle) => idle.as_mut().poll(cx).is_ready(),
+                        None => false,
+                    };
+
+                    if !timed_out {
+                        return Poll::Pending;
+                    }
+
+                    let timeout = this.policy.timeout.unwrap_or_default();
+                    Some(Err(ModelError::Timeout(timeout)))
+                }
+            };
+
+            this.reset_idle();
+            match event {
+                Some(Ok(Event::Open)) => continue,
+                Some(Ok(Event::Message(message))) => {
+                    this.started = true;
+                    return Poll::Ready(Some(Ok(message.data)));
+                }
+                Some(Err(error)) => {
+                    this.stream.close();
+                    if !this.started {
+                        if let Some(delay) = this.policy.delay_for(&error, this.attempt) {
+                            warn!("reconnecting model stream in {delay:?} after error: {error}");
+                            this.attempt += 1;
+                            this.delay = Some(Box::pin(tokio::time::sleep(delay)));
+                            continue;
+                        }
+                    }
+
+                    this.idle = None;
+
+                    // Read the body of an unsuccessful response for what went wrong.
+                    if let Some(response) = failed_response {
+                        this.failed = Some(Box::pin(this.read_status_error(response)));
+                        continue;
+                    }
+
+                    return Poll::Ready(Some(Err((this.parse_error)(error))));
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            multiplier: 2.0,
+            jitter: true,
+            timeout: Some(DEFAULT_TIMEOUT),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use reqwest::header::HeaderValue;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::models::openai;
+
+    use super::*;
+
+    /// Starts a local HTTP server that answers the n-th connection with the n-th raw response,
+    /// repeating the last one, and returns its URL along with a count of the requests served.
+    pub(crate) async fn mock_server(responses: Vec<String>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+
+        let served = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let index = served.fetch_add(1, Ordering::SeqCst);
+                let response = responses[index.min(responses.len() - 1)].clone();
+
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                loop {
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    request.extend_from_slice(&buffer[..read]);
+                    if read == 0 || request_complete(&request) {
+                        break;
+                    }
+                }
+
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        (url, hits)
+    }
+
+    /// Builds a raw HTTP response.
+    pub(crate) fn http_response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+        let mut response = format!("HTTP/1.1 {status}\r\nconnection: close\r\n");
+        for (name, value) in headers {
+            response.push_str(&format!("{name}: {value}\r\n"));
+        }
+
+        response.push_str(&format!("content-length: {}\r\n\r\n{body}", body.len()));
+        response
+    }
+
+    fn request_complete(request: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(request);
+        let Some(head_end) = text.find("\r\n\r\n") else {
+            return false;
+        };
+
+        let content_length = text[..head_end]
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+
+        request.len() >= head_end + 4 + content_length
+    }
+
+    #[test]
+    fn test_retry_parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(
+            "x-ratelimit-remaining-requests",
+            HeaderValue::from_static("0"),
+        );
+        headers.insert(
+            "x-ratelimit-reset-requests",
+            HeaderValue::from_static("6m0s"),
+        );
+        headers.insert(
+            "x-ratelimit-remaining-tokens",
+            HeaderValue::from_static("10"),
+        );
+        headers.insert("x-ratelimit-reset-tokens", HeaderValue::from_static("20ms"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(360)));
+
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(parse_retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert("retry-after", HeaderValue::from_static("2"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(2)));
+
+        headers.insert("retry-after-ms", HeaderValue::from_static("150"));
+        assert_eq!(
+            parse_retry_after(&headers),
+            Some(Duration::from_millis(150))
+        );
+
+        assert_eq!(
+            parse_reset_duration("2m59.5s"),
+            Some(Duration::from_secs_f64(179.5))
+        );
+        assert_eq!(parse_reset_duration("soon"), None);
+
+        assert_eq!(
+            parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_445_412_480))
+        );
+        assert_eq!(
+            parse_http_date("Thu, 29 Feb 2024 23:59:59 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_709_251_199))
+        );
+        assert_eq!(parse_http_date("Wednesday, 21-Oct-15 07:28:00 GMT"), None);
+    }
+
+    #[test]
+    fn test_retry_policy_delays() {
+        let policy = RetryPolicy::default().jitter(false);
+        assert_eq!(policy.backoff_for(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff_for(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff_for(20), DEFAULT_MAX_BACKOFF);
+
+        let rate_limited = ModelError::from(StatusError {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            retry_after: Some(Duration::from_secs(7)),
+            message: "slow down".to_string(),
+            body: String::new(),
+        });
+        let bad_request = ModelError::from(StatusError {
+            status: StatusCode::BAD_REQUEST,
+            retry_after: None,
+            message: "bad request".to_string(),
+            body: String::new(),
+        });
+        let rate_limited_for_long = ModelError::from(StatusError {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            retry_after: Some(Duration::from_secs(600)),
+            message: "slow down".to_string(),
+            body: String::new(),
+        });
+
+        assert!(rate_limited.is_retryable());
+        assert!(!bad_request.is_retryable());
+        assert_eq!(
+            policy.delay_for(&rate_limited, 0),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(policy.delay_for(&rate_limited, 3), None);
+        assert_eq!(policy.delay_for(&bad_request, 0), None);
+        assert_eq!(policy.delay_for(&rate_limited_for_long, 0), None);
+
+        let jittered = RetryPolicy::default().backoff_for(1);
+        assert!(jittered >= Duration::from_millis(500) && jittered <= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_send() {
+        let (url, hits) = mock_server(vec![
+            http_response("503 Service Unavailable", &[("retry-after", "0")], ""),
+            http_response(
+                "429 Too Many Requests",
+                &[("retry-after-ms", "10")],
+                r#"{"error":{"message":"Rate limit reached"}}"#,
+            ),
+            http_response("200 OK", &[], "ok"),
+        ])
+        .await;
+
+        let response = RetryPolicy::default()
+            .send(reqwest::Client::new().post(&url).body("{}"))
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let (url, hits) = mock_server(vec![http_response(
+            "401 Unauthorized",
+            &[],
+            r#"{"error":{"message":"Invalid API key","type":"invalid_request_error"}}"#,
+        )])
+        .await;
+
+        let error = RetryPolicy::default()
+            .send(reqwest::Client::new().post(&url).body("{}"))
+            .await
+            .unwrap_err();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        let ModelError::StatusError(error) = error else {
+            panic!("expected a status error, got {error:?}");
+        };
+        assert_eq!(error.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(error.message, "Invalid API key");
+
+        let error = parse_error_body::<openai::ResponseError>(error.into());
+        let ModelError::OpenAIResponseError(error) = error else {
+            panic!("expected an OpenAI error, got {error:?}");
+        };
+        assert_eq!(error.error.message, "Invalid API key");
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+
+use futures::{stream::BoxStream, Stream, StreamExt};
+
+use super::{AssistantMessage, ModelResult, ToolCall};
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// An event in a streamed model response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// A piece of the message content.
+    ContentDelta(String),
+
+    /// A piece of the model's refusal message.
+    RefusalDelta(String),
+
+    /// A piece of a tool call. Pieces with the same `index` belong to the same tool call.
+    ToolCallDelta(ToolCallDelta),
+
+    /// The reason the model stopped generating tokens.
+    FinishReason(String),
+
+    /// The token usage of the whole response, sent at the end of the stream.
+    Usage(TokenUsage),
+}
+
+/// A piece of a tool call made by the model.
+///
+/// The first piece of a tool call carries its `id` and `name`, and the `arguments` of all the
+/// pieces concatenate into the full JSON arguments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolCallDelta {
+    /// The position of the tool call among the tool calls of the response.
+    pub index: usize,
+
+    /// The ID of the tool call.
+    pub id: Option<String>,
+
+    /// The name of the function to call.
+    pub name: Option<String>,
+
+    /// A piece of the arguments to call the function with.
+    pub arguments: String,
+}
+
+/// Token usage of a model response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    /// Number of tokens in the prompt.
+    pub prompt_tokens: u64,
+
+    /// Number of tokens in the generated completion.
+    pub completion_tokens: u64,
+
+    /// Total number of tokens used (prompt + completion).
+    pub total_tokens: u64,
+}
+
+/// Folds stream events back into a whole response.
+#[derive(Debug, Clone, Default)]
+pub struct StreamAccumulator {
+    content: String,
+    refusal: Option<String>,
+    tool_calls: BTreeMap<usize, ToolCall>,
+    finish_reason: Option<String>,
+    usage: Option<TokenUsage>,
+}
+
+/// A streamed response folded back together by a `StreamAccumulator`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamedResponse {
+    /// The message generated by the model.
+    pub message: AssistantMessage,
+
+    /// The reason the model stopped generating tokens, if it was sent.
+    pub finish_reason: Option<String>,
+
+    /// The token usage of the response, if it was sent.
+    pub usage: Option<TokenUsage>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl StreamEvent {
+    /// Gets the content delta, if this is one.
+    pub fn content(&self) -> Option<&str> {
+        match self {
+            StreamEvent::ContentDelta(content) => Some(content),
+            _ => None,
+        }
+    }
+}
+
+impl TokenUsage {
+    /// Creates a new `TokenUsage`, deriving the total.
+    pub fn new(prompt_tokens: u64, completion_tokens: u64) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+}
+
+impl StreamAccumulator {
+    /// Creates a new, empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an event to the response.
+    pub fn push(&mut self, event: StreamEvent) {
+        match event {
+            StreamEvent::ContentDelta(content) => self.content.push_str(&content),
+            StreamEvent::RefusalDelta(refusal) => self
+                .refusal
+                .get_or_insert_with(String::new)
+                .push_str(&refusal),
+            StreamEvent::ToolCallDelta(delta) => {
+                let tool_call = self.tool_calls.entry(delta.index).or_default();
+                if let Some(id) = delta.id {
+                    tool_call.id = id;
+                }
+                if let Some(name) = delta.name {
+                    tool_call.name.push_str(&name);
+                }
+                tool_call.arguments.push_str(&delta.arguments);
+            }
+            StreamEvent::FinishReason(reason) => self.finish_reason = Some(reason),
+            StreamEvent::Usage(usage) => self.usage = Some(usage),
+        }
+    }
+
+    /// Returns the response accumulated so far.
+    pub fn finish(self) -> StreamedResponse {
+        let content = (!self.content.is_empty()).then_some(self.content);
+        let message = AssistantMessage {
+            content,
+            name: None,
+            refusal: self.refusal,
+            tool_calls: self.tool_calls.into_values().collect(),
+        };
+
+        StreamedResponse {
+            message,
+            finish_reason: self.finish_reason,
+            usage: self.usage,
+        }
+    }
+
+    /// Consumes a stream of events and folds them into a whole response.
+    pub async fn collect(
+        mut stream: impl Stream<Item = ModelResult<StreamEvent>> + Unpin,
+    ) -> ModelResult<StreamedResponse> {
+        let mut accumulator = Self::new();
+        while let Some(event) = stream.next().await {
+            accumulator.push(event?);
+        }
+
+        Ok(accumulator.finish())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Flattens a stream of per-chunk event batches into a stream of events.
+pub(crate) fn flatten_events<S>(stream: S) -> BoxStream<'static, ModelResult<StreamEvent>>
+where
+    S: Stream<Item = ModelResult<Vec<StreamEvent>>> + Send + 'static,
+{
+    let stream = stream.flat_map(|events| {
+        let events = match events {
+            Ok(events) => events.into_iter().map(Ok).collect(),
+            Err(error) => vec![Err(error)],
+        };
+
+        futures::stream::iter(events)
+    });
+
+    Box::pin(stream)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stream_accumulator_folds_events() {
+        let delta = |index, id: Option<&str>, name: Option<&str>, arguments: &str| {
+            StreamEvent::ToolCallDelta(ToolCallDelta {
+                index,
+                id: id.map(String::from),
+                name: name.map(String::from),
+                arguments: arguments.to_string(),
+            })
+        };
+
+        let events = vec![
+            StreamEvent::ContentDelta("Let me ".to_string()),
+            StreamEvent::ContentDelta("check.".to_string()),
+            delta(0, Some("call_a"), Some("get_weather"), ""),
+            delta(1, Some("call_b"), Some("get_time"), "{\"tz\":"),
+            delta(0, None, None, "{\"city\":\"Tokyo\"}"),
+            delta(1, None, None, "\"JST\"}"),
+            StreamEvent::FinishReason("tool_calls".to_string()),
+            StreamEvent::Usage(TokenUsage::new(20, 12)),
+        ];
+
+        let response =
+            StreamAccumulator::collect(futures::stream::iter(events.into_iter().map(Ok)))
+                .await
+                .unwrap();
+
+        assert_eq!(response.message.content.as_deref(), Some("Let me check."));
+        assert_eq!(
+            response.message.tool_calls,
+            vec![
+                ToolCall {
+                    id: "call_a".to_string(),
+                    name: "get_weather".to_string(),
+                    arguments: "{\"city\":\"Tokyo\"}".to_string(),
+                },
+                ToolCall {
+                    id: "call_b".to_string(),
+                    name: "get_time".to_string(),
+                    arguments: "{\"tz\":\"JST\"}".to_string(),
+                },
+            ]
+        );
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(response.usage.unwrap().total_tokens, 32);
+    }
+}
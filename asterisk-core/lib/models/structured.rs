@@ -0,0 +1,364 @@
+use std::future::Future;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::tools;
+
+use super::{ChatModel, ModelError, ModelResult, Prompt, PromptMessage, TokenUsage};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The number of times `prompt_structured` asks the model to fix a reply that doesn't match the
+/// schema before giving up.
+pub const DEFAULT_REPAIR_ATTEMPTS: usize = 1;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The JSON schema a structured reply must match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputSchema {
+    /// The name of the schema, made of letters, digits, underscores and dashes.
+    pub name: String,
+
+    /// The JSON schema itself.
+    pub schema: Value,
+}
+
+/// A structured reply along with the token usage of all the calls it took.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredResponse<T> {
+    /// The value the reply was parsed into.
+    pub value: T,
+
+    /// The ID of the model that generated the reply, as reported by the provider.
+    pub model: String,
+
+    /// The token usage of all the calls, if the provider reported it.
+    pub usage: Option<TokenUsage>,
+
+    /// The number of times the model was asked to fix its reply.
+    pub repairs: usize,
+}
+
+/// Error that occurs when a model's reply is not JSON matching the requested schema.
+#[derive(Debug, Clone, Error)]
+#[error("reply does not match the `{schema}` schema: {}", errors.join("; "))]
+pub struct StructuredOutputError {
+    /// The name of the schema the reply was checked against.
+    pub schema: String,
+
+    /// What is wrong with the reply.
+    pub errors: Vec<String>,
+
+    /// The reply as the model sent it.
+    pub output: String,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Traits
+//--------------------------------------------------------------------------------------------------
+
+/// A trait for chat models that can reply with a value of a Rust type.
+///
+/// The JSON schema of the type is derived with `schemars`. Backends whose API can constrain the
+/// output to a schema do so through `ChatModel::chat_with_schema`; the others are asked for it in
+/// the prompt. Either way the reply is validated against the schema before it is parsed.
+pub trait StructuredModel: ChatModel {
+    /// Sends messages to the model and parses the reply into a `T`, asking the model to fix an
+    /// invalid reply up to `DEFAULT_REPAIR_ATTEMPTS` times.
+    fn prompt_structured<T>(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> impl Future<Output = ModelResult<T>> + Send
+    where
+        T: DeserializeOwned + JsonSchema + Send,
+    {
+        let future = self.prompt_structured_with_usage(prompt, DEFAULT_REPAIR_ATTEMPTS);
+        async move { Ok(future.await?.value) }
+    }
+
+    /// Sends messages to the model and parses the reply into a `T`, asking the model to fix an
+    /// invalid reply up to `repair_attempts` times.
+    ///
+    /// Fails with `ModelError::StructuredOutputError` if the last reply is still invalid.
+    fn prompt_structured_with_usage<T>(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+        repair_attempts: usize,
+    ) -> impl Future<Output = ModelResult<StructuredResponse<T>>> + Send
+    where
+        T: DeserializeOwned + JsonSchema + Send,
+    {
+        let mut prompt = prompt.into();
+        async move {
+            let schema = OutputSchema::of::<T>();
+            let validator = jsonschema::draft7::new(&schema.schema)
+                .map_err(|error| ModelError::custom(anyhow::anyhow!("invalid schema: {error}")))?;
+
+            let mut usage: Option<TokenUsage> = None;
+            let mut repairs = 0;
+            loop {
+                let response = self
+                    .chat_with_schema(prompt.clone(), schema.clone())
+                    .await?;
+                usage = match (usage, response.usage) {
+                    (Some(total), Some(usage)) => Some(TokenUsage::new(
+                        total.prompt_tokens + usage.prompt_tokens,
+                        total.completion_tokens + usage.completion_tokens,
+                    )),
+                    (total, usage) => total.or(usage),
+                };
+
+                let errors = match schema.parse(&validator, &response.content) {
+                    Ok(value) => {
+                        return Ok(StructuredResponse {
+                            value,
+                            model: response.model,
+                            usage,
+                            repairs,
+                        })
+                    }
+                    Err(errors) => errors,
+                };
+
+                if repairs == repair_attempts {
+                    return Err(StructuredOutputError {
+                        schema: schema.name,
+                        errors,
+                        output: response.content,
+                    }
+                    .into());
+                }
+
+                repairs += 1;
+                prompt.push(PromptMessage::assistant(response.content));
+                prompt.push(PromptMessage::user(format!(
+                    "Your reply does not match the schema:\n- {}\n\nReply again with only the \
+                     corrected JSON.",
+                    errors.join("\n- ")
+                )));
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl OutputSchema {
+    /// Creates the schema of the given type, named after it.
+    pub fn of<T: JsonSchema>() -> Self {
+        let name = T::schema_name()
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => c,
+                _ => '_',
+            })
+            .collect();
+
+        Self {
+            name,
+            schema: tools::schema_for::<T>(),
+        }
+    }
+
+    /// Adds an instruction to reply with JSON matching the schema to the end of the prompt, for
+    /// backends that can't constrain their output to it.
+    pub fn instruct(&self, mut prompt: Prompt) -> Prompt {
+        prompt.push(PromptMessage::system(format!(
+            "Reply with only a JSON value matching this JSON schema, without any other text:\n{}",
+            self.schema
+        )));
+        prompt
+    }
+
+    /// Validates a reply against the schema and parses it, returning what is wrong with it if
+    /// either fails.
+    fn parse<T: DeserializeOwned>(
+        &self,
+        validator: &jsonschema::Validator,
+        output: &str,
+    ) -> Result<T, Vec<String>> {
+        let value: Value = serde_json::from_str(extract_json(output))
+            .map_err(|error| vec![format!("the reply is not valid JSON: {error}")])?;
+
+        let errors: Vec<String> = validator
+            .iter_errors(&value)
+            .map(|error| match error.instance_path.to_string() {
+                path if path.is_empty() => error.to_string(),
+                path => format!("at `{path}`: {error}"),
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        serde_json::from_value(value).map_err(|error| vec![error.to_string()])
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the JSON in a reply, leaving out a surrounding Markdown code fence or other text.
+fn extract_json(output: &str) -> &str {
+    let output = output.trim();
+    let start = output.find(['{', '[']);
+    let end = output.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start <= end => &output[start..=end],
+        _ => output,
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<M: ChatModel + ?Sized> StructuredModel for M {}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::{future::BoxFuture, stream::BoxStream};
+    use serde::Deserialize;
+
+    use crate::{
+        models::{ModelResponse, StreamEvent},
+        prompt,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct Weather {
+        city: String,
+        celsius: f32,
+    }
+
+    /// A model that sends back the given replies in order and keeps the prompts it got.
+    struct ScriptedModel {
+        replies: Mutex<Vec<&'static str>>,
+        prompts: Mutex<Vec<Prompt>>,
+    }
+
+    impl ScriptedModel {
+        fn new(replies: Vec<&'static str>) -> Self {
+            Self {
+                replies: Mutex::new(replies),
+                prompts: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl ChatModel for ScriptedModel {
+        fn model_id(&self) -> &str {
+            "scripted"
+        }
+
+        fn chat(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<String>> {
+            Box::pin(async move { Ok(self.chat_with_usage(prompt).await?.content) })
+        }
+
+        fn chat_with_usage(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+            self.prompts.lock().unwrap().push(prompt);
+            let content = self.replies.lock().unwrap().remove(0).to_string();
+            Box::pin(async move {
+                Ok(ModelResponse {
+                    content,
+                    model: "scripted".to_string(),
+                    usage: Some(TokenUsage::new(10, 5)),
+                })
+            })
+        }
+
+        fn chat_stream(
+            &self,
+            _prompt: Prompt,
+        ) -> BoxFuture<'_, ModelResult<BoxStream<'static, ModelResult<StreamEvent>>>> {
+            Box::pin(async {
+                Err(ModelError::custom(anyhow::anyhow!(
+                    "the scripted model doesn't stream"
+                )))
+            })
+        }
+    }
+
+    #[test]
+    fn test_structured_output_schema() {
+        let schema = OutputSchema::of::<Vec<Weather>>();
+        assert_eq!(schema.name, "Array_of_Weather");
+        assert_eq!(schema.schema["items"]["required"][0], "celsius");
+
+        assert_eq!(
+            extract_json("```json\n{\"city\": \"Oslo\"}\n```"),
+            "{\"city\": \"Oslo\"}"
+        );
+        assert_eq!(extract_json("no json"), "no json");
+    }
+
+    #[tokio::test]
+    async fn test_structured_prompt_with_repair() -> anyhow::Result<()> {
+        let model = ScriptedModel::new(vec![
+            r#"Sure! {"city": "Oslo"}"#,
+            r#"{"city": "Oslo", "celsius": 4.5}"#,
+        ]);
+
+        let response = model
+            .prompt_structured_with_usage::<Weather>(prompt! { user: "Weather?" }, 1)
+            .await?;
+        assert_eq!(
+            response.value,
+            Weather {
+                city: "Oslo".to_string(),
+                celsius: 4.5
+            }
+        );
+        assert_eq!(response.repairs, 1);
+        assert_eq!(response.usage, Some(TokenUsage::new(20, 10)));
+
+        // The second call carries the invalid reply and what is wrong with it, followed by the
+        // schema instruction the model gets by default.
+        let prompts = model.prompts.lock().unwrap();
+        let repair = prompts[1].clone().into_iter().rev().nth(1).unwrap();
+        let PromptMessage::User(repair) = repair else {
+            panic!("expected a user message, got {repair:?}");
+        };
+        assert!(repair
+            .content
+            .text()
+            .contains("\"celsius\" is a required property"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_prompt_gives_up() {
+        let model = ScriptedModel::new(vec!["not json", r#"{"city": 3}"#]);
+
+        let error = model
+            .prompt_structured_with_usage::<Weather>(prompt! { user: "Weather?" }, 0)
+            .await
+            .unwrap_err();
+        let ModelError::StructuredOutputError(error) = error else {
+            panic!("expected a structured output error, got {error:?}");
+        };
+        assert_eq!(error.schema, "Weather");
+        assert_eq!(error.output, "not json");
+        assert!(error.errors[0].starts_with("the reply is not valid JSON"));
+    }
+}
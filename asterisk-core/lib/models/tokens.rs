@@ -0,0 +1,475 @@
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use tiktoken_rs::CoreBPE;
+
+use super::{openai::ModelType, Prompt, PromptMessage};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The tokens a generic chat template adds around each message, on top of its content.
+pub const TOKENS_PER_MESSAGE: usize = 4;
+
+lazy_static! {
+    static ref O200K_BASE: CoreBPE =
+        tiktoken_rs::o200k_base().expect("the o200k_base encoding is bundled");
+    static ref CL100K_BASE: CoreBPE =
+        tiktoken_rs::cl100k_base().expect("the cl100k_base encoding is bundled");
+}
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The families of models that share a tokenizer, or at least a similar ratio of text to tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFamily {
+    /// OpenAI GPT and o-series models.
+    Gpt,
+
+    /// Anthropic Claude models.
+    Claude,
+
+    /// Google Gemini and Gemma models.
+    Gemini,
+
+    /// Meta Llama models and their derivatives.
+    Llama,
+
+    /// Mistral and Mixtral models.
+    Mistral,
+
+    /// Alibaba Qwen models.
+    Qwen,
+
+    /// Any other model.
+    Other,
+}
+
+/// The way a chat model lays out the messages of a prompt, which costs tokens of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChatTemplate {
+    /// The template of the OpenAI chat models.
+    ///
+    /// Each message is wrapped in `<|start|>{role}<|message|>...<|end|>` and the reply is primed
+    /// with `<|start|>assistant<|message|>`.
+    OpenAI,
+
+    /// The template of Llama 3 and 3.1.
+    ///
+    /// The prompt starts with `<|begin_of_text|>`, each message is wrapped in
+    /// `<|start_header_id|>{role}<|end_header_id|>\n\n...<|eot_id|>` and the reply is primed with
+    /// an assistant header.
+    Llama3,
+
+    /// A template of unknown layout, assumed to cost `TOKENS_PER_MESSAGE` per message.
+    #[default]
+    Generic,
+}
+
+/// The byte pair encodings the tokens can be counted with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// The encoding of GPT-4o and the o-series models.
+    O200kBase,
+
+    /// The encoding of GPT-4 and GPT-3.5.
+    ///
+    /// The Llama 3 tokenizer adds 28k tokens of its own to it, so Llama 3 counts made with it are
+    /// only an estimate.
+    Cl100kBase,
+}
+
+/// Counts tokens with a tiktoken byte pair encoding, exactly for the models that use it.
+///
+/// Counts for other models made with a close encoding, such as `TiktokenCounter::llama3_estimate`,
+/// are only estimates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TiktokenCounter {
+    encoding: Encoding,
+    template: ChatTemplate,
+    exact: bool,
+}
+
+/// Estimates the number of tokens in a text from its length.
+///
+/// It is only an estimate, but a cheap one that is close enough to decide when a prompt is getting
+/// too long.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EstimatingCounter {
+    /// The average number of characters in a token.
+    chars_per_token: f32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Traits
+//--------------------------------------------------------------------------------------------------
+
+/// A trait for counting the tokens a model sees in a text or prompt.
+pub trait TokenCounter: Send + Sync {
+    /// Returns the number of tokens in the text.
+    fn count(&self, text: &str) -> usize;
+
+    /// Returns the chat template the prompts are laid out with.
+    fn template(&self) -> ChatTemplate {
+        ChatTemplate::Generic
+    }
+
+    /// Whether the counts are exact rather than estimates, which may fall short of the real count
+    /// and call for a margin when planning around a model's context window.
+    fn is_exact(&self) -> bool {
+        false
+    }
+
+    /// Returns the number of tokens in a message, including the overhead of the chat template.
+    fn count_message(&self, message: &PromptMessage) -> usize {
+        let content = match message {
+            PromptMessage::System(message) => self.count(&message.content),
+            PromptMessage::User(message) => self.count(&message.content.text()),
+            PromptMessage::Assistant(message) => {
+                message
+                    .content
+                    .as_deref()
+                    .map_or(0, |content| self.count(content))
+                    + message
+                        .tool_calls
+                        .iter()
+                        .map(|call| self.count(&call.name) + self.count(&call.arguments))
+                        .sum::<usize>()
+            }
+            PromptMessage::Tool(message) => self.count(&message.content),
+        };
+
+        content + self.template().message_overhead()
+    }
+
+    /// Returns the number of tokens in a prompt, including the overhead of the chat template.
+    fn count_prompt(&self, prompt: &Prompt) -> usize {
+        prompt
+            .clone()
+            .into_iter()
+            .map(|message| self.count_message(&message))
+            .sum::<usize>()
+            + self.template().prompt_overhead()
+    }
+
+    /// Returns the longest start of the text that has at most `max_tokens` tokens.
+    fn truncate<'a>(&self, text: &'a str, max_tokens: usize) -> &'a str {
+        if self.count(text) <= max_tokens {
+            return text;
+        }
+
+        // Binary search over the character boundaries for the longest start that fits.
+        let boundaries = text
+            .char_indices()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let (mut low, mut high) = (0, boundaries.len());
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            if self.count(&text[..boundaries[mid - 1]]) <= max_tokens {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        match low {
+            0 => "",
+            low => &text[..boundaries[low - 1]],
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ModelFamily {
+    /// Guesses the family of a model from its ID, e.g. `gpt-4o-mini` or `llama3.1:8b`.
+    pub fn of(model_id: &str) -> Self {
+        // Model IDs may be namespaced, e.g. `meta-llama/Llama-3-8b` or `openai/gpt-4o`.
+        let name = model_id
+            .rsplit('/')
+            .next()
+            .unwrap_or(model_id)
+            .to_lowercase();
+
+        if name.starts_with("gpt") || name.starts_with("chatgpt") || is_o_series(&name) {
+            ModelFamily::Gpt
+        } else if name.starts_with("claude") {
+            ModelFamily::Claude
+        } else if name.starts_with("gemini") || name.starts_with("gemma") {
+            ModelFamily::Gemini
+        } else if name.contains("llama") {
+            ModelFamily::Llama
+        } else if name.starts_with("mistral") || name.starts_with("mixtral") {
+            ModelFamily::Mistral
+        } else if name.starts_with("qwen") {
+            ModelFamily::Qwen
+        } else {
+            ModelFamily::Other
+        }
+    }
+
+    /// Returns the number of tokens the models of the family typically fit in their context.
+    pub fn context_window(&self) -> usize {
+        match self {
+            ModelFamily::Gpt | ModelFamily::Llama | ModelFamily::Mistral => 128_000,
+            ModelFamily::Claude => 200_000,
+            ModelFamily::Gemini => 1_000_000,
+            ModelFamily::Qwen => 32_768,
+            ModelFamily::Other => 8_192,
+        }
+    }
+
+    /// Returns the average number of characters in a token of the models of the family.
+    pub fn chars_per_token(&self) -> f32 {
+        match self {
+            ModelFamily::Gpt | ModelFamily::Gemini | ModelFamily::Other => 4.0,
+            ModelFamily::Claude | ModelFamily::Llama | ModelFamily::Qwen => 3.5,
+            ModelFamily::Mistral => 3.2,
+        }
+    }
+}
+
+impl ChatTemplate {
+    /// Returns the tokens the template adds around each message, on top of its content.
+    pub fn message_overhead(&self) -> usize {
+        match self {
+            // `<|start|>`, the role, `<|message|>` and `<|end|>`.
+            ChatTemplate::OpenAI => 4,
+            // `<|start_header_id|>`, the role, `<|end_header_id|>`, `\n\n` and `<|eot_id|>`.
+            ChatTemplate::Llama3 => 5,
+            ChatTemplate::Generic => TOKENS_PER_MESSAGE,
+        }
+    }
+
+    /// Returns the tokens the template adds once per prompt, including the priming of the reply.
+    pub fn prompt_overhead(&self) -> usize {
+        match self {
+            // `<|start|>assistant<|message|>`.
+            ChatTemplate::OpenAI => 3,
+            // `<|begin_of_text|>` and `<|start_header_id|>assistant<|end_header_id|>\n\n`.
+            ChatTemplate::Llama3 => 5,
+            ChatTemplate::Generic => 0,
+        }
+    }
+}
+
+impl Encoding {
+    /// Returns the encoding of the OpenAI model with the given ID.
+    pub fn of_openai_model(model_id: &str) -> Self {
+        let name = model_id.rsplit('/').next().unwrap_or(model_id);
+        if name.starts_with("gpt-4o") || name.starts_with("chatgpt-4o") || is_o_series(name) {
+            Encoding::O200kBase
+        } else {
+            Encoding::Cl100kBase
+        }
+    }
+
+    /// Returns the byte pair encoding, loading it the first time it is used.
+    fn bpe(&self) -> &'static CoreBPE {
+        match self {
+            Encoding::O200kBase => &O200K_BASE,
+            Encoding::Cl100kBase => &CL100K_BASE,
+        }
+    }
+}
+
+impl TiktokenCounter {
+    /// Creates a counter for the given encoding and chat template.
+    pub fn new(encoding: Encoding, template: ChatTemplate) -> Self {
+        Self {
+            encoding,
+            template,
+            exact: true,
+        }
+    }
+
+    /// Creates a counter for the OpenAI model with the given ID.
+    pub fn openai(model_id: &str) -> Self {
+        Self::new(Encoding::of_openai_model(model_id), ChatTemplate::OpenAI)
+    }
+
+    /// Creates a counter for Llama 3 and 3.1 models, which estimates their tokens with the close
+    /// `Cl100kBase` encoding rather than their own.
+    pub fn llama3_estimate() -> Self {
+        Self {
+            exact: false,
+            ..Self::new(Encoding::Cl100kBase, ChatTemplate::Llama3)
+        }
+    }
+
+    /// Returns the encoding the tokens are counted with.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+}
+
+impl EstimatingCounter {
+    /// Creates a counter that assumes a token is `chars_per_token` characters long on average.
+    pub fn new(chars_per_token: f32) -> Self {
+        Self { chars_per_token }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns a counter for the tokens of the model with the given ID.
+///
+/// The tokens of OpenAI models are counted exactly. Those of Llama 3 models are estimated with the
+/// close `Cl100kBase` encoding, and those of other models from the length of the text, so
+/// `TokenCounter::is_exact` tells whether to leave a margin.
+pub fn token_counter(model_id: &str) -> Arc<dyn TokenCounter> {
+    match ModelFamily::of(model_id) {
+        ModelFamily::Gpt => Arc::new(TiktokenCounter::openai(model_id)),
+        ModelFamily::Llama if is_llama3(model_id) => Arc::new(TiktokenCounter::llama3_estimate()),
+        family => Arc::new(EstimatingCounter::new(family.chars_per_token())),
+    }
+}
+
+/// Returns true for the Llama 3 models under the names the providers give them, such as
+/// `llama3.1`, `llama-v3p1-8b-instruct` or `Meta-Llama-3.1-8B-Instruct`.
+fn is_llama3(model_id: &str) -> bool {
+    let name = model_id.to_lowercase();
+    ["llama3", "llama-3", "llama-v3"]
+        .iter()
+        .any(|prefix| name.contains(prefix))
+}
+
+/// Returns true for the OpenAI reasoning models, named `o1`, `o3-mini` and so on.
+fn is_o_series(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl TokenCounter for TiktokenCounter {
+    fn count(&self, text: &str) -> usize {
+        self.encoding.bpe().encode_ordinary(text).len()
+    }
+
+    fn template(&self) -> ChatTemplate {
+        self.template
+    }
+
+    fn is_exact(&self) -> bool {
+        self.exact
+    }
+}
+
+impl From<&ModelType> for TiktokenCounter {
+    fn from(model: &ModelType) -> Self {
+        Self::openai(&model.to_string())
+    }
+}
+
+impl TokenCounter for EstimatingCounter {
+    fn count(&self, text: &str) -> usize {
+        (text.chars().count() as f32 / self.chars_per_token).ceil() as usize
+    }
+}
+
+impl Default for EstimatingCounter {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl<T: TokenCounter + ?Sized> TokenCounter for Arc<T> {
+    fn count(&self, text: &str) -> usize {
+        (**self).count(text)
+    }
+
+    fn template(&self) -> ChatTemplate {
+        (**self).template()
+    }
+
+    fn is_exact(&self) -> bool {
+        (**self).is_exact()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_models_token_counter() {
+        assert_eq!(ModelFamily::of("gpt-4o-mini"), ModelFamily::Gpt);
+        assert_eq!(ModelFamily::of("o3-mini"), ModelFamily::Gpt);
+        assert_eq!(ModelFamily::of("openai/gpt-4o"), ModelFamily::Gpt);
+        assert_eq!(ModelFamily::of("llama3.1:8b"), ModelFamily::Llama);
+        assert_eq!(
+            ModelFamily::of("claude-3-5-sonnet-latest"),
+            ModelFamily::Claude
+        );
+        assert_eq!(ModelFamily::of("phi3:mini"), ModelFamily::Other);
+
+        let counter = EstimatingCounter::default();
+        assert_eq!(counter.count(""), 0);
+        assert_eq!(counter.count("Hello, world"), 3);
+
+        let mut prompt = Prompt::new();
+        prompt.push(PromptMessage::system("Hello, world"));
+        prompt.push(PromptMessage::user("Hi"));
+        assert_eq!(
+            counter.count_prompt(&prompt),
+            3 + 1 + 2 * TOKENS_PER_MESSAGE
+        );
+
+        assert_eq!(counter.truncate("Hello, world", 5), "Hello, world");
+        assert_eq!(counter.truncate("Hello, world", 2), "Hello, w");
+        assert_eq!(counter.truncate("héllo", 1), "héll");
+        assert_eq!(counter.truncate("Hello", 0), "");
+    }
+
+    #[test]
+    fn test_models_tiktoken_counter() {
+        assert_eq!(
+            TiktokenCounter::from(&ModelType::Gpt4oMini).encoding(),
+            Encoding::O200kBase
+        );
+        assert_eq!(
+            TiktokenCounter::from(&ModelType::Gpt4Turbo).encoding(),
+            Encoding::Cl100kBase
+        );
+
+        let counter = TiktokenCounter::openai("gpt-4o");
+        assert_eq!(counter.count("Hello, world!"), 4);
+
+        // Matches the prompt tokens OpenAI reports for the same messages.
+        let mut prompt = Prompt::new();
+        prompt.push(PromptMessage::system("You are a helpful assistant."));
+        prompt.push(PromptMessage::user("Hello, world!"));
+        assert_eq!(counter.count_prompt(&prompt), 6 + 4 + 2 * 4 + 3);
+
+        assert_eq!(
+            token_counter("accounts/fireworks/models/llama-v3p1-8b-instruct").template(),
+            ChatTemplate::Llama3
+        );
+        assert_eq!(
+            token_counter("Meta-Llama-3.1-8B-Instruct").template(),
+            ChatTemplate::Llama3
+        );
+        assert!(!token_counter("Meta-Llama-3.1-8B-Instruct").is_exact());
+        assert!(token_counter("gpt-4o-mini").is_exact());
+        assert_eq!(token_counter("llama2:7b").template(), ChatTemplate::Generic);
+        assert_eq!(
+            token_counter("gpt-4o-mini").template(),
+            ChatTemplate::OpenAI
+        );
+    }
+}
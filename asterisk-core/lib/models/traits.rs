@@ -1,6 +1,10 @@
-use futures::{stream::BoxStream, Future};
+use std::sync::Arc;
 
-use super::{ModelResult, Prompt};
+use futures::{future::BoxFuture, stream::BoxStream, Future};
+
+use super::{
+    EmbeddingResponse, ModelError, ModelResponse, ModelResult, OutputSchema, Prompt, StreamEvent,
+};
 
 //--------------------------------------------------------------------------------------------------
 // Traits
@@ -13,13 +17,154 @@ pub trait TextModel {
         &self,
         prompt: impl Into<Prompt> + Send,
     ) -> impl Future<Output = ModelResult<String>> + Send;
+
+    /// Sends messages to the model and gets a response back along with the token usage of the call.
+    ///
+    /// The response carries the ID of the model that produced it, which usage and prices are keyed
+    /// by, so there is no default.
+    fn prompt_with_usage(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> impl Future<Output = ModelResult<ModelResponse>> + Send;
 }
 
 /// A trait for models that can be used to generate text streams.
 pub trait TextStreamModel {
-    /// Sends messages to the model and gets back a stream of strings as response.
+    /// Sends messages to the model and gets back a stream of events as response.
     fn prompt_stream(
         &self,
         prompt: impl Into<Prompt> + Send,
-    ) -> impl Future<Output = ModelResult<BoxStream<'static, ModelResult<String>>>> + Send;
+    ) -> impl Future<Output = ModelResult<BoxStream<'static, ModelResult<StreamEvent>>>> + Send;
+}
+
+/// An object-safe counterpart of [`TextModel`] and [`TextStreamModel`].
+///
+/// This allows model backends to be chosen at runtime and stored as `Box<dyn ChatModel>` or
+/// `Arc<dyn ChatModel>`, both of which implement [`TextModel`] and [`TextStreamModel`] in turn.
+pub trait ChatModel: Send + Sync {
+    /// Returns the ID of the model.
+    fn model_id(&self) -> &str;
+
+    /// Sends messages to the model and gets a response back.
+    fn chat(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<String>>;
+
+    /// Sends messages to the model and gets a response back along with the token usage of the call.
+    ///
+    /// By default the response comes from `chat` without any usage.
+    fn chat_with_usage(&self, prompt: Prompt) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        Box::pin(async move {
+            Ok(ModelResponse {
+                content: self.chat(prompt).await?,
+                model: self.model_id().to_string(),
+                usage: None,
+            })
+        })
+    }
+
+    /// Sends messages to the model and gets back a stream of events as response.
+    fn chat_stream(
+        &self,
+        prompt: Prompt,
+    ) -> BoxFuture<'_, ModelResult<BoxStream<'static, ModelResult<StreamEvent>>>>;
+
+    /// Sends messages to the model, asking for a reply that is JSON matching the schema.
+    ///
+    /// By default the schema is added to the prompt as an instruction. Backends whose API can
+    /// constrain the output to a schema override this to do so.
+    fn chat_with_schema(
+        &self,
+        prompt: Prompt,
+        schema: OutputSchema,
+    ) -> BoxFuture<'_, ModelResult<ModelResponse>> {
+        self.chat_with_usage(schema.instruct(prompt))
+    }
+}
+
+/// A trait for models that turn text into embedding vectors.
+///
+/// Providers cap how many texts a single request may carry, so `embed_batch` is only ever given up
+/// to `batch_size` texts; `embed_all` splits larger inputs into batches of that size.
+pub trait EmbeddingModel: Send + Sync {
+    /// Returns the ID of the model.
+    fn model_id(&self) -> &str;
+
+    /// Returns the length of the vectors the model produces, if it is known ahead of time.
+    fn dimensions(&self) -> Option<usize>;
+
+    /// Returns the maximum number of texts embedded in a single request.
+    fn batch_size(&self) -> usize;
+
+    /// Embeds up to `batch_size` texts in a single request.
+    fn embed_batch(&self, texts: Vec<String>) -> BoxFuture<'_, ModelResult<EmbeddingResponse>>;
+
+    /// Embeds any number of texts, one request per `batch_size` texts.
+    fn embed_all(&self, texts: Vec<String>) -> BoxFuture<'_, ModelResult<EmbeddingResponse>> {
+        Box::pin(async move {
+            let mut response = EmbeddingResponse::default();
+            for batch in texts.chunks(self.batch_size().max(1)) {
+                response.extend(self.embed_batch(batch.to_vec()).await?);
+            }
+
+            Ok(response)
+        })
+    }
+
+    /// Embeds a single text.
+    fn embed(&self, text: String) -> BoxFuture<'_, ModelResult<Vec<f32>>> {
+        Box::pin(async move {
+            self.embed_batch(vec![text])
+                .await?
+                .embeddings
+                .pop()
+                .ok_or(ModelError::EmptyEmbeddingResponse)
+        })
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl TextModel for Box<dyn ChatModel> {
+    async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
+        self.chat(prompt.into()).await
+    }
+
+    async fn prompt_with_usage(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<ModelResponse> {
+        self.chat_with_usage(prompt.into()).await
+    }
+}
+
+impl TextStreamModel for Box<dyn ChatModel> {
+    async fn prompt_stream(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<BoxStream<'static, ModelResult<StreamEvent>>> {
+        self.chat_stream(prompt.into()).await
+    }
+}
+
+impl TextModel for Arc<dyn ChatModel> {
+    async fn prompt(&self, prompt: impl Into<Prompt> + Send) -> ModelResult<String> {
+        self.chat(prompt.into()).await
+    }
+
+    async fn prompt_with_usage(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<ModelResponse> {
+        self.chat_with_usage(prompt.into()).await
+    }
+}
+
+impl TextStreamModel for Arc<dyn ChatModel> {
+    async fn prompt_stream(
+        &self,
+        prompt: impl Into<Prompt> + Send,
+    ) -> ModelResult<BoxStream<'static, ModelResult<StreamEvent>>> {
+        self.chat_stream(prompt.into()).await
+    }
 }
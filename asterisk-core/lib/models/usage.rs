@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::TokenUsage;
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// The output of a model call along with what it cost to produce.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelResponse {
+    /// The content generated by the model.
+    pub content: String,
+
+    /// The ID of the model that generated the response, as reported by the provider.
+    pub model: String,
+
+    /// The token usage of the call, if the provider reported it.
+    pub usage: Option<TokenUsage>,
+}
+
+/// The price of a model in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// The price per million prompt tokens.
+    pub input_per_million: f64,
+
+    /// The price per million completion tokens.
+    pub output_per_million: f64,
+}
+
+/// Prices of models keyed by model ID.
+///
+/// A model ID without an exact entry uses the price of the longest ID it starts with, so a price
+/// for `gpt-4o-mini` also covers `gpt-4o-mini-2024-07-18`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+/// Token usage and estimated spend added up over a number of calls.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UsageTotals {
+    /// The number of calls.
+    pub calls: u64,
+
+    /// Number of tokens in the prompts.
+    pub prompt_tokens: u64,
+
+    /// Number of tokens in the generated completions.
+    pub completion_tokens: u64,
+
+    /// Total number of tokens used.
+    pub total_tokens: u64,
+
+    /// The estimated spend in US dollars, counting only calls to models with a known price.
+    pub cost: Option<f64>,
+}
+
+/// A single call recorded in a `UsageLedger`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    /// The ID of the thread the call was made for.
+    pub thread_id: String,
+
+    /// The ID of the model that was called.
+    pub model: String,
+
+    /// The token usage of the call.
+    pub usage: TokenUsage,
+
+    /// The estimated cost of the call in US dollars, if the model has a known price.
+    pub cost: Option<f64>,
+}
+
+/// Keeps a running account of token usage and estimated spend, overall, per thread and per model.
+#[derive(Debug, Clone, Default)]
+pub struct UsageLedger {
+    prices: PriceTable,
+    total: UsageTotals,
+    by_thread: HashMap<String, UsageTotals>,
+    by_model: HashMap<String, UsageTotals>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ModelPrice {
+    /// Creates a new price from the prices per million prompt and completion tokens.
+    pub fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+        }
+    }
+
+    /// Returns the cost of the given usage in US dollars.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+impl PriceTable {
+    /// Creates a new, empty price table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the price of a model.
+    pub fn insert(&mut self, model: impl Into<String>, price: ModelPrice) {
+        self.prices.insert(model.into(), price);
+    }
+
+    /// Sets the price of a model and returns the table.
+    pub fn with(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.insert(model, price);
+        self
+    }
+
+    /// Gets the price of a model.
+    pub fn get(&self, model: &str) -> Option<&ModelPrice> {
+        self.prices.get(model).or_else(|| {
+            self.prices
+                .iter()
+                .filter(|(id, _)| model.starts_with(id.as_str()))
+                .max_by_key(|(id, _)| id.len())
+                .map(|(_, price)| price)
+        })
+    }
+
+    /// Returns the estimated cost of the given usage of a model, if the model has a price.
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        self.get(model).map(|price| price.cost(usage))
+    }
+}
+
+impl UsageTotals {
+    /// Adds a call to the totals.
+    pub fn add(&mut self, usage: &TokenUsage, cost: Option<f64>) {
+        self.calls += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.total_tokens += usage.total_tokens;
+        if let Some(cost) = cost {
+            *self.cost.get_or_insert(0.0) += cost;
+        }
+    }
+}
+
+impl UsageLedger {
+    /// Creates a new ledger that prices calls with the given table.
+    pub fn new(prices: PriceTable) -> Self {
+        Self {
+            prices,
+            ..Default::default()
+        }
+    }
+
+    /// Records a call made for a thread and returns the record.
+    pub fn record(
+        &mut self,
+        thread_id: impl Into<String>,
+        model: impl Into<String>,
+        usage: TokenUsage,
+    ) -> UsageRecord {
+        let thread_id = thread_id.into();
+        let model = model.into();
+        let cost = self.prices.cost(&model, &usage);
+
+        self.total.add(&usage, cost);
+        self.by_thread
+            .entry(thread_id.clone())
+            .or_default()
+            .add(&usage, cost);
+        self.by_model
+            .entry(model.clone())
+            .or_default()
+            .add(&usage, cost);
+
+        UsageRecord {
+            thread_id,
+            model,
+            usage,
+            cost,
+        }
+    }
+
+    /// Returns the totals over all recorded calls.
+    pub fn total(&self) -> UsageTotals {
+        self.total
+    }
+
+    /// Returns the totals for a thread.
+    pub fn thread(&self, thread_id: &str) -> UsageTotals {
+        self.by_thread.get(thread_id).copied().unwrap_or_default()
+    }
+
+    /// Returns the totals for a model.
+    pub fn model(&self, model: &str) -> UsageTotals {
+        self.by_model.get(model).copied().unwrap_or_default()
+    }
+
+    /// Returns the totals of every thread.
+    pub fn by_thread(&self) -> &HashMap<String, UsageTotals> {
+        &self.by_thread
+    }
+
+    /// Returns the totals of every model.
+    pub fn by_model(&self) -> &HashMap<String, UsageTotals> {
+        &self.by_model
+    }
+
+    /// Returns the price table the ledger uses.
+    pub fn prices(&self) -> &PriceTable {
+        &self.prices
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl FromIterator<(String, ModelPrice)> for PriceTable {
+    fn from_iter<T: IntoIterator<Item = (String, ModelPrice)>>(iter: T) -> Self {
+        Self {
+            prices: iter.into_iter().collect(),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_ledger_aggregates() {
+        let prices = PriceTable::new()
+            .with("gpt-4o-mini", ModelPrice::new(0.15, 0.60))
+            .with("gpt-4o", ModelPrice::new(2.50, 10.00));
+        assert_eq!(
+            prices.get("gpt-4o-mini-2024-07-18"),
+            Some(&ModelPrice::new(0.15, 0.60))
+        );
+        assert_eq!(prices.get("llama-3.1-8b"), None);
+
+        let mut ledger = UsageLedger::new(prices);
+        let record = ledger.record(
+            "thread-a",
+            "gpt-4o-2024-08-06",
+            TokenUsage::new(1_000_000, 100_000),
+        );
+        assert_eq!(record.cost, Some(3.5));
+
+        ledger.record("thread-a", "llama-3.1-8b", TokenUsage::new(500, 50));
+        ledger.record("thread-b", "gpt-4o-2024-08-06", TokenUsage::new(0, 0));
+
+        let total = ledger.total();
+        assert_eq!(total.calls, 3);
+        assert_eq!(total.prompt_tokens, 1_000_500);
+        assert_eq!(total.completion_tokens, 100_050);
+        assert_eq!(total.cost, Some(3.5));
+
+        assert_eq!(ledger.thread("thread-a").calls, 2);
+        assert_eq!(ledger.thread("thread-b").calls, 1);
+        assert_eq!(ledger.model("llama-3.1-8b").cost, None);
+        assert_eq!(ledger.model("gpt-4o-2024-08-06").calls, 2);
+        assert_eq!(ledger.thread("thread-c"), UsageTotals::default());
+    }
+}
@@ -1,5 +1,6 @@
 use std::{error::Error, fmt::Display};
 
+use serde_json::json;
 use thiserror::Error;
 
 //-------------------------------------------------------------------------------------------------
@@ -16,6 +17,22 @@ pub enum ToolError {
     #[error("The tool failed to execute: {0}")]
     ExecutionFailed(String),
 
+    /// No tool with the given name is registered.
+    #[error("No tool named `{0}`")]
+    NotFound(String),
+
+    /// The action is not a tool invocation that can be made sense of.
+    #[error("Invalid action: {0}")]
+    InvalidAction(String),
+
+    /// The tool was called with invalid arguments.
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(String),
+
+    /// The tool call was cancelled before it finished.
+    #[error("The tool call was cancelled")]
+    Cancelled,
+
     /// The tool failed to parse.
     #[error("The tool failed to parse: {0}")]
     ParseFailed(#[from] serde_json::Error),
@@ -46,6 +63,25 @@ impl ToolError {
             error: error.into(),
         })
     }
+
+    /// Returns a short, stable name for the kind of error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ToolError::ExecutionFailed(_) => "execution_failed",
+            ToolError::NotFound(_) => "unknown_tool",
+            ToolError::InvalidAction(_) => "invalid_action",
+            ToolError::InvalidArguments(_) => "invalid_arguments",
+            ToolError::Cancelled => "cancelled",
+            ToolError::ParseFailed(_) => "parse_failed",
+            ToolError::SqliteConnectionError(_) => "execution_failed",
+            ToolError::Custom(_) => "execution_failed",
+        }
+    }
+
+    /// Renders the error as a JSON observation the agent can react to.
+    pub fn to_observation(&self) -> String {
+        json!({ "error": { "kind": self.kind(), "message": self.to_string() } }).to_string()
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
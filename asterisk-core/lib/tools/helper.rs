@@ -1,16 +1,214 @@
 use serde_json::{Map, Value};
 
-use super::ToolResult;
+use super::{ToolError, ToolResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The shape a tool invocation is expected to have, used in parse errors.
+const EXPECTED_INVOCATION: &str = r#"expected an object like {"name":"<tool>","args":{...}}"#;
 
 //--------------------------------------------------------------------------------------------------
 // Exports
 //--------------------------------------------------------------------------------------------------
 
 /// Parses the tool invocation.
+///
+/// Models don't always produce clean JSON, so the parser is lenient: the invocation may be wrapped
+/// in a code fence or surrounded by other text, may use single quotes, and may leave out `args`
+/// (or call it `arguments`) when the tool takes none. Anything it still can't make sense of is
+/// reported as `ToolError::InvalidAction`.
 pub fn parse_tool(tool: &str) -> ToolResult<(String, Map<String, Value>)> {
-    let json: Value = serde_json::from_str(tool)?;
-    let tool_name = json["name"].as_str().unwrap().to_string();
-    let tool_args = json["args"].as_object().unwrap().clone();
+    let candidate = extract_object(strip_code_fence(tool)).ok_or_else(|| {
+        ToolError::InvalidAction(format!("no JSON object found, {EXPECTED_INVOCATION}"))
+    })?;
+
+    let json: Value = serde_json::from_str(candidate)
+        .or_else(|error| {
+            serde_json::from_str(&single_to_double_quotes(candidate)).map_err(|_| error)
+        })
+        .map_err(|error| ToolError::InvalidAction(format!("{error}, {EXPECTED_INVOCATION}")))?;
+
+    let tool_name = json
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| ToolError::InvalidAction(format!("missing `name`, {EXPECTED_INVOCATION}")))?
+        .to_string();
+
+    let tool_args = match json.get("args").or_else(|| json.get("arguments")) {
+        None | Some(Value::Null) => Map::new(),
+        Some(Value::Object(args)) => args.clone(),
+        // Some models send the arguments as a JSON-encoded string.
+        Some(Value::String(args)) => match serde_json::from_str(args) {
+            Ok(Value::Object(args)) => args,
+            _ => {
+                return Err(ToolError::InvalidAction(format!(
+                    "`args` must be an object, {EXPECTED_INVOCATION}"
+                )))
+            }
+        },
+        Some(_) => {
+            return Err(ToolError::InvalidAction(format!(
+                "`args` must be an object, {EXPECTED_INVOCATION}"
+            )))
+        }
+    };
 
     Ok((tool_name, tool_args))
 }
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the content of the first code fence in the text, or the text itself if there is none.
+fn strip_code_fence(text: &str) -> &str {
+    let Some(start) = text.find("```") else {
+        return text;
+    };
+
+    // Skip the language tag, if any.
+    let content = &text[start + 3..];
+    let content = match content.find('\n') {
+        Some(newline) if !content[..newline].contains('{') => &content[newline + 1..],
+        _ => content,
+    };
+
+    match content.find("```") {
+        Some(end) => &content[..end],
+        None => content,
+    }
+}
+
+/// Returns the first balanced `{...}` in the text, ignoring braces inside strings.
+fn extract_object(text: &str) -> Option<&str> {
+    let start = text.find('{')?;
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (offset, c) in text[start..].char_indices() {
+        if let Some(q) = quote {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                _ if c == q => quote = None,
+                _ => {}
+            }
+
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[start..=start + offset]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Rewrites single-quoted strings as double-quoted ones, escaping any double quotes inside them.
+fn single_to_double_quotes(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut quote = None;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        match quote {
+            Some(q) if escaped => {
+                escaped = false;
+                // `\'` needs no escaping once the string is double-quoted.
+                if !(q == '\'' && c == '\'') {
+                    output.push('\\');
+                }
+                output.push(c);
+            }
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => {
+                quote = None;
+                output.push('"');
+            }
+            Some('\'') if c == '"' => output.push_str("\\\""),
+            Some(_) => output.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                output.push('"');
+            }
+            None => output.push(c),
+        }
+    }
+
+    output
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_tools_parse_tool() {
+        let parse = |tool: &str| {
+            let (name, args) = parse_tool(tool).unwrap();
+            (name, Value::Object(args))
+        };
+
+        let expected = (
+            "response_channel".to_string(),
+            json!({ "message": "It's 1.5, {roughly}" }),
+        );
+
+        assert_eq!(
+            parse(r#"{"name":"response_channel","args":{"message":"It's 1.5, {roughly}"}}"#),
+            expected
+        );
+        assert_eq!(
+            parse("```json\n{\"name\":\"response_channel\",\"args\":{\"message\":\"It's 1.5, {roughly}\"}}\n```"),
+            expected
+        );
+        assert_eq!(
+            parse(
+                r#"Sure! {"name":"response_channel","args":{"message":"It's 1.5, {roughly}"}} Let me know."#
+            ),
+            expected
+        );
+        assert_eq!(
+            parse(r#"{'name': 'response_channel', 'args': {'message': 'It\'s 1.5, {roughly}'}}"#),
+            expected
+        );
+        assert_eq!(
+            parse(r#"{"name":"inbox"}"#),
+            ("inbox".to_string(), json!({}))
+        );
+        assert_eq!(
+            parse(r#"{"name":"inbox","arguments":"{}"}"#),
+            ("inbox".to_string(), json!({}))
+        );
+
+        for tool in [
+            "I will read the inbox now.",
+            r#"{"args":{}}"#,
+            r#"{"name":"inbox","args":[1]}"#,
+            r#"{"name":"inbox","args":{"#,
+        ] {
+            assert!(
+                matches!(parse_tool(tool), Err(ToolError::InvalidAction(_))),
+                "{tool}"
+            );
+        }
+    }
+}
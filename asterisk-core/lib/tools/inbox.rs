@@ -1,17 +1,31 @@
 //! This module contains the tools for the dreamer agent.
 
+use std::sync::{Arc, RwLock};
+
+use futures::future::{self, BoxFuture};
 use serde_json::{Map, Value};
+use tokio_util::sync::CancellationToken;
 
 use super::{Tool, ToolError, ToolResult};
 
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The name of the inbox tool.
+pub const INBOX_TOOL_NAME: &str = "inbox";
+
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
 /// The tool for scanning the user message.
-#[derive(Default)]
+///
+/// Clones of an inbox share the same message, so the agent can keep a handle to update it while
+/// the tool itself sits in a `ToolRegistry`.
+#[derive(Debug, Clone, Default)]
 pub struct Inbox {
-    message: Option<String>,
+    message: Arc<RwLock<Option<String>>>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -20,8 +34,8 @@ pub struct Inbox {
 
 impl Inbox {
     /// Updates the message.
-    pub fn update_message(&mut self, message: String) {
-        self.message = Some(message);
+    pub fn update_message(&self, message: String) {
+        *self.message.write().unwrap() = Some(message);
     }
 }
 
@@ -31,17 +45,24 @@ impl Inbox {
 
 impl Tool for Inbox {
     fn name(&self) -> String {
-        "message_box".to_string()
+        INBOX_TOOL_NAME.to_string()
     }
 
     fn description(&self) -> String {
-        "This tool is used to read the user message".to_string()
+        "Reads the latest user's message from the outside world. Use this to read the user's message."
+            .to_string()
     }
 
-    fn execute(&self, _: Map<String, Value>) -> ToolResult<String> {
-        match &self.message {
+    fn execute(
+        &self,
+        _: Map<String, Value>,
+        _: CancellationToken,
+    ) -> BoxFuture<'_, ToolResult<String>> {
+        let result = match &*self.message.read().unwrap() {
             Some(message) => Ok(message.clone()),
             None => Err(ToolError::custom(anyhow::anyhow!("Message is not set"))),
-        }
+        };
+
+        Box::pin(future::ready(result))
     }
 }
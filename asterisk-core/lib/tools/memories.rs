@@ -1,34 +1,141 @@
-//! This module contains the tools for the dreamer agent.
+//! This module contains the knowledge base tool for the dreamer agent.
 
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, Once},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use rusqlite::Connection;
-use serde_json::{Map, Value};
+use futures::future::BoxFuture;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 
-use super::{Tool, ToolResult};
+use crate::models::EmbeddingModel;
+
+use super::{ToolError, ToolResult, TypedTool};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The name of the knowledge base tool.
+pub const MEMORIES_TOOL_NAME: &str = "knowledge_base";
+
+/// The importance given to memories that don't specify one.
+pub const DEFAULT_IMPORTANCE: u64 = 5;
+
+/// The highest importance a memory can have.
+pub const MAX_IMPORTANCE: u64 = 10;
+
+/// The number of memories returned by `recall` and `list` when no limit is given.
+pub const DEFAULT_LIMIT: usize = 5;
+
+/// The schema migrations, in order. The database's `user_version` is the number applied so far.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE memories (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        value TEXT NOT NULL,
+        importance INTEGER NOT NULL,
+        embedding BLOB,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX memories_importance ON memories (importance DESC, updated_at DESC);
+"#];
+
+//--------------------------------------------------------------------------------------------------
+// Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Turns text into an embedding vector used for similarity search over memories.
+///
+/// Every `EmbeddingModel` is an `Embedder`.
+pub trait Embedder: Send + Sync {
+    /// Embeds the given text.
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, ToolResult<Vec<f32>>>;
+}
 
 //--------------------------------------------------------------------------------------------------
 // Types
 //--------------------------------------------------------------------------------------------------
 
 /// The tool for managing memories.
-#[allow(dead_code)] // TODO: remove this
+///
+/// Memories are stored in SQLite. When an `Embedder` is set, `recall` ranks memories by the cosine
+/// distance between their embeddings and the query using `sqlite-vec`; otherwise it falls back to
+/// matching the query text.
+///
+/// Clones share the same database, so the same memories can back both the tool and, for example,
+/// a context provider.
+#[derive(Clone)]
 pub struct Memories {
     /// The database connection.
-    database: Connection,
+    database: Arc<Mutex<Connection>>,
+
+    /// The embedder used for similarity search.
+    embedder: Option<Arc<dyn Embedder>>,
 }
 
 /// A memory entry.
-#[allow(dead_code)] // TODO: remove this
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct MemoryEntry {
     /// The name of the memory.
-    name: String,
+    pub name: String,
 
     /// The value of the memory.
-    value: String,
+    pub value: String,
 
-    /// The importance of the memory.
-    importance: u64,
+    /// The importance of the memory, from 0 to `MAX_IMPORTANCE`.
+    pub importance: u64,
+
+    /// The cosine distance to the recall query, if the memory was found by similarity.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+
+    /// When the memory was last updated, in seconds since the Unix epoch.
+    #[serde(skip)]
+    pub updated_at: i64,
+}
+
+/// An operation on the knowledge base.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum MemoryOperation {
+    /// Stores a memory, replacing any existing memory with the same name.
+    Remember {
+        /// A short, unique name for the memory.
+        name: String,
+
+        /// What to remember.
+        value: String,
+
+        /// How important the memory is, from 0 to 10. Defaults to 5.
+        importance: Option<u64>,
+    },
+
+    /// Finds the memories most relevant to a query.
+    Recall {
+        /// What to look for.
+        query: String,
+
+        /// The maximum number of memories to return. Defaults to 5.
+        limit: Option<usize>,
+    },
+
+    /// Deletes a memory.
+    Forget {
+        /// The name of the memory to delete.
+        name: String,
+    },
+
+    /// Lists the most important memories.
+    List {
+        /// The maximum number of memories to return. Defaults to 5.
+        limit: Option<usize>,
+    },
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -38,34 +145,304 @@ pub struct MemoryEntry {
 impl Memories {
     /// Creates a new memory tool with an in-memory database.
     pub fn new() -> ToolResult<Self> {
+        register_sqlite_vec();
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Creates a new memory tool with a database at the given path, so memories survive restarts.
+    pub fn with_path(path: impl AsRef<Path>) -> ToolResult<Self> {
+        register_sqlite_vec();
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Sets the embedder used for similarity search.
+    pub fn embedder(mut self, embedder: impl Embedder + 'static) -> Self {
+        self.embedder = Some(Arc::new(embedder));
+        self
+    }
+
+    /// Whether an embedder is set, so `recall` ranks memories by similarity.
+    pub fn has_embedder(&self) -> bool {
+        self.embedder.is_some()
+    }
+
+    /// Stores a memory, replacing any existing memory with the same name.
+    pub async fn remember(&self, name: &str, value: &str, importance: u64) -> ToolResult<()> {
+        if importance > MAX_IMPORTANCE {
+            return Err(ToolError::InvalidArguments(format!(
+                "importance must be between 0 and {MAX_IMPORTANCE}"
+            )));
+        }
+
+        let embedding = match &self.embedder {
+            Some(embedder) => Some(to_blob(&embedder.embed(&format!("{name}: {value}")).await?)),
+            None => None,
+        };
+
+        let (name, value) = (name.to_string(), value.to_string());
+        let now = now();
+        self.with_database(move |database| {
+            database.execute(
+                "INSERT INTO memories (name, value, importance, embedding, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+                 ON CONFLICT (name) DO UPDATE SET
+                    value = excluded.value,
+                    importance = excluded.importance,
+                    embedding = excluded.embedding,
+                    updated_at = excluded.updated_at",
+                params![name, value, importance, embedding, now],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Finds the memories most relevant to the query.
+    pub async fn recall(&self, query: &str, limit: usize) -> ToolResult<Vec<MemoryEntry>> {
+        let Some(embedder) = &self.embedder else {
+            let pattern = format!("%{}%", escape_like(query.trim()));
+            return self
+                .with_database(move |database| {
+                    let mut statement = database.prepare(
+                        "SELECT name, value, importance, NULL, updated_at FROM memories
+                         WHERE name LIKE ?1 ESCAPE '\\' OR value LIKE ?1 ESCAPE '\\'
+                         ORDER BY importance DESC, updated_at DESC
+                         LIMIT ?2",
+                    )?;
+
+                    let entries = statement
+                        .query_map(params![pattern, limit], MemoryEntry::from_row)?
+                        .collect::<Result<_, _>>()?;
+
+                    Ok(entries)
+                })
+                .await;
+        };
+
+        let embedding = embedder.embed(query).await?;
+        self.with_database(move |database| {
+            let mut statement = database.prepare(
+                "SELECT name, value, importance, vec_distance_cosine(embedding, ?1) AS distance,
+                        updated_at
+                 FROM memories
+                 WHERE embedding IS NOT NULL AND vec_length(embedding) = ?2
+                 ORDER BY distance ASC, importance DESC
+                 LIMIT ?3",
+            )?;
+
+            let entries = statement
+                .query_map(
+                    params![to_blob(&embedding), embedding.len(), limit],
+                    MemoryEntry::from_row,
+                )?
+                .collect::<Result<_, _>>()?;
+
+            Ok(entries)
+        })
+        .await
+    }
+
+    /// Deletes a memory, returning whether it existed.
+    pub async fn forget(&self, name: &str) -> ToolResult<bool> {
+        let name = name.to_string();
+        self.with_database(move |database| {
+            let deleted =
+                database.execute("DELETE FROM memories WHERE name = ?1", params![name])?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    /// Lists the most important memories, most recently updated first among equals.
+    pub async fn list(&self, limit: usize) -> ToolResult<Vec<MemoryEntry>> {
+        self.with_database(move |database| {
+            let mut statement = database.prepare(
+                "SELECT name, value, importance, NULL, updated_at FROM memories
+                 ORDER BY importance DESC, updated_at DESC
+                 LIMIT ?1",
+            )?;
+
+            let entries = statement
+                .query_map(params![limit], MemoryEntry::from_row)?
+                .collect::<Result<_, _>>()?;
+
+            Ok(entries)
+        })
+        .await
+    }
+
+    /// Gets the memory with the given name.
+    pub async fn get(&self, name: &str) -> ToolResult<Option<MemoryEntry>> {
+        let name = name.to_string();
+        self.with_database(move |database| {
+            let entry = database
+                .query_row(
+                    "SELECT name, value, importance, NULL, updated_at FROM memories WHERE name = ?1",
+                    params![name],
+                    MemoryEntry::from_row,
+                )
+                .optional()?;
+
+            Ok(entry)
+        })
+        .await
+    }
+
+    /// Runs an operation and renders its outcome as an observation.
+    pub async fn run(&self, operation: MemoryOperation) -> ToolResult<String> {
+        match operation {
+            MemoryOperation::Remember {
+                name,
+                value,
+                importance,
+            } => {
+                self.remember(&name, &value, importance.unwrap_or(DEFAULT_IMPORTANCE))
+                    .await?;
+                Ok(format!("Remembered `{name}`."))
+            }
+            MemoryOperation::Recall { query, limit } => {
+                let entries = self.recall(&query, limit.unwrap_or(DEFAULT_LIMIT)).await?;
+                Ok(serde_json::to_string(&entries)?)
+            }
+            MemoryOperation::Forget { name } => {
+                if self.forget(&name).await? {
+                    Ok(format!("Forgot `{name}`."))
+                } else {
+                    Err(ToolError::ExecutionFailed(format!(
+                        "no memory named `{name}`"
+                    )))
+                }
+            }
+            MemoryOperation::List { limit } => {
+                let entries = self.list(limit.unwrap_or(DEFAULT_LIMIT)).await?;
+                Ok(serde_json::to_string(&entries)?)
+            }
+        }
+    }
+
+    /// Migrates the database and wraps it in a memory tool.
+    fn from_connection(mut database: Connection) -> ToolResult<Self> {
+        migrate(&mut database)?;
         Ok(Self {
-            database: Connection::open_in_memory()?,
+            database: Arc::new(Mutex::new(database)),
+            embedder: None,
         })
     }
 
-    /// Creates a new memory tool with a database at the given path.
-    pub fn with_path(path: impl AsRef<Path>) -> ToolResult<Self> {
+    /// Runs the given query on the blocking thread pool, as SQLite calls block and the database may
+    /// be on disk.
+    async fn with_database<T>(
+        &self,
+        query: impl FnOnce(&Connection) -> ToolResult<T> + Send + 'static,
+    ) -> ToolResult<T>
+    where
+        T: Send + 'static,
+    {
+        let database = Arc::clone(&self.database);
+        tokio::task::spawn_blocking(move || {
+            let database = database.lock().map_err(|_| {
+                ToolError::ExecutionFailed("the memory database is poisoned".to_string())
+            })?;
+            query(&database)
+        })
+        .await
+        .map_err(ToolError::custom)?
+    }
+}
+
+impl MemoryEntry {
+    /// Reads an entry from a `name, value, importance, distance, updated_at` row.
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
         Ok(Self {
-            database: Connection::open(path)?,
+            name: row.get(0)?,
+            value: row.get(1)?,
+            importance: row.get(2)?,
+            distance: row.get(3)?,
+            updated_at: row.get(4)?,
         })
     }
 }
 
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Registers `sqlite-vec` with every connection opened from now on.
+fn register_sqlite_vec() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        #[allow(clippy::missing_transmute_annotations)]
+        rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(
+            sqlite_vec::sqlite3_vec_init as *const (),
+        )));
+    });
+}
+
+/// Applies the migrations the database hasn't seen yet, each in its own transaction so a failed
+/// migration is rolled back.
+fn migrate(database: &mut Connection) -> ToolResult<()> {
+    let version: usize = database.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = database.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Escapes the `LIKE` wildcards in the given text, to be used with `ESCAPE '\'`.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Encodes an embedding in the little-endian `float32` format `sqlite-vec` expects.
+fn to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+/// Returns the current time as seconds since the Unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
 //--------------------------------------------------------------------------------------------------
 // Trait Implementations
 //--------------------------------------------------------------------------------------------------
 
-impl Tool for Memories {
+impl<T: EmbeddingModel> Embedder for T {
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, ToolResult<Vec<f32>>> {
+        Box::pin(async move {
+            EmbeddingModel::embed(self, text.to_string())
+                .await
+                .map_err(ToolError::custom)
+        })
+    }
+}
+
+impl TypedTool for Memories {
+    type Args = MemoryOperation;
+
     fn name(&self) -> String {
-        "knowledge_base".to_string()
+        MEMORIES_TOOL_NAME.to_string()
     }
 
     fn description(&self) -> String {
-        "This tool is used to manage the knowledge base".to_string()
+        "Manages your long-term knowledge base, which persists across conversations. Use \
+         `remember` to store a fact under a short name, `recall` to find the facts relevant to a \
+         query, `forget` to delete a fact and `list` to see the most important ones."
+            .to_string()
     }
 
-    fn execute(&self, _: Map<String, Value>) -> ToolResult<String> {
-        Ok("".to_string())
+    async fn call(&self, args: MemoryOperation, _: CancellationToken) -> ToolResult<String> {
+        self.run(args).await
     }
 }
 
@@ -75,14 +452,126 @@ impl Tool for Memories {
 
 #[cfg(test)]
 mod tests {
+    use futures::future;
+    use serde_json::{json, Value};
+
+    use crate::tools::Tool;
+
     use super::*;
 
-    #[test]
-    fn test_tools_memory() -> anyhow::Result<()> {
-        let _memory = Memories::new()?;
+    /// An embedder that counts the vowels in the text.
+    struct Vowels;
+
+    impl Embedder for Vowels {
+        fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, ToolResult<Vec<f32>>> {
+            let embedding = "aeiou"
+                .chars()
+                .map(|vowel| text.chars().filter(|c| *c == vowel).count() as f32)
+                .collect();
+
+            Box::pin(future::ready(Ok(embedding)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tools_memories() -> anyhow::Result<()> {
+        let memories = Memories::new()?;
+        let execute = |args: Value| {
+            memories.execute(args.as_object().unwrap().clone(), CancellationToken::new())
+        };
+
+        let args = json!({ "operation": "remember", "name": "user_name", "value": "Steve" });
+        assert_eq!(execute(args).await?, "Remembered `user_name`.");
+        memories.remember("user_city", "Lagos", 8).await?;
+        memories.remember("user_name", "Stephen", 3).await?;
+
+        let listed = memories.list(DEFAULT_LIMIT).await?;
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].name, "user_city");
+        assert_eq!(listed[1].value, "Stephen");
+
+        let recalled = execute(json!({ "operation": "recall", "query": "Lagos" })).await?;
+        let recalled: Value = serde_json::from_str(&recalled)?;
+        assert_eq!(
+            recalled,
+            json!([{ "name": "user_city", "value": "Lagos", "importance": 8 }])
+        );
+
+        assert_eq!(
+            execute(json!({ "operation": "forget", "name": "user_city" })).await?,
+            "Forgot `user_city`."
+        );
+        assert!(matches!(
+            execute(json!({ "operation": "forget", "name": "user_city" })).await,
+            Err(ToolError::ExecutionFailed(_))
+        ));
+        assert!(matches!(
+            execute(
+                json!({ "operation": "remember", "name": "x", "value": "y", "importance": 11 })
+            )
+            .await,
+            Err(ToolError::InvalidArguments(_))
+        ));
+        assert!(matches!(
+            execute(json!({ "operation": "dream" })).await,
+            Err(ToolError::InvalidArguments(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tools_memories_recall_escapes_wildcards() -> anyhow::Result<()> {
+        let memories = Memories::new()?;
+        memories.remember("discount", "50% off", 5).await?;
+        memories.remember("score", "500 points", 5).await?;
+        memories.remember("file", "user_notes.txt", 5).await?;
+
+        let recalled = memories.recall("50%", DEFAULT_LIMIT).await?;
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].name, "discount");
+        assert!(memories
+            .recall("user%notes", DEFAULT_LIMIT)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tools_memories_similarity() -> anyhow::Result<()> {
+        let memories = Memories::new()?.embedder(Vowels);
+        memories.remember("a", "aaaa", 1).await?;
+        memories.remember("u", "uuuu", 9).await?;
+        memories.remember("o", "oooo", 5).await?;
+
+        let recalled = memories.recall("aaa", 2).await?;
+        assert_eq!(recalled[0].name, "a");
+        assert!(recalled[0].distance.unwrap() < recalled[1].distance.unwrap());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tools_memories_with_path() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("memories-{}.db", uuid::Uuid::new_v4()));
+
+        Memories::with_path(&path)?
+            .remember("user_name", "Steve", 5)
+            .await?;
+
+        let memories = Memories::with_path(&path)?;
+        assert_eq!(memories.get("user_name").await?.unwrap().value, "Steve");
+
+        let version: usize = memories
+            .with_database(|database| {
+                Ok(database.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+            })
+            .await?;
+        assert_eq!(version, MIGRATIONS.len());
 
-        // let result = memory.execute(Map::new())?;
-        // assert_eq!(result, "".to_string());
+        drop(memories);
+        std::fs::remove_file(path)?;
 
         Ok(())
     }
@@ -10,6 +10,8 @@ mod traits;
 
 pub mod inbox;
 pub mod memories;
+pub mod outbox;
+pub mod registry;
 
 pub use error::*;
 pub use helper::*;
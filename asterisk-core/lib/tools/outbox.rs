@@ -0,0 +1,170 @@
+//! This module contains the outbox tool that sends the dreamer agent's replies to the user.
+
+use futures::future::{self, BoxFuture};
+use serde_json::{json, Map, Value};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::{Tool, ToolError, ToolResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The name of the outbox tool.
+pub const OUTBOX_TOOL_NAME: &str = "response_channel";
+
+/// The observation returned once a reply has been sent.
+pub const REPLY_SENT_OBSERVATION: &str = "Message sent to the user.";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A reply from the agent to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    /// The message for the user.
+    pub message: String,
+}
+
+/// The tool for sending replies to the user.
+///
+/// Replies are sent as `T` over the channel the outbox is connected to, so an agent can deliver
+/// them alongside the other events it emits.
+pub struct Outbox<T = Reply> {
+    reply_tx: Option<mpsc::UnboundedSender<T>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl<T> Outbox<T> {
+    /// Creates a new outbox connected to the given channel.
+    pub fn new(reply_tx: mpsc::UnboundedSender<T>) -> Self {
+        Self {
+            reply_tx: Some(reply_tx),
+        }
+    }
+
+    /// Connects the outbox to the given channel.
+    pub fn connect(&mut self, reply_tx: mpsc::UnboundedSender<T>) {
+        self.reply_tx = Some(reply_tx);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<T> Default for Outbox<T> {
+    fn default() -> Self {
+        Self { reply_tx: None }
+    }
+}
+
+impl<T> Tool for Outbox<T>
+where
+    T: From<Reply>,
+{
+    fn name(&self) -> String {
+        OUTBOX_TOOL_NAME.to_string()
+    }
+
+    fn description(&self) -> String {
+        "Sends a message to the user in the outside world. Use this to respond to the user always."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "message": {
+                    "type": "string",
+                    "description": "The message for the user."
+                }
+            },
+            "required": ["message"]
+        })
+    }
+
+    fn execute(
+        &self,
+        input: Map<String, Value>,
+        _: CancellationToken,
+    ) -> BoxFuture<'_, ToolResult<String>> {
+        Box::pin(future::ready(self.send(input)))
+    }
+}
+
+impl<T> Outbox<T>
+where
+    T: From<Reply>,
+{
+    /// Sends the reply described by the tool arguments.
+    fn send(&self, input: Map<String, Value>) -> ToolResult<String> {
+        let Some(reply_tx) = &self.reply_tx else {
+            return Err(ToolError::custom(anyhow::anyhow!(
+                "Outbox is not connected"
+            )));
+        };
+
+        let message = input
+            .get("message")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ToolError::InvalidArguments("missing `message` string".to_string()))?;
+
+        reply_tx
+            .send(T::from(Reply {
+                message: message.to_string(),
+            }))
+            .map_err(|_| ToolError::ExecutionFailed("the user is not listening".to_string()))?;
+
+        Ok(REPLY_SENT_OBSERVATION.to_string())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tools_outbox() -> anyhow::Result<()> {
+        let args = |value: Value| value.as_object().unwrap().clone();
+        let cancel = CancellationToken::new;
+
+        let outbox = Outbox::<Reply>::default();
+        assert!(outbox
+            .execute(args(json!({"message": "Hi"})), cancel())
+            .await
+            .is_err());
+
+        let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
+        let outbox = Outbox::<Reply>::new(reply_tx);
+        assert_eq!(
+            outbox
+                .execute(args(json!({"message": "The answer is 1.5"})), cancel())
+                .await?,
+            REPLY_SENT_OBSERVATION
+        );
+        assert_eq!(
+            reply_rx.try_recv()?,
+            Reply {
+                message: "The answer is 1.5".to_string()
+            }
+        );
+
+        assert!(matches!(
+            outbox.execute(args(json!({"text": "Hi"})), cancel()).await,
+            Err(ToolError::InvalidArguments(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! A registry of the tools available to an agent.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+use tokio_util::sync::CancellationToken;
+
+use crate::models::openai;
+
+use super::{Tool, ToolError, ToolResult};
+
+//--------------------------------------------------------------------------------------------------
+// Constants
+//--------------------------------------------------------------------------------------------------
+
+/// The placeholder in a system instruction that is replaced with the rendered tools section.
+pub const TOOLS_PLACEHOLDER: &str = "{{tools}}";
+
+//--------------------------------------------------------------------------------------------------
+// Types
+//--------------------------------------------------------------------------------------------------
+
+/// A collection of tools keyed by `Tool::name()`.
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: BTreeMap<String, Box<dyn Tool + Send + Sync>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Methods
+//--------------------------------------------------------------------------------------------------
+
+impl ToolRegistry {
+    /// Creates a new, empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tool to the registry, replacing any existing tool with the same name.
+    pub fn register(&mut self, tool: impl Tool + Send + Sync + 'static) {
+        self.register_boxed(Box::new(tool));
+    }
+
+    /// Adds a boxed tool to the registry, replacing any existing tool with the same name.
+    pub fn register_boxed(&mut self, tool: Box<dyn Tool + Send + Sync>) {
+        self.tools.insert(tool.name(), tool);
+    }
+
+    /// Gets the tool with the given name.
+    pub fn get(&self, name: &str) -> Option<&(dyn Tool + Send + Sync)> {
+        self.tools.get(name).map(AsRef::as_ref)
+    }
+
+    /// Returns the names of the registered tools in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tools.keys().map(String::as_str)
+    }
+
+    /// Returns the number of registered tools.
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    /// Returns true if no tools are registered.
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Executes the tool with the given name, giving up with `ToolError::Cancelled` as soon as
+    /// `cancel` is cancelled.
+    pub async fn execute(
+        &self,
+        name: &str,
+        args: Map<String, Value>,
+        cancel: CancellationToken,
+    ) -> ToolResult<String> {
+        let tool = self
+            .get(name)
+            .ok_or_else(|| ToolError::NotFound(name.to_string()))?;
+
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(ToolError::Cancelled),
+            result = tool.execute(args, cancel.clone()) => result,
+        }
+    }
+
+    /// Returns the definitions of the registered tools for the OpenAI API.
+    pub fn openai_tools(&self) -> Vec<openai::Tool> {
+        self.tools
+            .values()
+            .map(|tool| openai::Tool::from(tool.as_ref()))
+            .collect()
+    }
+
+    /// Renders the "# Tools" section of a system instruction describing the registered tools.
+    pub fn render(&self) -> String {
+        let mut section = String::from(
+            "# Tools\n\nYou have access to the following tools. Call a tool with an action like \
+             `{\"name\":\"<tool>\",\"args\":{...}}`, where `args` follows the tool's JSON schema:\n",
+        );
+
+        for tool in self.tools.values() {
+            section.push_str(&format!(
+                "\n- `{}`: {}\n\n```json\n{}\n```\n",
+                tool.name(),
+                tool.description(),
+                tool.parameters()
+            ));
+        }
+
+        section
+    }
+
+    /// Inserts the rendered tools section into a system instruction.
+    ///
+    /// The section replaces `TOOLS_PLACEHOLDER` if the instruction has one and is appended to the
+    /// instruction otherwise.
+    pub fn render_into(&self, instruction: &str) -> String {
+        if instruction.contains(TOOLS_PLACEHOLDER) {
+            return instruction.replace(TOOLS_PLACEHOLDER, self.render().trim_end());
+        }
+
+        format!("{}\n\n{}", instruction.trim_end(), self.render())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl FromIterator<Box<dyn Tool + Send + Sync>> for ToolRegistry {
+    fn from_iter<T: IntoIterator<Item = Box<dyn Tool + Send + Sync>>>(iter: T) -> Self {
+        let mut registry = Self::new();
+        for tool in iter {
+            registry.register_boxed(tool);
+        }
+
+        registry
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tools::{inbox::Inbox, outbox::Outbox};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tools_registry() {
+        let inbox = Inbox::default();
+        let mut registry = ToolRegistry::new();
+        registry.register(inbox.clone());
+        registry.register(Outbox::<crate::tools::outbox::Reply>::default());
+
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            vec!["inbox", "response_channel"]
+        );
+
+        inbox.update_message("Hello".to_string());
+        let execute = |name| registry.execute(name, Map::new(), CancellationToken::new());
+        assert_eq!(execute("inbox").await.unwrap(), "Hello");
+        assert!(matches!(
+            execute("calculator").await,
+            Err(ToolError::NotFound(_))
+        ));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(matches!(
+            registry.execute("inbox", Map::new(), cancel).await,
+            Err(ToolError::Cancelled)
+        ));
+
+        let definitions = registry.openai_tools();
+        assert_eq!(definitions[1].function.name, "response_channel");
+
+        let instruction = registry.render_into("# Identity\n\n{{tools}}\n\n### Reminder");
+        assert!(instruction.starts_with("# Identity\n\n# Tools\n"));
+        assert!(instruction.contains("- `inbox`: Reads the latest user's message"));
+        assert!(instruction.contains(r#""required":["message"]"#));
+        assert!(instruction.ends_with("```\n\n### Reminder"));
+    }
+}
@@ -1,12 +1,22 @@
-use serde_json::{Map, Value};
+use futures::{future::BoxFuture, Future};
+use schemars::{gen::SchemaSettings, JsonSchema};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use tokio_util::sync::CancellationToken;
 
-use super::ToolResult;
+use crate::models::openai;
+
+use super::{ToolError, ToolResult};
 
 //--------------------------------------------------------------------------------------------------
 // Traits
 //--------------------------------------------------------------------------------------------------
 
 /// A tool that an agent can use.
+///
+/// Tools run on the same runtime as the agent, so any I/O should be awaited rather than blocked
+/// on. The `cancel` token is cancelled when the agent stops waiting for the result, which tools
+/// that spawn work of their own can use to stop it.
 pub trait Tool {
     /// Returns the name of the tool.
     fn name(&self) -> String;
@@ -14,6 +24,173 @@ pub trait Tool {
     /// Returns the description of the tool.
     fn description(&self) -> String;
 
+    /// Returns the JSON schema of the arguments the tool takes.
+    ///
+    /// Defaults to an object with no properties, for tools that take no arguments.
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
     /// Executes the tool.
-    fn execute(&self, input: Map<String, Value>) -> ToolResult<String>;
+    fn execute(
+        &self,
+        input: Map<String, Value>,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, ToolResult<String>>;
+}
+
+/// A tool whose arguments are deserialized into a typed value.
+///
+/// Every `TypedTool` is a `Tool`, with the argument schema derived from `Args` and arguments that
+/// don't fit it rejected with `ToolError::InvalidArguments`.
+pub trait TypedTool: Send + Sync {
+    /// The arguments the tool takes.
+    type Args: DeserializeOwned + JsonSchema + Send;
+
+    /// Returns the name of the tool.
+    fn name(&self) -> String;
+
+    /// Returns the description of the tool.
+    fn description(&self) -> String;
+
+    /// Calls the tool with the given arguments.
+    fn call(
+        &self,
+        args: Self::Args,
+        cancel: CancellationToken,
+    ) -> impl Future<Output = ToolResult<String>> + Send;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Returns the JSON schema of a type, inlined so it can be handed to a model as is.
+pub fn schema_for<T: JsonSchema>() -> Value {
+    let settings = SchemaSettings::draft07().with(|settings| {
+        settings.inline_subschemas = true;
+        settings.meta_schema = None;
+    });
+
+    let mut schema = serde_json::to_value(settings.into_generator().into_root_schema_for::<T>())
+        .unwrap_or_else(|_| json!({ "type": "object" }));
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("title");
+    }
+
+    schema
+}
+
+//--------------------------------------------------------------------------------------------------
+// Trait Implementations
+//--------------------------------------------------------------------------------------------------
+
+impl<T: TypedTool> Tool for T {
+    fn name(&self) -> String {
+        TypedTool::name(self)
+    }
+
+    fn description(&self) -> String {
+        TypedTool::description(self)
+    }
+
+    fn parameters(&self) -> Value {
+        schema_for::<T::Args>()
+    }
+
+    fn execute(
+        &self,
+        input: Map<String, Value>,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, ToolResult<String>> {
+        Box::pin(async move {
+            let args = serde_json::from_value(Value::Object(input))
+                .map_err(|error| ToolError::InvalidArguments(error.to_string()))?;
+
+            self.call(args, cancel).await
+        })
+    }
+}
+
+impl<T: Tool + ?Sized> From<&T> for openai::Tool {
+    fn from(tool: &T) -> Self {
+        openai::Tool {
+            r#type: openai::ToolType::Function,
+            function: openai::Function {
+                name: tool.name(),
+                description: Some(tool.description()),
+                parameters: Some(tool.parameters()),
+                strict: None,
+            },
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    /// Gets the current weather in a city.
+    struct Weather;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct WeatherArgs {
+        /// The city to get the weather for.
+        city: String,
+
+        /// The unit of the temperature.
+        unit: Option<String>,
+    }
+
+    impl TypedTool for Weather {
+        type Args = WeatherArgs;
+
+        fn name(&self) -> String {
+            "get_weather".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Gets the current weather in a city.".to_string()
+        }
+
+        async fn call(&self, args: WeatherArgs, _: CancellationToken) -> ToolResult<String> {
+            let unit = args.unit.unwrap_or("celsius".to_string());
+            Ok(format!("It is 21 degrees {unit} in {}.", args.city))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tools_typed_tool() -> anyhow::Result<()> {
+        let tool: Box<dyn Tool + Send + Sync> = Box::new(Weather);
+
+        let schema = tool.parameters();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], json!(["city"]));
+        assert_eq!(
+            schema["properties"]["city"]["description"],
+            "The city to get the weather for."
+        );
+
+        let args = json!({ "city": "Tokyo" }).as_object().unwrap().clone();
+        let output = tool.execute(args, CancellationToken::new()).await?;
+        assert_eq!(output, "It is 21 degrees celsius in Tokyo.");
+
+        let args = json!({ "town": "Tokyo" }).as_object().unwrap().clone();
+        assert!(matches!(
+            tool.execute(args, CancellationToken::new()).await,
+            Err(ToolError::InvalidArguments(_))
+        ));
+
+        let definition = openai::Tool::from(tool.as_ref());
+        assert_eq!(definition.function.name, "get_weather");
+        assert_eq!(definition.function.parameters, Some(schema));
+
+        Ok(())
+    }
 }